@@ -18,7 +18,7 @@ post /todos {
   })
 }
 
-set BASE_URL "httas..."
+set BASE_URL "https://example.com"
 post http://lasdf.. {}
 // asdfasdf
 
@@ -26,6 +26,16 @@ fn criterion_benchmark(c: &mut Criterion) {
             let _ = parser.parse();
         })
     });
+
+    // what the language server would redo on every request, without its parse cache
+    let document = SOURCE.repeat(100);
+
+    c.bench_function("parse ast of 100 requests", |b| {
+        b.iter(|| {
+            let mut parser = parser::Parser::new(&document);
+            let _ = parser.parse();
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
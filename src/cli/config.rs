@@ -47,6 +47,17 @@ impl ConfigArgs {
                     );
                 }
             },
+            ConfigCommand::Show { effective } => {
+                if effective {
+                    let config = rested::config::Config::load()?;
+                    println!("{}", serde_json::to_string_pretty(&config)?);
+                } else {
+                    let path = confy::get_configuration_file_path("rested", None)?;
+                    if path.exists() {
+                        println!("{}", std::fs::read_to_string(path)?.trim_end());
+                    }
+                }
+            }
             ConfigCommand::Path {} => {
                 println!(
                     "{}",
@@ -65,6 +76,13 @@ enum ConfigCommand {
         #[command(subcommand)]
         command: ManageScratchDirCommand,
     },
+    /// Print the configurations as they're persisted
+    Show {
+        /// Print the configurations actually in use as json, with defaults filled in for
+        /// whatever isn't persisted
+        #[arg(long)]
+        effective: bool,
+    },
     /// Where these configurations are persisted
     Path {},
 }
@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use rested::interpreter::{
+    environment::Environment,
+    interpret_program_with_options,
+    ir::{Request, RequestMethod},
+    read_program_text, script_dir, InterpretOptions,
+};
+
+#[derive(Debug, Args)]
+pub struct CurlArgs {
+    /// Namespace in which to look for environment variables
+    #[arg(short = 'n', long)]
+    pub namespace: Option<String>,
+
+    /// One or more names of the specific request(s) to export
+    #[arg(short = 'r', long, num_args(1..))]
+    pub request: Option<Vec<String>>,
+
+    /// Path to the script to export. If none is provided, script is read
+    /// from stdin
+    pub file: Option<PathBuf>,
+}
+
+impl CurlArgs {
+    pub fn handle(self, mut env: Environment) -> anyhow::Result<()> {
+        if let Some(ns) = self.namespace {
+            env.select_variables_namespace(ns);
+        }
+
+        let options = InterpretOptions {
+            script_dir: script_dir(self.file.as_deref()),
+            ..Default::default()
+        };
+
+        let code = read_program_text(self.file)?;
+        let program = interpret_program_with_options(&code, &env, options)?;
+
+        // skipped requests aren't in the program at all, so only the names are left to filter by
+        let requests = program.items.iter().filter(|item| {
+            match (
+                &self.request,
+                item.name.as_deref().unwrap_or(&item.request.url),
+            ) {
+                (None, _) => true,
+                (Some(desired), name) => desired.iter().any(|n| n == name),
+            }
+        });
+
+        for item in requests {
+            println!("{}", curl_command(&item.request));
+        }
+
+        Ok(())
+    }
+}
+
+/// A `curl` command line that sends the same method, headers and body to the same url.
+pub fn curl_command(request: &Request) -> String {
+    let mut args = vec!["curl".to_string()];
+
+    match request.method {
+        // curl would wait for a body that never comes with `-X HEAD`
+        RequestMethod::HEAD => args.push("--head".to_string()),
+        RequestMethod::GRAPHQL => args.push("-X POST".to_string()),
+        method => args.push(format!("-X {method}")),
+    }
+
+    for header in request.headers.iter() {
+        args.push(format!(
+            "-H {}",
+            shell_quote(&format!("{}: {}", header.name, header.value))
+        ));
+    }
+
+    if let Some(body) = &request.body {
+        // `--data` would read a body starting with `@` as the name of a file to send instead
+        args.push(format!("--data-raw {}", shell_quote(body)));
+    }
+
+    args.push(shell_quote(&request.url));
+
+    args.join(" ")
+}
+
+/// Quotes the argument for a POSIX shell, unless it's made only of characters that are safe
+/// as they are.
+fn shell_quote(arg: &str) -> String {
+    let is_safe =
+        |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | ':' | '@' | ',');
+
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return arg.to_string();
+    }
+
+    // nothing is special inside single quotes, so a single quote has to end them, be
+    // escaped, then start them again
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rested::{interpreter::environment::Environment, parser::ast::Program};
+
+    use super::{curl_command, shell_quote};
+
+    #[test]
+    fn quotes_only_what_needs_quoting() {
+        assert_eq!(
+            shell_quote("http://localhost:8080/api"),
+            "http://localhost:8080/api"
+        );
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("?a=1&b=$HOME"), "'?a=1&b=$HOME'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn exports_the_method_headers_body_and_url() {
+        let code = r#"
+            post http://localhost/api?a=1&b=2 {
+               header "Authorization" "Bearer t0k3n"
+               body json({ name: "it's me" })
+            }
+
+            head http://localhost/api
+        "#;
+
+        let program = Program::from(code)
+            .interpret(&Environment::in_memory())
+            .unwrap();
+
+        let commands: Vec<_> = program
+            .items
+            .iter()
+            .map(|item| curl_command(&item.request))
+            .collect();
+
+        assert_eq!(
+            commands,
+            [
+                r#"curl -X POST -H 'Authorization: Bearer t0k3n' --data-raw '{"name":"it'\''s me"}' 'http://localhost/api?a=1&b=2'"#,
+                "curl --head http://localhost/api"
+            ]
+        );
+    }
+
+    #[test]
+    fn exports_bodies_starting_with_an_at_sign_as_they_are() {
+        let code = r#"post http://localhost/api { body "@admin" }"#;
+
+        let program = Program::from(code)
+            .interpret(&Environment::in_memory())
+            .unwrap();
+
+        assert_eq!(
+            curl_command(&program.items[0].request),
+            "curl -X POST --data-raw @admin http://localhost/api"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use colored::Colorize;
+use rested::interpreter::{
+    environment::Environment,
+    interpret_program_with_options, read_program_text,
+    runner::{request_id::RequestId, RunOptions, RunResponse},
+    script_dir, InterpretOptions,
+};
+use similar::{ChangeTag, TextDiff};
+
+use super::run::RunFailure;
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// Base url to resolve the pathnames of requests against first
+    #[arg(long, value_parser = parse_base_url)]
+    pub base_a: String,
+
+    /// Base url to resolve the pathnames of requests against second
+    #[arg(long, value_parser = parse_base_url)]
+    pub base_b: String,
+
+    /// Namespace in which to look for environment variables
+    #[arg(short = 'n', long)]
+    pub namespace: Option<String>,
+
+    /// Path to the script to run. If none is provided, script is read
+    /// from stdin
+    pub file: Option<PathBuf>,
+}
+
+impl DiffArgs {
+    pub fn handle(self, mut env: Environment) -> anyhow::Result<()> {
+        if let Some(ns) = self.namespace {
+            env.select_variables_namespace(ns);
+        }
+
+        let script_dir = script_dir(self.file.as_deref());
+
+        let code = read_program_text(self.file)?;
+
+        let run_against = |base_url: &str| -> anyhow::Result<Vec<(RequestId, RunResponse)>> {
+            let run_options = RunOptions {
+                quiet: true,
+                ..Default::default()
+            };
+
+            let options = InterpretOptions {
+                base_url: Some(base_url.to_string()),
+                script_dir: script_dir.clone(),
+                send_bound_requests: Some(run_options.clone()),
+                ..Default::default()
+            };
+
+            let program = interpret_program_with_options(&code, &env, options)
+                .map_err(|e| e.context(RunFailure::Script))?;
+
+            Ok(program.run_ureq_with_options(None, run_options))
+        };
+
+        let responses_a = run_against(&self.base_a)?;
+        let responses_b = run_against(&self.base_b)?;
+
+        let total = responses_a.len();
+        let mut differing = 0;
+
+        for ((id, a), (_, b)) in responses_a.iter().zip(responses_b.iter()) {
+            let label = format!("{} {}", id.method, id.url_or_name).bold();
+
+            let diff = match (a, b) {
+                (RunResponse::Success(a), RunResponse::Success(b)) => diff_bodies(a, b),
+                _ => Some(format!(
+                    "{}\n{}",
+                    describe(&self.base_a, a),
+                    describe(&self.base_b, b)
+                )),
+            };
+
+            match diff {
+                Some(diff) => {
+                    differing += 1;
+                    println!("{label} {}\n{diff}", "differs".red());
+                }
+                None => println!("{label} {}", "same".green()),
+            }
+        }
+
+        if differing > 0 {
+            return Err(RunFailure::Differences { differing, total }.into());
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_base_url(s: &str) -> Result<String, String> {
+    match url::Url::parse(s) {
+        // Without a host, something like "localhost:8080" parses with "localhost" as the scheme
+        Ok(url) if url.has_host() => Ok(s.to_string()),
+        _ => Err("expected a url with a scheme, like \"http://localhost:8080\"".to_string()),
+    }
+}
+
+fn describe(base_url: &str, response: &RunResponse) -> String {
+    match response {
+        RunResponse::Success(_) => format!("{base_url}: succeeded"),
+        RunResponse::ErrorStatus { status, .. } => {
+            format!("{base_url}: responded with status code {status}")
+        }
+        RunResponse::Transport(error) => format!("{base_url}: got no response: {error}"),
+        RunResponse::BodyRead(error) => {
+            format!("{base_url}: failed to read the response body: {error}")
+        }
+        RunResponse::Failure(error) | RunResponse::Assertion(error) => {
+            format!("{base_url}: {error}")
+        }
+    }
+}
+
+/// A line diff of two response bodies, if they differ. Json bodies are compared
+/// pretty-printed, with their keys sorted, so that only the data has to match.
+fn diff_bodies(a: &str, b: &str) -> Option<String> {
+    let (a, b) = (normalize(a), normalize(b));
+
+    if a == b {
+        return None;
+    }
+
+    let diff = TextDiff::from_lines(&a, &b)
+        .iter_all_changes()
+        .map(|change| {
+            let line = change.to_string_lossy();
+            let line = line.trim_end_matches('\n');
+            match change.tag() {
+                ChangeTag::Delete => format!("-{line}").red().to_string(),
+                ChangeTag::Insert => format!("+{line}").green().to_string(),
+                ChangeTag::Equal => format!(" {line}"),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Some(diff)
+}
+
+fn normalize(body: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(json) => serde_json::to_string_pretty(&json).unwrap_or_else(|_| body.to_string()),
+        Err(_) => body.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::diff_bodies;
+
+    #[test]
+    fn json_bodies_are_compared_by_their_data() {
+        assert_eq!(
+            diff_bodies(r#"{"a": 1, "b": [2]}"#, r#"{"b":[2],"a":1}"#),
+            None
+        );
+        assert!(diff_bodies(r#"{"a": 1}"#, r#"{"a": 2}"#).is_some());
+    }
+
+    #[test]
+    fn other_bodies_are_compared_line_by_line() {
+        assert_eq!(diff_bodies("same\n", "same\n"), None);
+
+        colored::control::set_override(false);
+        assert_eq!(
+            diff_bodies("one\ntwo\n", "one\nthree\n").unwrap(),
+            " one\n-two\n+three"
+        );
+    }
+}
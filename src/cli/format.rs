@@ -6,12 +6,20 @@ use std::{
 
 use anyhow::anyhow;
 use clap::Args;
-use rested::{error::ColoredMetaError, parser::ast::Program};
+use rested::{error::ColoredMetaError, fmt::FormattedPrinter, parser::ast::Program};
 
 #[derive(Debug, Args)]
 pub struct FormatArgs {
-    /// Path to the script to format
+    /// Path to the script to format, read from stdin when not given
     pub file: Option<PathBuf>,
+
+    /// Sort the keys of object literals alphabetically
+    #[arg(long)]
+    pub sort_keys: bool,
+
+    /// Wrap arrays and function arguments that don't fit on a line this wide
+    #[arg(long)]
+    pub max_line_width: Option<usize>,
 }
 
 impl FormatArgs {
@@ -24,8 +32,14 @@ impl FormatArgs {
 
         let program = Program::from(&code);
 
+        let mut printer = FormattedPrinter::new().sort_keys(self.sort_keys);
+
+        if let Some(width) = self.max_line_width {
+            printer = printer.max_line_width(width);
+        }
+
         let formatted_text = program
-            .to_formatted_string()
+            .format_with(printer)
             .map_err(|err| anyhow!(ColoredMetaError(&err).to_string()))?;
 
         println!("{}", formatted_text);
@@ -1,5 +1,8 @@
 pub mod config;
+pub mod curl;
+pub mod diff;
 pub mod format;
 pub mod run;
 pub mod scratch;
 pub mod snapshot;
+pub mod tokens;
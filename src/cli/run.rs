@@ -1,18 +1,34 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use anyhow::Context;
-use clap::Args;
+use clap::{Args, ValueEnum};
+use colored::Colorize;
 use rested::interpreter::{
-    environment::Environment, interpret_program, ir, read_program_text,
-    runner::request_id::RequestId,
+    cassette_runner::{CassetteMode, Cassettes},
+    environment::{DotenvPrecedence, EnvLayer, Environment},
+    interpret_program_with_options, ir, read_program_text,
+    runner::{request_id::RequestId, RetryPolicy, RunOptions, RunResponse},
+    script_dir,
+    value::Value,
+    InterpretOptions, Stdin,
 };
+use rested::parser::ast::Program;
 
-#[derive(Debug, Args)]
+#[derive(Debug, Default, Clone, Args)]
 pub struct RunArgs {
     /// Namespace in which to look for environment variables
     #[arg(short = 'n', long)]
     pub namespace: Option<String>,
 
+    /// Namespaces, or paths to env files, to layer over one another in order, later ones taking
+    /// precedence, e.g. `--env staging --env ./secrets.json`. Paths must have a `/` or end
+    /// with `.json`, to tell them apart from namespaces
+    #[arg(long = "env", conflicts_with = "namespace", value_parser = parse_env_layer)]
+    pub envs: Vec<EnvLayer>,
+
     /// One or more names of the specific request(s) to run
     #[arg(short = 'r', long, num_args(1..))]
     pub request: Option<Vec<String>>,
@@ -21,9 +37,83 @@ pub struct RunArgs {
     /// from stdin
     pub file: Option<PathBuf>,
 
+    /// Run every script (.rd file) that git says changed, instead of just one; those staged
+    /// for commit, unless `--since` is given
+    #[arg(long, conflicts_with_all = ["file", "prompt", "list_env"])]
+    pub only_changed: bool,
+
+    /// With `--only-changed`, run the scripts that differ from this git ref, e.g. main
+    #[arg(long, requires = "only_changed")]
+    pub since: Option<String>,
+
     /// Rested will prompt you for which request to pick
     #[arg(long, conflicts_with = "request")]
     pub prompt: bool,
+
+    /// Comma separated response status codes for which to resend a request, e.g 502,503,504
+    #[arg(long, value_delimiter = ',')]
+    pub retry_on: Option<Vec<u16>>,
+
+    /// How many times to resend a request that failed with one of the `--retry-on` status codes
+    #[arg(long, default_value_t = 3, requires = "retry_on")]
+    pub retries: u32,
+
+    /// Print at most this many lines of each response body; `@log` files still get all of it
+    #[arg(long)]
+    pub max_body_lines: Option<usize>,
+
+    /// How to write header names when sending them
+    #[arg(long, value_enum, default_value_t = HeaderCase::AsWritten)]
+    pub header_case: HeaderCase,
+
+    /// Path to a dotenv file whose variables can also be read with `env(..)`
+    #[arg(long)]
+    pub dotenv: Option<PathBuf>,
+
+    /// Let variables from the `--dotenv` file take precedence over the namespaced ones
+    #[arg(long, requires = "dotenv")]
+    pub dotenv_override: bool,
+
+    /// Path to a file whose contents replace the body of the one request given with `--request`
+    #[arg(long, requires = "request")]
+    pub body_file: Option<PathBuf>,
+
+    /// Path to a JSON file with an object to bind to `input` in the script
+    #[arg(long)]
+    pub input: Option<PathBuf>,
+
+    /// Save each response into a cassette file in this folder, to `--replay` later
+    #[arg(long, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Answer requests with the responses in the cassettes in this folder, instead of
+    /// sending them; requests without a cassette are sent and recorded
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+
+    /// Print each request, and the head of its response, as raw HTTP to stderr, with
+    /// credentials masked
+    #[arg(long)]
+    pub trace_requests: bool,
+
+    /// Instead of running anything, list the variables the script reads with `env(..)`, and
+    /// whether they're set in the selected namespace
+    #[arg(long, conflicts_with_all = ["request", "prompt"])]
+    pub list_env: bool,
+
+    /// Evaluate the script, and print each request with its headers and body, without
+    /// sending any of them
+    #[arg(long, conflicts_with_all = ["record", "replay", "trace_requests"])]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Clone, ValueEnum)]
+pub enum HeaderCase {
+    /// Send header names exactly as they're written in the script
+    #[default]
+    AsWritten,
+    /// Canonicalize header names in Train-Case, e.g. `content-type` as `Content-Type`
+    Train,
 }
 
 impl RunArgs {
@@ -32,21 +122,297 @@ impl RunArgs {
             env.select_variables_namespace(ns);
         }
 
+        env.select_layers(&self.envs)?;
+
+        if let Some(path) = self.dotenv {
+            let precedence = if self.dotenv_override {
+                DotenvPrecedence::Higher
+            } else {
+                DotenvPrecedence::Lower
+            };
+            env.load_dotenv(path, precedence)?;
+        }
+
+        let script_dir = script_dir(self.file.as_deref());
+
+        let stdin = match self.file {
+            Some(_) => Stdin::Read,
+            None => Stdin::Script,
+        };
+
         let code = read_program_text(self.file)?;
-        let program = interpret_program(&code, env)?;
 
-        let requests = if self.prompt {
-            Some(prompt_for_selected_request(&program)?)
+        if self.list_env {
+            for name in Program::from(&code).env_vars() {
+                match env.get_variable_value(&name.to_string()) {
+                    Some(_) => println!("{} {}", name, "set".green()),
+                    None => println!("{} {}", name, "missing".red()),
+                }
+            }
+
+            return Ok(());
+        }
+
+        let run_options = RunOptions {
+            retry: self.retry_on.map(|status_codes| RetryPolicy {
+                status_codes: status_codes.into(),
+                retries: self.retries,
+                ..Default::default()
+            }),
+            max_body_lines: self.max_body_lines,
+            canonicalize_headers: matches!(self.header_case, HeaderCase::Train),
+            conditional_cache_path: None,
+            quiet: false,
+            trace_requests: self.trace_requests,
+            env: None,
+            dry_run: self.dry_run,
+            cassettes: match (self.record, self.replay) {
+                (Some(dir), _) => Some(Cassettes {
+                    dir,
+                    mode: CassetteMode::Record,
+                }),
+                (_, Some(dir)) => Some(Cassettes {
+                    dir,
+                    mode: CassetteMode::Replay,
+                }),
+                _ => None,
+            },
+        };
+
+        let input = self.input.map(|path| read_input(&path)).transpose()?;
+
+        let interpret = |send_bound_requests, only_send_bound_requests_used_by| {
+            let options = InterpretOptions {
+                input: input.clone(),
+                script_dir: script_dir.clone(),
+                send_bound_requests,
+                only_send_bound_requests_used_by,
+                stdin,
+                ..Default::default()
+            };
+
+            interpret_program_with_options(&code, &env, options)
+                .map_err(|e| e.context(RunFailure::Script))
+        };
+
+        // a first pass, that sends nothing, picks out the selected requests, so that only the
+        // bound requests they use are sent
+        let (requests, used_by) = if self.prompt || self.request.is_some() {
+            let program = interpret(None, None)?;
+
+            let requests = match &self.request {
+                Some(requests) if !self.prompt => requests.clone(),
+                _ => prompt_for_selected_request(&program)?,
+            };
+
+            let spans = program
+                .items
+                .iter()
+                .filter(|item| {
+                    let name = item.name.as_deref().unwrap_or(&item.request.url);
+                    requests.iter().any(|r| r == name)
+                })
+                .map(|item| item.span.start)
+                .collect();
+
+            (Some(requests), Some(spans))
         } else {
-            self.request
+            (None, None)
         };
 
-        program.run_ureq(requests.as_deref());
+        // so that they're sent just like the rest, recorded, traced and retried alike
+        let mut program = interpret(Some(run_options.clone()), used_by)?;
+
+        if let Some(path) = &self.body_file {
+            let body = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read body file {}", path.display()))?;
+
+            override_body(
+                &mut program,
+                self.request.as_deref().unwrap_or_default(),
+                body,
+            )?;
+        }
+
+        let options = RunOptions {
+            env: Some(env),
+            ..run_options
+        };
+
+        let responses = program.run_ureq_with_options(requests.as_deref(), options);
+
+        let failed = responses
+            .iter()
+            .filter(|(_, res)| !res.is_success() && !matches!(res, RunResponse::Assertion(_)))
+            .count();
+
+        if failed > 0 {
+            return Err(RunFailure::Requests {
+                failed,
+                total: responses.len(),
+            }
+            .into());
+        }
+
+        let failed_assertions = responses
+            .iter()
+            .filter(|(_, res)| matches!(res, RunResponse::Assertion(_)))
+            .count();
+
+        if failed_assertions > 0 {
+            return Err(RunFailure::Assertions {
+                failed: failed_assertions,
+                total: responses.len(),
+            }
+            .into());
+        }
 
         Ok(())
     }
 }
 
+fn parse_env_layer(s: &str) -> Result<EnvLayer, String> {
+    if s.contains('/') || s.contains(std::path::MAIN_SEPARATOR) || s.ends_with(".json") {
+        Ok(EnvLayer::File(s.into()))
+    } else {
+        Ok(EnvLayer::Namespace(s.to_string()))
+    }
+}
+
+/// Why a run failed, which decides the exit code of the process.
+#[derive(Debug)]
+pub enum RunFailure {
+    /// Some requests couldn't be sent, or were responded to with an error status.
+    Requests { failed: usize, total: usize },
+    /// The script has syntax errors, or failed to evaluate.
+    Script,
+    /// Some requests got different responses from the two base urls being compared.
+    Differences { differing: usize, total: usize },
+    /// Some responses failed their assertions, like `@expect(..)` or `@assert_contains(..)`.
+    Assertions { failed: usize, total: usize },
+}
+
+impl RunFailure {
+    /// 1 for failed requests or differing responses, 2 for a bad script, and 3 for failed
+    /// assertions.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            RunFailure::Requests { .. } | RunFailure::Differences { .. } => 1,
+            RunFailure::Script => 2,
+            RunFailure::Assertions { .. } => 3,
+        }
+    }
+
+    /// How bad the failure is, to report the worst of many: a bad script is worse than failed
+    /// requests or differing responses, which are worse than failed assertions.
+    pub fn severity(&self) -> u8 {
+        match self {
+            RunFailure::Assertions { .. } => 0,
+            RunFailure::Requests { .. } | RunFailure::Differences { .. } => 1,
+            RunFailure::Script => 2,
+        }
+    }
+
+    /// Like [Self::severity], where errors that aren't a [RunFailure] are as bad as failed
+    /// requests, exiting with 1 like them.
+    pub fn severity_of(error: &anyhow::Error) -> u8 {
+        error
+            .downcast_ref::<RunFailure>()
+            .map(RunFailure::severity)
+            .unwrap_or(1)
+    }
+}
+
+impl std::error::Error for RunFailure {}
+
+impl std::fmt::Display for RunFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunFailure::Requests { failed, total } => {
+                write!(f, "{failed} out of {total} requests failed")
+            }
+            RunFailure::Script => f.write_str("failed to interpret the script"),
+            RunFailure::Differences { differing, total } => {
+                write!(f, "{differing} out of {total} responses differ")
+            }
+            RunFailure::Assertions { failed, total } => {
+                write!(
+                    f,
+                    "{failed} out of {total} responses failed their assertions"
+                )
+            }
+        }
+    }
+}
+
+/// Scripts that git says changed under `dir`, excluding deleted ones: those staged for commit,
+/// or with `since`, those that differ from that ref.
+pub fn changed_scripts(dir: &Path, since: Option<&str>) -> anyhow::Result<Vec<PathBuf>> {
+    let mut git = std::process::Command::new("git");
+    git.current_dir(dir)
+        .args(["diff", "--name-only", "--relative", "--diff-filter=d"]);
+
+    match since {
+        Some(base) => git.arg(base),
+        None => git.arg("--cached"),
+    };
+
+    let output = git.output().context("failed to run git")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "failed to list changed scripts: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|path| path.ends_with(".rd"))
+        .map(|path| dir.join(path))
+        .collect())
+}
+
+/// Reads the JSON object given with `--input`.
+fn read_input(path: &PathBuf) -> anyhow::Result<Value> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read input file {}", path.display()))?;
+
+    let json: serde_json::Value = serde_json::from_str(&text)
+        .with_context(|| format!("failed to parse input file {} as JSON", path.display()))?;
+
+    if !json.is_object() {
+        anyhow::bail!("input file {} must contain a JSON object", path.display());
+    }
+
+    Ok(json.into())
+}
+
+/// Replaces the body of the one request with the given name, or url when it has none, for
+/// `--body-file`.
+fn override_body(program: &mut ir::Program, names: &[String], body: String) -> anyhow::Result<()> {
+    let [name] = names else {
+        anyhow::bail!("--body-file needs exactly one --request whose body to replace");
+    };
+
+    let mut matching = program
+        .items
+        .iter_mut()
+        .filter(|item| item.name.as_deref().unwrap_or(&item.request.url) == name);
+
+    let item = matching
+        .next()
+        .with_context(|| format!("there's no request named {name:?} to replace the body of"))?;
+
+    if matching.next().is_some() {
+        anyhow::bail!("more than one request is named {name:?}, so --body-file can't tell whose body to replace");
+    }
+
+    item.request.body = Some(body);
+
+    Ok(())
+}
+
 fn prompt_for_selected_request(program: &ir::Program) -> anyhow::Result<Vec<String>> {
     let request_names: Vec<_> = program
         .items
@@ -79,3 +445,127 @@ fn prompt_for_selected_request(program: &ir::Program) -> anyhow::Result<Vec<Stri
 
     return Ok(selected_items);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use rested::{interpreter::environment::Environment, parser::ast::Program};
+
+    use super::{changed_scripts, override_body, parse_env_layer, RunFailure};
+
+    #[test]
+    fn lists_only_staged_scripts() {
+        let dir = std::env::temp_dir().join("rested_lists_only_staged_scripts");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let git = |args: &[&str]| {
+            assert!(Command::new("git")
+                .current_dir(&dir)
+                .args(args)
+                .output()
+                .unwrap()
+                .status
+                .success())
+        };
+
+        git(&["init", "-q"]);
+
+        for file in ["staged.rd", "unstaged.rd", "notes.txt"] {
+            std::fs::write(dir.join(file), "get /").unwrap();
+        }
+
+        git(&["add", "staged.rd", "notes.txt"]);
+
+        assert_eq!(
+            changed_scripts(&dir, None).unwrap(),
+            [dir.join("staged.rd")]
+        );
+    }
+
+    #[test]
+    fn env_layers_are_files_only_when_written_as_paths() {
+        use rested::interpreter::environment::EnvLayer;
+
+        assert_eq!(
+            parse_env_layer("staging"),
+            Ok(EnvLayer::Namespace("staging".to_string()))
+        );
+        assert_eq!(
+            parse_env_layer("ci.env.rd.json"),
+            Ok(EnvLayer::File("ci.env.rd.json".into()))
+        );
+        assert_eq!(
+            parse_env_layer("./secrets"),
+            Ok(EnvLayer::File("./secrets".into()))
+        );
+    }
+
+    #[test]
+    fn bad_scripts_are_the_worst_failures() {
+        let failure = |failure: RunFailure| anyhow::Error::from(failure).context("in \"a.rd\"");
+
+        let script = failure(RunFailure::Script);
+        let requests = failure(RunFailure::Requests {
+            failed: 1,
+            total: 2,
+        });
+        let assertions = failure(RunFailure::Assertions {
+            failed: 1,
+            total: 2,
+        });
+
+        assert!(RunFailure::severity_of(&script) > RunFailure::severity_of(&requests));
+        assert!(RunFailure::severity_of(&requests) > RunFailure::severity_of(&assertions));
+        assert_eq!(
+            RunFailure::severity_of(&anyhow::anyhow!("no such file")),
+            RunFailure::severity_of(&requests)
+        );
+    }
+
+    #[test]
+    fn errors_outside_of_a_git_repository() {
+        assert!(changed_scripts(std::path::Path::new("/"), None).is_err());
+    }
+
+    #[test]
+    fn overrides_the_body_of_exactly_one_request() {
+        let env = Environment::in_memory();
+
+        let code = r#"
+            @name("create")
+            post http://localhost/items { body "old" }
+
+            @name("twice")
+            @each([1, 2])
+            post http://localhost/items { body "old" }
+
+            post http://localhost/other { body "old" }
+        "#;
+
+        let program = Program::from(code);
+        let mut program = program.interpret(&env).unwrap();
+
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        override_body(&mut program, &names(&["create"]), "new".into()).unwrap();
+        override_body(
+            &mut program,
+            &names(&["http://localhost/other"]),
+            "url".into(),
+        )
+        .unwrap();
+
+        let bodies: Vec<_> = program
+            .items
+            .iter()
+            .map(|item| item.request.body.as_deref().unwrap())
+            .collect();
+        assert_eq!(bodies, ["new", "old", "old", "url"]);
+
+        assert!(override_body(&mut program, &names(&["twice"]), "new".into()).is_err());
+        assert!(override_body(&mut program, &names(&["missing"]), "new".into()).is_err());
+        assert!(override_body(&mut program, &names(&["create", "twice"]), "new".into()).is_err());
+    }
+}
@@ -183,6 +183,7 @@ impl ScratchCommandArgs {
                         namespace: namespace.clone(),
                         file: Some(file_name),
                         prompt: *prompt,
+                        ..Default::default()
                     }
                     .handle(env)?;
                 }
@@ -223,6 +224,7 @@ impl ScratchCommandArgs {
                         namespace: self.namespace.clone(),
                         file: Some(file_name),
                         prompt: false,
+                        ..Default::default()
                     }
                     .handle(env)?;
                 }
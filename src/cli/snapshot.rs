@@ -1,11 +1,12 @@
 use std::path::PathBuf;
 
+use super::curl::curl_command;
 use clap::{Args, ValueEnum};
 use rested::interpreter::{
     environment::Environment,
-    interpret_program,
+    interpret_program_with_options,
     ir::{LogDestination, RequestItem},
-    read_program_text,
+    read_program_text, script_dir, InterpretOptions,
 };
 
 #[derive(Debug, Args)]
@@ -15,6 +16,10 @@ pub struct SnapshotArgs {
 
     /// Path to the script to snapshot
     pub file: Option<PathBuf>,
+
+    /// Leave missing env variables as `${env:NAME}` placeholders instead of failing
+    #[arg(long)]
+    pub partial: bool,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -24,8 +29,14 @@ pub enum Format {
 
 impl SnapshotArgs {
     pub fn handle(self, env: Environment) -> anyhow::Result<()> {
+        let options = InterpretOptions {
+            partial: self.partial,
+            script_dir: script_dir(self.file.as_deref()),
+            ..Default::default()
+        };
+
         let code = read_program_text(self.file)?;
-        let program = interpret_program(&code, env)?;
+        let program = interpret_program_with_options(&code, &env, options)?;
 
         for item in program.items.iter() {
             println!("{}\n", item.to_curl_string());
@@ -51,21 +62,12 @@ impl ToCurlString for RequestItem {
             buffer.push_str(&format!("echo {}", name))
         }
 
-        buffer.push_str(&format!("curl -X {} ", self.request.method));
-
-        for header in self.request.headers.iter() {
-            buffer.push_str("-H ");
-            buffer.push_str(&format!("\"{}: {}\" ", header.name, header.value));
-        }
-
-        if let Some(body) = &self.request.body {
-            buffer.push_str(&format!("-d '{}' ", body));
-        }
-
-        buffer.push_str(&self.request.url);
+        buffer.push_str(&curl_command(&self.request));
 
         if let Some(dest) = &self.log_destination {
             match dest {
+                LogDestination::Std => {}
+                LogDestination::Stderr => buffer.push_str(" 1>&2"),
                 LogDestination::File(path) => {
                     buffer.push_str(&format!(" 1> {}", path.to_string_lossy()))
                 }
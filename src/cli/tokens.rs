@@ -0,0 +1,28 @@
+use std::{
+    fs,
+    io::{stdin, Read},
+    path::PathBuf,
+};
+
+use clap::Args;
+use rested::lexer::tokenize;
+
+#[derive(Debug, Args)]
+pub struct TokensArgs {
+    /// Path to the script to tokenize
+    pub file: Option<PathBuf>,
+}
+
+impl TokensArgs {
+    pub fn handle(self) -> anyhow::Result<()> {
+        let code = self.file.map(fs::read_to_string).unwrap_or_else(|| {
+            let mut buf = String::new();
+            stdin().read_to_string(&mut buf)?;
+            Ok(buf)
+        })?;
+
+        println!("{}", serde_json::to_string_pretty(&tokenize(&code))?);
+
+        Ok(())
+    }
+}
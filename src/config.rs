@@ -7,6 +7,7 @@ use tracing::{info, warn};
 use crate::{interpreter::environment::Environment, ENV_FILE_NAME};
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub scratch_dir: PathBuf,
 }
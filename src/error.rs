@@ -42,7 +42,7 @@ impl<'e, EK: Display + std::error::Error + std::clone::Clone>
     }
 
     fn squiggle(&self) -> ColoredString {
-        "\u{2248}".repeat(self.0.span.width()).red()
+        self.0.context.squiggle().red()
     }
 
     fn message(&self) -> Option<ColoredString> {
@@ -57,7 +57,7 @@ impl<'e, EK: Display + std::error::Error + std::clone::Clone>
         self.0.line_below().map(|l| l.normal())
     }
 
-    fn error_start(&self) -> Location {
-        self.0.error_start()
+    fn squiggle_offset(&self) -> usize {
+        self.0.squiggle_offset()
     }
 }
@@ -12,7 +12,8 @@ pub trait ErrorDisplay<D: Display + Deref<Target = str>> {
     fn line(&self) -> D;
     fn line_above(&self) -> Option<D>;
     fn line_below(&self) -> Option<D>;
-    fn error_start(&self) -> Location;
+    /// How many characters into the line the squiggle starts.
+    fn squiggle_offset(&self) -> usize;
     fn squiggle(&self) -> D;
     fn message(&self) -> Option<D>;
 
@@ -25,8 +26,8 @@ pub trait ErrorDisplay<D: Display + Deref<Target = str>> {
         }
 
         writeln!(f, "{}", self.line())?;
-        let error_start = self.error_start();
-        let indent_to_error_location = " ".repeat(error_start.col);
+        let squiggle_offset = self.squiggle_offset();
+        let indent_to_error_location = " ".repeat(squiggle_offset);
 
         writeln!(
             f,
@@ -42,7 +43,7 @@ pub trait ErrorDisplay<D: Display + Deref<Target = str>> {
             writeln!(
                 f,
                 "{}   {}",
-                " ".repeat(error_start.col + location.len()),
+                " ".repeat(squiggle_offset + location.len()),
                 m
             )?
         };
@@ -60,22 +61,61 @@ pub struct ErrorSourceContext {
     above: Option<utils::String>,
     pub line: utils::String,
     below: Option<utils::String>,
+    /// Where the squiggle starts on the line, in characters.
+    // These are worked out from the span, which is serialized along with this already.
+    #[serde(skip)]
+    pub squiggle_offset: usize,
+    /// How many characters of the line the squiggle is under, at least one.
+    #[serde(skip)]
+    pub squiggle_width: usize,
 }
 
 impl ErrorSourceContext {
-    pub fn new(location: &Location, code: &str) -> Self {
-        let line_of_token = location.line;
+    /// The line where `span` ends, with the lines around it, and where on that line to draw
+    /// the squiggle under the span.
+    ///
+    /// Positions are in bytes, but the squiggle is measured in characters, so it lines up
+    /// under text with multibyte characters. A span that starts on an earlier line is only
+    /// underlined from the first non-blank character of its last line.
+    pub fn new(span: Span, code: &str) -> Self {
+        let line_of_token = span.end.line;
         let line_before = line_of_token.checked_sub(1);
         let line_after = line_of_token + 1;
 
         let get_line = |lnum: usize| code.lines().nth(lnum).map(|s| s.to_string());
 
+        let line = get_line(line_of_token).unwrap_or_default();
+
+        let start_col = if span.start.line == span.end.line {
+            span.start.col
+        } else {
+            line.len() - line.trim_start().len()
+        };
+
+        // the end is inclusive, and may be just past the end of the line, like at EOF
+        let end_col = span.end.col.max(start_col) + 1;
+
+        // counting a character that's only partly before `col`, and any columns past the end
+        let chars_up_to = |col: usize| {
+            line.char_indices().take_while(|(i, _)| *i < col).count()
+                + col.saturating_sub(line.len())
+        };
+
+        let squiggle_offset = chars_up_to(start_col);
+        let squiggle_width = chars_up_to(end_col).saturating_sub(squiggle_offset).max(1);
+
         ErrorSourceContext {
             above: line_before.and_then(get_line).map(|line| line.into()),
-            line: get_line(line_of_token).unwrap_or_default().into(),
+            line: line.into(),
             below: get_line(line_after).map(|l| l.into()),
+            squiggle_offset,
+            squiggle_width,
         }
     }
+
+    pub fn squiggle(&self) -> String {
+        "\u{2248}".repeat(self.squiggle_width)
+    }
 }
 
 #[derive(Clone, PartialEq, Serialize)]
@@ -97,7 +137,7 @@ impl<E: Display + std::error::Error + Clone> ContextualError<E> {
         Self {
             inner_error,
             message: None,
-            context: ErrorSourceContext::new(&span.end.into(), source_code),
+            context: ErrorSourceContext::new(span, source_code),
             span,
         }
     }
@@ -113,7 +153,7 @@ pub trait ToContextualError: Display + std::error::Error + Clone {
         ContextualError {
             inner_error: self,
             message: None,
-            context: ErrorSourceContext::new(&span.end.into(), source_code),
+            context: ErrorSourceContext::new(span, source_code),
             span,
         }
     }
@@ -133,7 +173,7 @@ impl<E: Display + std::error::Error + Clone> ErrorDisplay<utils::String> for Con
     }
 
     fn squiggle(&self) -> utils::String {
-        "\u{2248}".repeat(self.span.width()).into()
+        self.context.squiggle().into()
     }
 
     fn message(&self) -> Option<utils::String> {
@@ -148,8 +188,8 @@ impl<E: Display + std::error::Error + Clone> ErrorDisplay<utils::String> for Con
         self.context.below.clone()
     }
 
-    fn error_start(&self) -> Location {
-        self.span.start.into()
+    fn squiggle_offset(&self) -> usize {
+        self.context.squiggle_offset
     }
 }
 
@@ -160,3 +200,35 @@ impl<E: Display + std::error::Error + Clone> std::fmt::Display for ContextualErr
         self.format(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorSourceContext;
+    use crate::lexer::locations::{Position, Span};
+
+    #[test]
+    fn squiggles_under_multibyte_characters_by_character() {
+        let code = r#"let é = "ü""#;
+        // `"ü"` is at bytes 10 to 13
+        let context = ErrorSourceContext::new(
+            Span::new(Position::new(0, 10, 10), Position::new(0, 13, 13)),
+            code,
+        );
+
+        assert_eq!(context.squiggle_offset, 9);
+        assert_eq!(context.squiggle_width, 3);
+    }
+
+    #[test]
+    fn squiggles_only_the_last_line_of_multiline_spans() {
+        let code = "get /a {\n    header \"a\"\n}";
+        let context = ErrorSourceContext::new(
+            Span::new(Position::new(0, 7, 7), Position::new(1, 14, 23)),
+            code,
+        );
+
+        assert_eq!(&*context.line, "    header \"a\"");
+        assert_eq!(context.squiggle_offset, 4);
+        assert_eq!(context.squiggle_width, 11);
+    }
+}
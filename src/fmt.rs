@@ -11,12 +11,52 @@ use crate::{
     utils,
 };
 
+type ObjectItem<'a, 'source> =
+    &'a utils::OneOf<ast::result::ParsedNode<'source, ObjectEntry<'source>>, ast::Literal<'source>>;
+
+/// Orders the items of an object by key, moving each entry along with the line comments
+/// right above it. Comments after the last entry stay at the end.
+fn sorted_object_items<'a, 'source>(
+    entry_list: &'a ast::ObjectEntryList<'source>,
+) -> Vec<ObjectItem<'a, 'source>> {
+    let mut groups: Vec<Vec<ObjectItem>> = vec![];
+    let mut pending_comments = vec![];
+
+    for item in entry_list.items.iter() {
+        pending_comments.push(item);
+
+        if item.this().is_some() {
+            groups.push(std::mem::take(&mut pending_comments));
+        }
+    }
+
+    groups.sort_by_key(|group| {
+        group
+            .last()
+            .and_then(|item| item.this())
+            .and_then(|node| node.get().ok())
+            .and_then(|entry| entry.key.get().ok())
+            .map(|key| key.value)
+    });
+
+    groups
+        .into_iter()
+        .flatten()
+        .chain(pending_comments)
+        .collect()
+}
+
 impl<'source> ast::Program<'source> {
     pub fn to_formatted_string(
         &self,
     ) -> Result<String, Box<error_meta::ContextualError<parser::error::ParseError<'source>>>> {
-        let mut formatter = FormattedPrinter::new();
+        self.format_with(FormattedPrinter::new())
+    }
 
+    pub fn format_with(
+        &self,
+        mut formatter: FormattedPrinter<'source>,
+    ) -> Result<String, Box<error_meta::ContextualError<parser::error::ParseError<'source>>>> {
         self.visit_with(&mut formatter);
 
         if let Some(err) = formatter.error {
@@ -36,6 +76,8 @@ pub struct FormattedPrinter<'source> {
     let_statement_streak: u16,
     line_comment_streak: u16,
     is_after_attribute: bool,
+    sort_keys: bool,
+    max_line_width: Option<usize>,
 }
 
 impl<'source> FormattedPrinter<'source> {
@@ -49,9 +91,25 @@ impl<'source> FormattedPrinter<'source> {
             let_statement_streak: 0,
             line_comment_streak: 0,
             is_after_attribute: false,
+            sort_keys: false,
+            max_line_width: None,
         }
     }
 
+    /// Sort the keys of object literals alphabetically, keeping line comments above the
+    /// entries they precede.
+    pub fn sort_keys(mut self, sort_keys: bool) -> Self {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Put each item of an array or argument list on its own line, when the list doesn't fit
+    /// on a line `width` characters wide.
+    pub fn max_line_width(mut self, width: usize) -> Self {
+        self.max_line_width = Some(width);
+        self
+    }
+
     fn push(&mut self, s: char) {
         self.output.push(s)
     }
@@ -82,10 +140,51 @@ impl<'source> FormattedPrinter<'source> {
         self.indent -= 1;
     }
 
+    /// Whether the line being printed, and the delimiter closing it, is wider than the max line
+    /// width.
+    fn overflows_line(&self) -> bool {
+        let Some(max_line_width) = self.max_line_width else {
+            return false;
+        };
+
+        let line = self.output.rsplit('\n').next().unwrap_or_default();
+
+        line.chars().count() + 1 > max_line_width
+    }
+
     pub fn into_output(self) -> String {
         self.output
     }
 
+    /// Prints the statements of a request block or `headers` declaration between braces, one
+    /// per line and indented, or just `{}` when there are none.
+    fn print_block(&mut self, block: &ast::Block<'source>) {
+        self.push('{');
+        if block.statements.is_empty() {
+            self.push('}');
+            return;
+        }
+        self.new_line();
+
+        let len = block.statements.len();
+        let mut i = 0;
+        for statement in block.statements.iter() {
+            self.push_indent();
+
+            self.visit_statement(statement);
+            i += 1;
+
+            if i < len {
+                self.new_line();
+            }
+
+            self.pop_indent();
+        }
+
+        self.new_line();
+        self.push('}');
+    }
+
     /// Prints one or two new lines when applicable.
     fn handle_new_line_before_item(&mut self, item: &Item) {
         if self.is_first_item {
@@ -147,8 +246,10 @@ impl<'source> Visitor<'source> for FormattedPrinter<'source> {
     }
 
     fn visit_request(&mut self, request: &crate::parser::ast::Request<'source>) {
-        self.push_str(&request.method.to_string().to_lowercase());
-        self.push(' ');
+        if !request.method_is_inferred() {
+            self.push_str(&request.method.to_string().to_lowercase());
+            self.push(' ');
+        }
 
         match &request.endpoint {
             ast::Endpoint::Expr(expr) => self.visit_expr(expr),
@@ -158,34 +259,15 @@ impl<'source> Visitor<'source> for FormattedPrinter<'source> {
 
         if let Some(block) = &request.block {
             self.push(' ');
-
-            self.push('{');
-            if block.statements.is_empty() {
-                self.push('}');
-                return;
-            }
-            self.new_line();
-
-            let len = block.statements.len();
-            let mut i = 0;
-            for statement in block.statements.iter() {
-                self.push_indent();
-
-                self.visit_statement(statement);
-                i += 1;
-
-                if i < len {
-                    self.new_line();
-                }
-
-                self.pop_indent();
-            }
-
-            self.new_line();
-            self.push('}');
+            self.print_block(block);
         }
     }
 
+    fn visit_headers_declaration(&mut self, declaration: &ast::HeadersDeclaration<'source>) {
+        self.push_str("headers ");
+        self.print_block(&declaration.block);
+    }
+
     fn visit_constant_declaration(
         &mut self,
         ConstantDeclaration { identifier, value }: &ConstantDeclaration<'source>,
@@ -218,10 +300,28 @@ impl<'source> Visitor<'source> for FormattedPrinter<'source> {
                 self.push(' ');
                 self.visit_expr(value);
             }
+            ast::Statement::Query { value, name } => {
+                self.push_str("query ");
+                self.visit_parsed_node(name);
+                self.push(' ');
+                self.visit_expr(value);
+            }
             ast::Statement::Body { value, .. } => {
                 self.push_str("body ");
                 self.visit_expr(value);
             }
+            ast::Statement::FormBody { value, .. } => {
+                self.push_str("body form ");
+                self.visit_expr(value);
+            }
+            ast::Statement::GraphqlQuery { value, .. } => {
+                self.push_str("query ");
+                self.visit_expr(value);
+            }
+            ast::Statement::GraphqlVariables { value, .. } => {
+                self.push_str("variables ");
+                self.visit_expr(value);
+            }
             ast::Statement::LineComment(comment) => self.push_str(comment.value),
             ast::Statement::Error(error) => self.visit_error(error),
         }
@@ -264,14 +364,20 @@ impl<'source> Visitor<'source> for FormattedPrinter<'source> {
 
                 self.new_line();
 
-                for (i, item) in entry_list.items.iter().enumerate() {
+                let items = if self.sort_keys {
+                    sorted_object_items(entry_list)
+                } else {
+                    entry_list.items.iter().collect()
+                };
+
+                for (i, item) in items.iter().enumerate() {
                     self.push_indent();
 
                     match item {
                         utils::OneOf::This(node) => {
                             self.visit_parsed_node(node);
 
-                            if i != entry_list.items.len() - 1 {
+                            if i != items.len() - 1 {
                                 self.push_str(",");
                             }
                         }
@@ -298,6 +404,14 @@ impl<'source> Visitor<'source> for FormattedPrinter<'source> {
             }
             Expression::EmptyArray(_) => self.push_str("[]"),
             Expression::EmptyObject(_) => self.push_str("{}"),
+            Expression::Conditional(expr) => {
+                self.visit_expr(&expr.condition);
+                self.push_str(" ? ");
+                self.visit_expr(&expr.consequent);
+                self.push_str(" : ");
+                self.visit_expr(&expr.alternate);
+            }
+            Expression::Request(request) => self.visit_request(request),
             Expression::TemplateStringLiteral { parts, .. } => {
                 self.push('`');
                 for part in parts.iter() {
@@ -339,24 +453,63 @@ impl<'source> Visitor<'source> for FormattedPrinter<'source> {
     }
 
     fn visit_expr_list(&mut self, expr_list: &parser::ast::ExpressionList<'source>) {
-        for (i, item) in expr_list.items.iter().enumerate() {
-            match item {
-                crate::utils::OneOf::This(expr) => {
+        let has_comments = expr_list.items.iter().any(|item| item.that().is_some());
+
+        if !has_comments {
+            let start = self.output.len();
+
+            // Nested lists are kept on one line too, so that when the whole list doesn't fit, it's
+            // the outermost one that gets wrapped first
+            let max_line_width = self.max_line_width.take();
+
+            for (i, item) in expr_list.items.iter().enumerate() {
+                if let Some(expr) = item.this() {
                     self.visit_expr(expr);
 
                     if i != expr_list.items.len() - 1 {
                         self.push_str(", ");
                     }
                 }
-                crate::utils::OneOf::That(comment) => {
-                    self.new_line();
-                    self.put_indentation();
-                    self.visit_line_comment(comment);
-                    self.new_line();
-                    self.put_indentation();
+            }
+
+            self.max_line_width = max_line_width;
+
+            if !self.overflows_line() {
+                return;
+            }
+
+            self.output.truncate(start);
+        }
+
+        // Comments are line comments, so with any of them around, every item gets its own
+        // line, the same as in objects. So does every item of a list too wide for one line.
+        let last_expr = expr_list
+            .items
+            .iter()
+            .rposition(|item| item.this().is_some());
+
+        self.new_line();
+
+        for (i, item) in expr_list.items.iter().enumerate() {
+            self.push_indent();
+
+            match item {
+                crate::utils::OneOf::This(expr) => {
+                    self.visit_expr(expr);
+
+                    if Some(i) != last_expr {
+                        self.push_str(",");
+                    }
                 }
+                crate::utils::OneOf::That(comment) => self.visit_line_comment(comment),
             }
+
+            self.new_line();
+
+            self.pop_indent();
         }
+
+        self.put_indentation();
     }
 
     fn visit_error(
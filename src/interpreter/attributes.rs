@@ -26,6 +26,23 @@ impl<'source, 'p> AttributeStack<'source, 'p> {
         })
     }
 
+    /// Adds an attribute that may be given more than once, unlike with [Self::add].
+    pub fn push(&mut self, id: &'p Token<'source>, params: Option<&'p ExpressionList<'source>>) {
+        self.inner.push(Attribute {
+            identifier: id,
+            params,
+        })
+    }
+
+    pub fn get_all<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a Attribute<'source, 'p>> {
+        self.inner
+            .iter()
+            .filter(move |att| att.identifier.text == name)
+    }
+
     pub fn get(&self, name: &str) -> Option<&Attribute<'source, 'p>> {
         self.inner.iter().find(|att| att.identifier.text == name)
     }
@@ -1,4 +1,9 @@
-use std::{fs::File, io::Read, path::PathBuf};
+use std::{
+    fs::File,
+    io::{stdin, IsTerminal, Read},
+    path::PathBuf,
+    sync::OnceLock,
+};
 
 use anyhow::Context;
 
@@ -15,6 +20,109 @@ pub fn read_file<P: Into<PathBuf>>(file_name: P) -> anyhow::Result<Value> {
     Ok(string.into())
 }
 
+/// All of standard input, read the first time it's asked for and kept for every time after,
+/// since it can only be read once. Nothing is read from a terminal, where it would block until
+/// someone typed out the whole input.
+pub fn read_stdin() -> anyhow::Result<Value> {
+    static STDIN: OnceLock<String> = OnceLock::new();
+
+    if let Some(string) = STDIN.get() {
+        return Ok(string.clone().into());
+    }
+
+    let mut string = String::new();
+
+    if !stdin().is_terminal() {
+        stdin()
+            .read_to_string(&mut string)
+            .context("failed to read stdin")?;
+    }
+
+    Ok(STDIN.get_or_init(|| string).clone().into())
+}
+
+/// The MIME type of a file with one of the common text extensions.
+pub fn content_type_of_file<P: Into<PathBuf>>(file_name: P) -> Option<&'static str> {
+    let file_name = file_name.into();
+    let extension = file_name.extension()?.to_str()?.to_ascii_lowercase();
+
+    let content_type = match extension.as_str() {
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" => "text/plain",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        _ => return None,
+    };
+
+    Some(content_type)
+}
+
+pub fn read_file_bytes_base64<P: Into<PathBuf>>(file_name: P) -> anyhow::Result<Value> {
+    use base64::Engine;
+
+    let bytes = std::fs::read(file_name.into()).context("failed to read a file")?;
+
+    Ok(base64::engine::general_purpose::STANDARD
+        .encode(bytes)
+        .into())
+}
+
+/// The standard base64 encoding of the text, like for a Basic auth header.
+pub fn base64_encode(text: &str) -> Value {
+    use base64::Engine;
+
+    base64::engine::general_purpose::STANDARD
+        .encode(text)
+        .into()
+}
+
+/// Decodes standard base64 into the text it encodes.
+pub fn base64_decode(encoded: &str) -> anyhow::Result<Value> {
+    use base64::Engine;
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .context("not valid base64")?;
+
+    let text = String::from_utf8(bytes).context("the decoded bytes aren't text")?;
+
+    Ok(text.into())
+}
+
+/// The longest string, in bytes, that [repeat_string] will build.
+pub const MAX_REPEATED_STRING_LEN: usize = 64 * 1024 * 1024;
+
+pub fn repeat_string(text: &str, count: usize) -> anyhow::Result<Value> {
+    match text.len().checked_mul(count) {
+        Some(len) if len <= MAX_REPEATED_STRING_LEN => Ok(text.repeat(count).into()),
+        _ => anyhow::bail!(
+            "repeating a string of {} bytes {count} times exceeds the limit of {} bytes",
+            text.len(),
+            MAX_REPEATED_STRING_LEN
+        ),
+    }
+}
+
+/// A random (version 4) UUID, like `0b4f9a0e-5b6c-4c1f-9d2e-3a7b8c9d0e1f`.
+pub fn uuid_v4() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+
+    bytes[6] = (bytes[6] & 0x0f) | 0x40; // version 4
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // RFC 4122 variant
+
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
 pub fn call_env(
     env: &crate::interpreter::environment::Environment,
     variable: &String,
@@ -32,8 +140,55 @@ pub fn escaping_new_lines(text: String) -> Value {
     return s.into();
 }
 
-pub fn json_stringify(value: Value) -> Value {
-    serde_json::to_string(&value)
-      .expect("failed to json stringify this value; even though our parser should have made sure this value is valid")
-      .into()
+/// Writes the value as compact JSON, or indented over multiple lines when `pretty`.
+pub fn json_stringify(value: Value, pretty: bool) -> Value {
+    let json = value.to_json_value();
+
+    if pretty {
+        serde_json::to_string_pretty(&json)
+            .expect("a json value should always serialize")
+            .into()
+    } else {
+        json.to_string().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{base64_decode, base64_encode, content_type_of_file, uuid_v4};
+
+    #[test]
+    fn base64_round_trips() {
+        assert_eq!(base64_encode("user:pass").to_string(), "dXNlcjpwYXNz");
+        assert_eq!(
+            base64_decode("dXNlcjpwYXNz").unwrap().to_string(),
+            "user:pass"
+        );
+        assert!(base64_decode("not base64!").is_err());
+    }
+
+    #[test]
+    fn infers_content_types_from_common_extensions() {
+        assert_eq!(content_type_of_file("data.json"), Some("application/json"));
+        assert_eq!(
+            content_type_of_file("./in/data.XML"),
+            Some("application/xml")
+        );
+        assert_eq!(content_type_of_file("notes.txt"), Some("text/plain"));
+        assert_eq!(content_type_of_file("rows.csv"), Some("text/csv"));
+        assert_eq!(content_type_of_file("page.html"), Some("text/html"));
+        assert_eq!(content_type_of_file("image.png"), None);
+        assert_eq!(content_type_of_file("Makefile"), None);
+    }
+
+    #[test]
+    fn generates_version_4_uuids() {
+        let uuid = uuid_v4();
+
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(uuid.matches('-').count(), 4);
+        assert_eq!(&uuid[14..15], "4");
+        assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"));
+        assert_ne!(uuid, uuid_v4());
+    }
 }
@@ -0,0 +1,135 @@
+use std::error::Error;
+use std::{fs, path::PathBuf};
+
+use tracing::{info, warn};
+
+use super::ir::{Header, Request};
+use super::runner::{Response, RunStrategy};
+
+/// Whether to save responses into cassettes, or to answer requests with them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CassetteMode {
+    /// Send every request, saving each response.
+    Record,
+    /// Answer requests with their saved responses, only sending (and recording) the ones
+    /// that have none yet.
+    Replay,
+}
+
+/// A folder of cassettes, one per method and url.
+#[derive(Debug, Clone)]
+pub struct Cassettes {
+    pub dir: PathBuf,
+    pub mode: CassetteMode,
+}
+
+/// A saved response, keyed by the method and url of its request.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Cassette {
+    method: String,
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+/// Runs requests with another strategy, recording the responses into cassettes, or
+/// replaying them from cassettes instead, for working offline.
+///
+/// Error responses aren't recorded, so replaying requests that failed sends them again.
+pub struct CassetteRun<S> {
+    cassettes: Cassettes,
+    inner: S,
+}
+
+impl<S: RunStrategy> CassetteRun<S> {
+    pub fn new(cassettes: Cassettes, inner: S) -> Self {
+        Self { cassettes, inner }
+    }
+
+    fn path_for(&self, request: &Request) -> PathBuf {
+        let key: String = format!("{}_{}", request.method, request.url)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+
+        self.cassettes.dir.join(key).with_extension("json")
+    }
+
+    fn replay(&self, request: &Request) -> Option<Response> {
+        let path = self.path_for(request);
+
+        let cassette: Cassette = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| {
+                serde_json::from_str(&text)
+                    .map_err(|e| warn!("ignoring the cassette at {path:?}: {e}"))
+                    .ok()
+            })
+            .filter(|c: &Cassette| {
+                // urls that only differ in their special characters share a file
+                c.method == request.method.to_string() && c.url == request.url
+            })?;
+
+        info!("replaying the response from {path:?}");
+
+        Some(Response {
+            status: cassette.status,
+            headers: cassette
+                .headers
+                .into_iter()
+                .map(|(name, value)| Header::new(name, value))
+                .collect(),
+            bytes: request
+                .download
+                .is_some()
+                .then(|| cassette.body.clone().into_bytes()),
+            body: cassette.body,
+            streamed: false,
+            filename: None,
+        })
+    }
+
+    fn record(&self, request: &Request, response: &Response) -> anyhow::Result<()> {
+        let cassette = Cassette {
+            method: request.method.to_string(),
+            url: request.url.clone(),
+            status: response.status,
+            headers: response
+                .headers
+                .iter()
+                .map(|h| (h.name.clone(), h.value.clone()))
+                .collect(),
+            body: match &response.bytes {
+                Some(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                None => response.body.clone(),
+            },
+        };
+
+        fs::create_dir_all(&self.cassettes.dir)?;
+        fs::write(
+            self.path_for(request),
+            serde_json::to_string_pretty(&cassette)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+impl<S: RunStrategy> RunStrategy for CassetteRun<S> {
+    fn run_request(&mut self, request: &Request) -> std::result::Result<Response, Box<dyn Error>> {
+        if self.cassettes.mode == CassetteMode::Replay {
+            if let Some(response) = self.replay(request) {
+                return Ok(response);
+            }
+        }
+
+        let response = self.inner.run_request(request)?;
+
+        if let Err(e) = self.record(request, &response) {
+            warn!("failed to record the response to {}: {e}", request.url);
+        }
+
+        Ok(response)
+    }
+}
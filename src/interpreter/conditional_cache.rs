@@ -0,0 +1,105 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use tracing::warn;
+
+use super::ir::Header;
+
+/// The validators of the last response to a request, which make the next request to the
+/// same url conditional.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct Validators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Remembers the `ETag` and `Last-Modified` headers of responses to `@conditional` requests,
+/// by url, across runs.
+#[derive(Debug)]
+pub struct ConditionalCache {
+    path: Option<PathBuf>,
+    entries: HashMap<String, Validators>,
+}
+
+impl ConditionalCache {
+    /// Next to the config file, if its folder can be found.
+    pub fn default_path() -> Option<PathBuf> {
+        confy::get_configuration_file_path("rested", None)
+            .ok()
+            .and_then(|path| path.parent().map(|dir| dir.join("conditional-cache.json")))
+    }
+
+    /// Loads the cache at `path`, starting over with an empty one if it's missing or
+    /// can't be read. Without a path, the cache only lasts for this run.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = path
+            .as_ref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                fs::read_to_string(path)
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+                    .map_err(|e| warn!("ignoring the conditional requests cache at {path:?}: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        Self { path, entries }
+    }
+
+    /// `If-None-Match` and `If-Modified-Since` headers from the last response to `url`.
+    pub fn conditional_headers(&self, url: &str) -> Vec<Header> {
+        let Some(validators) = self.entries.get(url) else {
+            return vec![];
+        };
+
+        let mut headers = vec![];
+
+        if let Some(etag) = &validators.etag {
+            headers.push(Header::new("If-None-Match".to_string(), etag.clone()));
+        }
+
+        if let Some(last_modified) = &validators.last_modified {
+            headers.push(Header::new(
+                "If-Modified-Since".to_string(),
+                last_modified.clone(),
+            ));
+        }
+
+        headers
+    }
+
+    /// Keeps the validators among the response `headers` for the next request to `url`.
+    pub fn remember(&mut self, url: &str, headers: &[Header]) {
+        let find = |name: &str| {
+            headers
+                .iter()
+                .find(|h| h.name.eq_ignore_ascii_case(name))
+                .map(|h| h.value.clone())
+        };
+
+        let validators = Validators {
+            etag: find("etag"),
+            last_modified: find("last-modified"),
+        };
+
+        if validators.etag.is_none() && validators.last_modified.is_none() {
+            self.entries.remove(url);
+        } else {
+            self.entries.insert(url.to_string(), validators);
+        }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, serde_json::to_string_pretty(&self.entries)?)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use std::error::Error;
+
+use colored::Colorize;
+
+use super::ir::Request;
+use super::runner::{Response, RunStrategy};
+
+/// What every request is "responded" to with, since none of them are sent.
+pub const DRY_RUN_BODY: &str = "(dry run, not sent)";
+
+/// Prints each request as it would be sent, with its method, url, headers and body, without
+/// sending it.
+#[derive(Debug, Default)]
+pub struct DryRun;
+
+impl RunStrategy for DryRun {
+    fn run_request(&mut self, request: &Request) -> std::result::Result<Response, Box<dyn Error>> {
+        println!(
+            "{} {}",
+            request.method.to_string().yellow().bold(),
+            request.url
+        );
+
+        for header in request.headers.iter() {
+            println!("{}: {}", header.name.dimmed(), header.value);
+        }
+
+        if let Some(body) = &request.body {
+            println!("\n{body}");
+        }
+
+        Ok(Response {
+            status: 200,
+            headers: [].into(),
+            body: DRY_RUN_BODY.to_string(),
+            streamed: false,
+            bytes: None,
+            filename: None,
+        })
+    }
+}
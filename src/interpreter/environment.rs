@@ -1,13 +1,44 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use anyhow::Context;
-use tracing::info;
+use tracing::{info, warn};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Environment {
     pub env_file_name: PathBuf,
     pub namespaced_variables: HashMap<String, HashMap<String, String>>,
     selected_namespace: Option<String>,
+    dotenv: Option<Dotenv>,
+    /// Whether the variables are saved to, and were loaded from, the env file.
+    persisted: bool,
+    /// What couldn't be loaded from the env file, which saving over it would lose.
+    malformed: Option<MalformedEnvFile>,
+}
+
+/// One `--env` of a run, see [Environment::select_layers].
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvLayer {
+    Namespace(String),
+    File(PathBuf),
+}
+
+/// Variables from a plain dotenv file, layered over the namespaced variables.
+#[derive(Debug, Clone)]
+struct Dotenv {
+    variables: HashMap<String, String>,
+    precedence: DotenvPrecedence,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DotenvPrecedence {
+    /// Dotenv variables are only used when the selected namespace doesn't define them.
+    #[default]
+    Lower,
+    /// Dotenv variables take over those defined in the selected namespace.
+    Higher,
 }
 
 impl Environment {
@@ -16,6 +47,9 @@ impl Environment {
             env_file_name: file_name.into(),
             namespaced_variables: HashMap::from([("default".to_string(), HashMap::new())]),
             selected_namespace: None,
+            dotenv: None,
+            persisted: true,
+            malformed: None,
         };
 
         env.load_variables_from_file()?;
@@ -23,6 +57,36 @@ impl Environment {
         Ok(env)
     }
 
+    /// An environment with only a 'default' namespace, that's never read from or saved to
+    /// a file.
+    pub fn in_memory() -> Self {
+        Self {
+            env_file_name: PathBuf::new(),
+            namespaced_variables: HashMap::from([("default".to_string(), HashMap::new())]),
+            selected_namespace: None,
+            dotenv: None,
+            persisted: false,
+            malformed: None,
+        }
+    }
+
+    /// Adds `(namespace, name, value)` variables, creating the namespaces as needed.
+    pub fn with_variables<N, K, V>(mut self, variables: impl IntoIterator<Item = (N, K, V)>) -> Self
+    where
+        N: Into<String>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        for (namespace, name, value) in variables {
+            self.namespaced_variables
+                .entry(namespace.into())
+                .or_default()
+                .insert(name.into(), value.into());
+        }
+
+        self
+    }
+
     fn load_variables_from_file(&mut self) -> anyhow::Result<(), std::io::Error> {
         let file = std::fs::File::options()
             .read(true)
@@ -33,15 +97,134 @@ impl Environment {
 
         info!("loading env from file: {}", self.env_file_name.display());
 
-        let reader = std::io::BufReader::new(file);
+        let mut text = String::new();
+        std::io::Read::read_to_string(&mut std::io::BufReader::new(file), &mut text)?;
+
+        let mut namespaced_variables = match read_namespaced_variables(&text) {
+            Ok((namespaced_variables, problems)) => {
+                if !problems.is_empty() {
+                    let malformed = MalformedEnvFile { problems };
+                    warn!(
+                        "skipping what's malformed in {}: {malformed}",
+                        self.env_file_name.display()
+                    );
+                    self.malformed = Some(malformed);
+                }
+
+                namespaced_variables
+            }
+            Err(malformed) => {
+                warn!("{} is malformed: {malformed}", self.env_file_name.display());
+                self.malformed = Some(malformed);
+                HashMap::new()
+            }
+        };
+
+        if !namespaced_variables.contains_key("default") {
+            info!("adding a 'default' namespace");
+            namespaced_variables.insert("default".to_string(), HashMap::new());
+        }
+
+        self.namespaced_variables = namespaced_variables;
+
+        Ok(())
+    }
+
+    /// Checks that the env file is a map of namespaces to maps of variable names to string
+    /// values, with a 'default' namespace, describing everything that's malformed otherwise.
+    pub fn validate_file<P: AsRef<Path>>(path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read env file {}", path.display()))?;
+
+        parse_namespaced_variables(&text)
+            .with_context(|| format!("{} is malformed", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Loads a dotenv file (`KEY=value` lines) as a layer consulted when looking up variables.
+    pub fn load_dotenv<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        precedence: DotenvPrecedence,
+    ) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read dotenv file {}", path.display()))?;
+
+        info!("loading dotenv from file: {}", path.display());
+
+        self.dotenv = Some(Dotenv {
+            variables: parse_dotenv(&text),
+            precedence,
+        });
+
+        Ok(())
+    }
+
+    /// Overlays the variables of `other` onto these, namespace by namespace, with those of
+    /// `other` taking precedence. The result is never saved, so as not to leak variables from
+    /// one file into another.
+    pub fn merge(&mut self, other: Environment) {
+        for (namespace, variables) in other.namespaced_variables {
+            self.namespaced_variables
+                .entry(namespace)
+                .or_default()
+                .extend(variables);
+        }
+
+        self.persisted = false;
+    }
+
+    /// Selects a namespace made up of the given layers, applied in order, later ones taking
+    /// precedence. A namespace lays its variables over those layered so far, and an env file
+    /// [merges](Self::merge) its namespaces into these, over those of the namespaces layered so
+    /// far and under those layered after it. Like with [Self::merge], the result is never saved.
+    ///
+    /// With only env files, they're merged, without selecting another namespace.
+    pub fn select_layers(&mut self, layers: &[EnvLayer]) -> anyhow::Result<()> {
+        let mut layered = HashMap::new();
+        let mut namespaces = vec![];
+
+        for layer in layers {
+            match layer {
+                EnvLayer::Namespace(namespace) => {
+                    let variables = self
+                        .namespaced_variables
+                        .get(namespace)
+                        .ok_or_else(|| anyhow::anyhow!("undefined namespace '{namespace}'"))?;
+
+                    layered.extend(variables.clone());
+                    namespaces.push(namespace.as_str());
+                }
+                EnvLayer::File(path) => {
+                    if !path.is_file() {
+                        anyhow::bail!("no env file at {}", path.display());
+                    }
 
-        self.namespaced_variables = serde_json::from_reader(reader)
-            .context("failed to read env file as json")
-            .unwrap_or_else(|err| {
-                info!("{err:#}");
-                info!("creating new configuration with a 'default' namespace");
-                HashMap::from([("default".to_string(), HashMap::new())])
-            });
+                    let file = Environment::new(path)?;
+
+                    for namespace in namespaces.iter() {
+                        if let Some(variables) = file.namespaced_variables.get(*namespace) {
+                            layered.extend(variables.clone());
+                        }
+                    }
+
+                    self.merge(file);
+                }
+            }
+        }
+
+        if namespaces.is_empty() {
+            return Ok(());
+        }
+
+        let name = namespaces.join("+");
+
+        self.namespaced_variables.insert(name.clone(), layered);
+        self.persisted = false;
+        self.select_variables_namespace(name);
 
         Ok(())
     }
@@ -62,7 +245,12 @@ impl Environment {
             .get(&self.selected_namespace())
             .unwrap();
 
-        variables_map.get(name)
+        let dotenv_value = self.dotenv.as_ref().and_then(|d| d.variables.get(name));
+
+        match self.dotenv.as_ref().map(|d| d.precedence) {
+            Some(DotenvPrecedence::Higher) => dotenv_value.or_else(|| variables_map.get(name)),
+            _ => variables_map.get(name).or(dotenv_value),
+        }
     }
 
     pub fn get_variable_value_per_namespace(&self, name: &String) -> Vec<(&String, &String)> {
@@ -90,7 +278,27 @@ impl Environment {
         Ok(())
     }
 
+    /// Whether [Self::set_variable] saves variables to the env file, which it doesn't for
+    /// [Self::in_memory] environments, or ones made up of several.
+    pub fn is_persisted(&self) -> bool {
+        self.persisted
+    }
+
+    /// Does nothing for [Self::in_memory] environments, and fails rather than overwrite an
+    /// env file that couldn't be loaded whole.
     pub fn save_to_file(&self) -> anyhow::Result<()> {
+        if !self.persisted {
+            return Ok(());
+        }
+
+        if let Some(malformed) = &self.malformed {
+            anyhow::bail!(
+                "won't overwrite {}, which would lose what's malformed in it; fix it first, \
+                 `rstd env validate` lists what's wrong:\n{malformed}",
+                self.env_file_name.display()
+            );
+        }
+
         let file = std::fs::File::options()
             .write(true)
             .truncate(true)
@@ -102,3 +310,309 @@ impl Environment {
         Ok(())
     }
 }
+
+type NamespacedVariables = HashMap<String, HashMap<String, String>>;
+
+/// Everything that's wrong with an env file.
+#[derive(Debug, Clone)]
+pub struct MalformedEnvFile {
+    pub problems: Vec<String>,
+}
+
+impl std::error::Error for MalformedEnvFile {}
+
+impl std::fmt::Display for MalformedEnvFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.problems.join("\n"))
+    }
+}
+
+/// Reads namespaced variables out of the text of an env file, where an empty file has
+/// nothing but an empty 'default' namespace, and fails on anything malformed.
+fn parse_namespaced_variables(text: &str) -> Result<NamespacedVariables, MalformedEnvFile> {
+    let (namespaced_variables, mut problems) = read_namespaced_variables(text)?;
+
+    if !namespaced_variables.contains_key("default") {
+        problems.push("there's no 'default' namespace".to_string());
+    }
+
+    if !problems.is_empty() {
+        return Err(MalformedEnvFile { problems });
+    }
+
+    Ok(namespaced_variables)
+}
+
+/// Reads what namespaced variables it can out of the text of an env file, along with the
+/// problems with the namespaces and variables it had to skip, only failing when the text
+/// isn't an object of namespaces at all.
+fn read_namespaced_variables(
+    text: &str,
+) -> Result<(NamespacedVariables, Vec<String>), MalformedEnvFile> {
+    if text.trim().is_empty() {
+        return Ok((
+            HashMap::from([("default".to_string(), HashMap::new())]),
+            vec![],
+        ));
+    }
+
+    let malformed = |problem: String| MalformedEnvFile {
+        problems: vec![problem],
+    };
+
+    let namespaces = match serde_json::from_str(text) {
+        Ok(serde_json::Value::Object(namespaces)) => namespaces,
+        Ok(value) => {
+            return Err(malformed(format!(
+                "expected an object of namespaces, but found {}",
+                json_kind(&value)
+            )))
+        }
+        Err(e) => return Err(malformed(format!("it isn't valid JSON: {e}"))),
+    };
+
+    let mut problems = vec![];
+    let mut namespaced_variables = HashMap::new();
+
+    for (namespace, variables) in namespaces {
+        let serde_json::Value::Object(variables) = variables else {
+            problems.push(format!(
+                "namespace '{namespace}' is {}, but namespaces must be objects of variables",
+                json_kind(&variables)
+            ));
+            continue;
+        };
+
+        let mut values = HashMap::new();
+
+        for (name, value) in variables {
+            match value {
+                serde_json::Value::String(value) => {
+                    values.insert(name, value);
+                }
+                value => problems.push(format!(
+                    "variable '{name}' in namespace '{namespace}' is {}, but variables must be strings",
+                    json_kind(&value)
+                )),
+            }
+        }
+
+        namespaced_variables.insert(namespace, values);
+    }
+
+    Ok((namespaced_variables, problems))
+}
+
+fn json_kind(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a boolean",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+fn parse_dotenv(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|quote| {
+                    value
+                        .strip_prefix(*quote)
+                        .and_then(|v| v.strip_suffix(*quote))
+                })
+                .unwrap_or(value);
+
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_dotenv, parse_namespaced_variables, EnvLayer, Environment};
+
+    #[test]
+    fn in_memory_environments_hold_variables_by_namespace() {
+        let mut env = Environment::in_memory().with_variables([
+            ("default", "host", "localhost"),
+            ("prod", "host", "example.com"),
+        ]);
+
+        assert_eq!(
+            env.get_variable_value(&"host".to_string()).unwrap(),
+            "localhost"
+        );
+
+        env.select_variables_namespace("prod".to_string());
+        assert_eq!(
+            env.get_variable_value(&"host".to_string()).unwrap(),
+            "example.com"
+        );
+
+        env.set_variable("token".to_string(), "abc".to_string())
+            .unwrap();
+        assert_eq!(env.get_variable_value(&"token".to_string()).unwrap(), "abc");
+        assert!(!env.env_file_name.exists());
+    }
+
+    #[test]
+    fn layers_take_precedence_in_order() {
+        let mut env = Environment::in_memory().with_variables([
+            ("staging", "host", "staging.example.com"),
+            ("staging", "token", "staging-token"),
+            ("secrets", "token", "secret-token"),
+        ]);
+
+        env.merge(Environment::in_memory().with_variables([("secrets", "key", "abc")]));
+
+        env.select_layers(&[
+            EnvLayer::Namespace("staging".to_string()),
+            EnvLayer::Namespace("secrets".to_string()),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            env.get_variable_value(&"host".to_string()).unwrap(),
+            "staging.example.com"
+        );
+        assert_eq!(
+            env.get_variable_value(&"token".to_string()).unwrap(),
+            "secret-token"
+        );
+        assert_eq!(env.get_variable_value(&"key".to_string()).unwrap(), "abc");
+
+        assert!(env
+            .select_layers(&[EnvLayer::Namespace("missing".to_string())])
+            .is_err());
+    }
+
+    #[test]
+    fn env_files_are_layered_in_order_with_namespaces() {
+        let path = std::env::temp_dir().join("rested_env_files_are_layered_in_order.json");
+        std::fs::write(
+            &path,
+            r#"{ "staging": { "token": "file-token", "key": "file-key" } }"#,
+        )
+        .unwrap();
+
+        let mut env = Environment::in_memory().with_variables([
+            ("staging", "host", "staging.example.com"),
+            ("staging", "token", "staging-token"),
+            ("secrets", "key", "secret-key"),
+        ]);
+
+        env.select_layers(&[
+            EnvLayer::Namespace("staging".to_string()),
+            EnvLayer::File(path.clone()),
+            EnvLayer::Namespace("secrets".to_string()),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            env.get_variable_value(&"host".to_string()).unwrap(),
+            "staging.example.com"
+        );
+        assert_eq!(
+            env.get_variable_value(&"token".to_string()).unwrap(),
+            "file-token"
+        );
+        assert_eq!(
+            env.get_variable_value(&"key".to_string()).unwrap(),
+            "secret-key"
+        );
+
+        let missing = std::env::temp_dir().join("rested_no_such_env_file.json");
+        assert!(env.select_layers(&[EnvLayer::File(missing)]).is_err());
+    }
+
+    #[test]
+    fn describes_every_malformed_namespace_and_variable() {
+        let err = parse_namespaced_variables(
+            r#"{ "default": { "host": "localhost" }, "prod": { "port": 443, "debug": false }, "ci": [] }"#,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.problems,
+            [
+                "namespace 'ci' is an array, but namespaces must be objects of variables",
+                "variable 'debug' in namespace 'prod' is a boolean, but variables must be strings",
+                "variable 'port' in namespace 'prod' is a number, but variables must be strings",
+            ]
+        );
+
+        let err = parse_namespaced_variables(r#"{ "staging": {} }"#).unwrap_err();
+        assert_eq!(err.problems, ["there's no 'default' namespace"]);
+
+        let err = parse_namespaced_variables(r#"{ "default": { "#).unwrap_err();
+        assert!(err.problems[0].starts_with("it isn't valid JSON"));
+
+        assert!(parse_namespaced_variables("  ").unwrap()["default"].is_empty());
+        assert_eq!(
+            parse_namespaced_variables(r#"{ "default": { "host": "localhost" } }"#).unwrap()
+                ["default"]["host"],
+            "localhost"
+        );
+    }
+
+    #[test]
+    fn loading_keeps_the_namespaces_of_files_without_a_default_one() {
+        let path = std::env::temp_dir().join("rested_keeps_namespaces.env.rd.json");
+        std::fs::write(&path, r#"{ "staging": { "host": "s.example.com" } }"#).unwrap();
+
+        let mut env = Environment::new(&path).unwrap();
+        env.set_variable("k".to_string(), "v".to_string()).unwrap();
+
+        let saved = parse_namespaced_variables(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["staging"]["host"], "s.example.com");
+        assert_eq!(saved["default"]["k"], "v");
+    }
+
+    #[test]
+    fn loading_skips_malformed_variables_but_never_saves_over_them() {
+        let path = std::env::temp_dir().join("rested_never_saves_over_malformed.env.rd.json");
+        let text = r#"{ "default": { "host": "localhost", "port": 443 } }"#;
+        std::fs::write(&path, text).unwrap();
+
+        let mut env = Environment::new(&path).unwrap();
+        assert_eq!(
+            env.get_variable_value(&"host".to_string()).unwrap(),
+            "localhost"
+        );
+
+        assert!(env.set_variable("k".to_string(), "v".to_string()).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+    }
+
+    #[test]
+    fn parses_dotenv_lines() {
+        let vars = parse_dotenv(
+            r#"
+# a comment
+TOKEN=abc123
+export HOST = localhost
+QUOTED="hello world"
+SINGLE='it''s'
+EMPTY=
+not a variable
+"#,
+        );
+
+        assert_eq!(vars.len(), 5);
+        assert_eq!(vars["TOKEN"], "abc123");
+        assert_eq!(vars["HOST"], "localhost");
+        assert_eq!(vars["QUOTED"], "hello world");
+        assert_eq!(vars["SINGLE"], "it''s");
+        assert_eq!(vars["EMPTY"], "");
+    }
+}
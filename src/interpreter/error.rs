@@ -13,6 +13,7 @@ pub enum InterpreterErrorKind {
     RequiredArguments { required: usize, received: usize },
     EnvVariableNotFound { name: String },
     RequestWithPathnameWithoutBaseUrl,
+    InvalidBaseUrl { url: String },
     UndefinedCallable { name: String },
     UndeclaredIdentifier { name: String },
     UnsupportedAttribute { name: String },
@@ -41,6 +42,9 @@ impl std::fmt::Display for InterpreterErrorKind {
             InterpreterErrorKind::RequestWithPathnameWithoutBaseUrl => {
                 "BASE_URL needs to be set first for requests to work with just pathnames; try writing like set BASE_URL \"<api orgin>\" before this request".to_string()
             }
+            InterpreterErrorKind::InvalidBaseUrl { url } => {
+                format!("BASE_URL must be an absolute url with a scheme, but found {:?}", url)
+            }
             InterpreterErrorKind::UndefinedCallable { name } => {
                 format!("attempting to calling an undefined function: {}", name)
             }
@@ -216,6 +220,14 @@ impl<'i> InterpErrorFactory<'i> {
         )
     }
 
+    pub fn invalid_base_url(&self, url: String, at: Span) -> ContextualError<InterpreterErrorKind> {
+        ContextualError::new(
+            InterpreterErrorKind::InvalidBaseUrl { url },
+            at,
+            self.source_code,
+        )
+    }
+
     pub fn type_mismatch(
         &self,
         expected: ValueTag,
@@ -1,31 +1,70 @@
 use super::builtin;
 use super::environment::Environment;
 use super::value::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use tracing::warn;
 
 use crate::error_meta::ContextualError;
-use crate::interpreter::ir::LogDestination;
+use crate::interpreter::ir::{
+    BodyAssertion, DownloadTarget, EnvCapture, LogDestination, ResponseFormat,
+};
+use crate::interpreter::json_path::JsonPath;
 use crate::interpreter::value::ValueTag;
 use crate::lexer;
 use crate::parser::ast::{
-    self, ConstantDeclaration, Endpoint, Expression, Item, TemplateStringPart, VariableDeclaration,
+    self, ConstantDeclaration, Endpoint, Expression, Item, RequestMethod, TemplateStringPart,
+    VariableDeclaration,
 };
 
-use crate::lexer::locations::GetSpan;
+use crate::lexer::locations::{GetSpan, Location, Position, Span};
 
 use super::attributes::AttributeStack;
 use super::error::{InterpErrorFactory, InterpreterErrorKind};
 use super::ir::Header;
 use super::ir::RequestItem;
+use super::runner::{send_bound_request, Backoff, RetryPolicy, RunOptions};
+use super::Stdin;
+use std::time::Duration;
 
 type Result<T> = std::result::Result<T, Box<ContextualError<InterpreterErrorKind>>>;
 
+/// The most elements `@each(..)` will make requests for.
+pub const MAX_EACH_ITERATIONS: usize = 1000;
+
+/// The most times `@repeat(..)` will send a request.
+pub const MAX_REPEAT: usize = 10_000;
+
 pub struct Evaluator<'source, 'p, 'env> {
     program: &'p ast::Program<'source>,
     error_factory: InterpErrorFactory<'source>,
     env: &'env Environment,
     base_url: Option<String>,
+    /// Whether the base url was given up front, overriding `set BASE_URL` in the program.
+    fixed_base_url: bool,
     pub let_bindings: HashMap<&'source str, Value>,
+    /// From `set RETRY { .. }`.
+    pub retry: Option<RetryPolicy>,
+    /// From `set ALLOW_INSECURE_LOCALHOST true`.
+    pub allow_insecure_localhost: bool,
+    /// From `set DEFAULT_TIMEOUT 3000`, for requests without a `@timeout(..)`.
+    default_timeout: Option<Duration>,
+    /// From `set USER_AGENT "rested/1.0"`, for requests without a `User-Agent` header.
+    user_agent: Option<String>,
+    /// From `headers { .. }`, for every request after it that doesn't set them itself.
+    default_headers: Vec<Header>,
+    /// From the last `@group(..)`, for every request after it.
+    group: Option<String>,
+    /// Whether `env(..)` of a missing variable evaluates to a `${env:NAME}` placeholder,
+    /// instead of failing.
+    partial: bool,
+    /// What relative paths given to `read(..)` and `read_bytes_base64(..)` are resolved against.
+    script_dir: Option<PathBuf>,
+    /// How requests bound with `let` are sent, if they're not just bound to null.
+    bound_requests: Option<RunOptions>,
+    /// The variables whose bound requests are sent, if not all of them.
+    bound_requests_used: Option<BTreeSet<&'source str>>,
+    stdin: Stdin,
     attributes: AttributeStack<'source, 'p>,
 }
 
@@ -36,11 +75,70 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
             program,
             env,
             base_url: None,
+            fixed_base_url: false,
             let_bindings: HashMap::new(),
+            retry: None,
+            allow_insecure_localhost: false,
+            default_timeout: None,
+            user_agent: None,
+            default_headers: vec![],
+            group: None,
+            partial: false,
+            script_dir: None,
+            bound_requests: None,
+            bound_requests_used: None,
+            stdin: Stdin::Empty,
             attributes: AttributeStack::new(),
         }
     }
 
+    /// Resolve pathnames against `base_url`, ignoring `set BASE_URL` in the program.
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = Some(base_url);
+        self.fixed_base_url = true;
+        self
+    }
+
+    /// Bind `input` to a value the program didn't declare, like data given on the command line.
+    pub fn with_input(mut self, input: Value) -> Self {
+        self.let_bindings.insert("input", input);
+        self
+    }
+
+    /// Leave `env(..)` of missing variables as `${env:NAME}` placeholders, for when the
+    /// structure of the requests matters more than their values.
+    pub fn partial(mut self) -> Self {
+        self.partial = true;
+        self
+    }
+
+    /// Resolve relative paths given to `read(..)` against `script_dir`, rather than the
+    /// current directory.
+    pub fn with_script_dir(mut self, script_dir: PathBuf) -> Self {
+        self.script_dir = Some(script_dir);
+        self
+    }
+
+    /// Send the requests bound with `let` with these options, binding their responses, rather
+    /// than null.
+    pub fn running_bound_requests(mut self, options: RunOptions) -> Self {
+        self.bound_requests = Some(options);
+        self
+    }
+
+    /// Of the requests bound with `let`, only send those that the requests starting at these
+    /// positions use, binding the rest to null.
+    pub fn only_running_bound_requests_used_by(mut self, requests: &[Position]) -> Self {
+        self.bound_requests_used = Some(self.program.variables_used_by(requests));
+        self
+    }
+
+    /// Set what `stdin()` evaluates to, an empty string by default.
+    pub fn with_stdin(mut self, stdin: Stdin) -> Self {
+        self.stdin = stdin;
+        self
+    }
+
     pub fn evaluate(
         &mut self,
     ) -> std::result::Result<Vec<RequestItem>, Box<[ContextualError<InterpreterErrorKind>]>> {
@@ -50,9 +148,8 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
 
         for item in self.program.items.iter() {
             match self.evaluate_item(item) {
-                Ok(Some(r)) => requests.push(r),
+                Ok(items) => requests.extend(items),
                 Err(error) => errors_in_items.push(*error),
-                _ => {}
             };
         }
 
@@ -63,197 +160,1267 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
         Ok(requests)
     }
 
-    fn evaluate_item(&mut self, item: &'p Item<'source>) -> Result<Option<RequestItem>> {
+    fn evaluate_item(&mut self, item: &'p Item<'source>) -> Result<Vec<RequestItem>> {
         use ast::Item::*;
         match item {
-            Request(ast::Request {
-                method,
-                endpoint,
-                block,
-                span,
-            }) => {
-                // Handle @skip
-                if self.attributes.get("skip").is_some() {
-                    self.attributes.clear();
-                    return Ok(None);
+            Request(request) => {
+                // Handle @skip and @skip_if(..)
+                let items = if self.attributes.get("skip").is_some() {
+                    Ok(vec![])
+                } else {
+                    self.evaluate_skip_if().and_then(|skip| {
+                        if skip {
+                            Ok(vec![])
+                        } else {
+                            self.evaluate_request_for_each(request)
+                        }
+                    })
+                };
+
+                self.attributes.clear();
+
+                return items;
+            }
+            Set(ConstantDeclaration { identifier, value }) => {
+                let identifier = identifier.get()?;
+
+                if identifier.text == "RETRY" {
+                    self.retry = Some(self.evaluate_retry_policy(value)?);
+                    return Ok(vec![]);
                 }
 
-                let span = span.to_end_of(endpoint.span());
+                if identifier.text == "ALLOW_INSECURE_LOCALHOST" {
+                    self.allow_insecure_localhost = match self.evaluate_expression(value)? {
+                        Value::Bool(allow) => allow,
+                        val => {
+                            return Err(self
+                                .error_factory
+                                .type_mismatch(ValueTag::Bool, val, value.span())
+                                .with_message("expected true or false")
+                                .into())
+                        }
+                    };
+                    return Ok(vec![]);
+                }
 
-                let path = self.evaluate_request_endpoint(endpoint)?;
+                if identifier.text == "DEFAULT_TIMEOUT" {
+                    self.default_timeout =
+                        Some(Duration::from_millis(self.evaluate_count(value)? as u64));
+                    return Ok(vec![]);
+                }
 
-                let mut headers = vec![];
-                let mut body: Option<String> = None;
+                if identifier.text == "USER_AGENT" {
+                    self.user_agent = Some(self.evaluate_string(value)?);
+                    return Ok(vec![]);
+                }
 
-                if let Some(statements) = block.as_ref().map(|b| &b.statements) {
-                    for statement in statements.iter() {
-                        match statement {
-                            ast::Statement::Header { name, value } => {
-                                match self.evaluate_expression(value)? {
-                                    Value::String(value) => headers
-                                        .push(Header::new(name.get()?.value.to_string(), value)),
-                                    val => return Err(self
-                                        .error_factory
-                                        .type_mismatch(ValueTag::String, val, value.span())
-                                        .with_message(
-                                            "maybe you want to stringify it with a json(..) call",
-                                        )
-                                        .into()),
-                                }
-                            }
-                            ast::Statement::Body { value, .. } => {
-                                if body.is_none() {
-                                    body = match self.evaluate_expression(value)? {
-                                            Value::String(value) => Some(value),
-                                            val => {
-                                                return Err(self
-                                                    .error_factory
-                                                    .type_mismatch(
-                                                        ValueTag::String,
-                                                        val,
-                                                        value.span(),
-                                                    )
-                                                    .with_message("maybe you want to stringify it with a json(..) call")
-                                                    .into())
-                                            }
-                                        }
-                                }
-                            }
-                            ast::Statement::LineComment(_) => {}
-                            ast::Statement::Error(err) => {
-                                unreachable!(
-                                    "all syntax errors should have been caught, but found {}",
-                                    err
-                                )
-                            }
+                if identifier.text != "BASE_URL" {
+                    return Err(self
+                        .error_factory
+                        .unknown_constant(identifier)
+                        .with_message("BASE_URL, RETRY, ALLOW_INSECURE_LOCALHOST, DEFAULT_TIMEOUT and USER_AGENT are the only constants")
+                        .into());
+                }
+
+                if self.fixed_base_url {
+                    return Ok(vec![]);
+                }
+
+                self.base_url = match self.evaluate_expression(value)? {
+                    Value::String(s) if s.trim().is_empty() => {
+                        return Err(self
+                            .error_factory
+                            .invalid_base_url(s, value.span())
+                            .with_message("it's empty, if it's read from the env, make sure the variable has a value in the selected namespace")
+                            .into())
+                    }
+                    Value::String(s) => match url::Url::parse(&s) {
+                        // Without a host, something like "localhost:8080" parses with
+                        // "localhost" as the scheme
+                        Ok(url) if url.has_host() => Some(s),
+                        _ => {
+                            return Err(self
+                                .error_factory
+                                .invalid_base_url(s, value.span())
+                                .with_message("try prefixing it with a scheme, like \"http://\"")
+                                .into())
                         }
+                    },
+                    expr => {
+                        return Err(self
+                            .error_factory
+                            .type_mismatch(ValueTag::String, expr, value.span())
+                            .into())
                     }
-                }
+                };
+            }
+            Headers(ast::HeadersDeclaration { block, .. }) => {
+                for statement in block.statements.iter() {
+                    match statement {
+                        ast::Statement::Header { name, value } => {
+                            let name = name.get()?.value;
 
-                let name_of_request = match self.attributes.get("name") {
-                    Some(att) => {
-                        if let Some(args) = att.params {
-                            let [arg] = self.expect_x_args::<1>(args)?;
-                            let value = match self.evaluate_expression(arg)? {
+                            let value = match self.evaluate_expression(value)? {
                                 Value::String(value) => value,
                                 val => {
                                     return Err(self
                                         .error_factory
-                                        .type_mismatch(ValueTag::String, val, arg.span())
+                                        .type_mismatch(ValueTag::String, val, value.span())
                                         .into())
                                 }
                             };
-                            Some(value)
-                        } else {
+
+                            // a later block overrides the headers of an earlier one
+                            self.default_headers
+                                .retain(|h| !h.name.eq_ignore_ascii_case(name));
+                            self.default_headers
+                                .push(Header::new(name.to_string(), value));
+                        }
+                        ast::Statement::LineComment(_) => {}
+                        ast::Statement::Error(err) => {
+                            unreachable!(
+                                "all syntax errors should have been caught, but found {}",
+                                err
+                            )
+                        }
+                        statement => {
                             return Err(self
                                 .error_factory
-                                .required_args(att.identifier.span(), 1, 0)
-                                .with_message(
-                                    "@name(..) must be given an argument, like @name(\"req_1\")",
+                                .other(
+                                    statement.span(),
+                                    "only headers may be declared for every request",
                                 )
-                                .into());
+                                .into())
                         }
                     }
-                    None => None,
+                }
+            }
+            LineComment(_) => {}
+            Attribute(ast::Attribute {
+                identifier,
+                arguments,
+                ..
+            }) => {
+                let identifier = identifier.get()?;
+
+                match identifier.text {
+                    "name" | "log" | "dbg" | "skip" | "max_events" | "repeat" | "download"
+                    | "conditional" | "chunked" | "order" | "each" | "idempotency_key" | "as"
+                    | "auth" | "capture_env" | "timeout" | "expect" | "skip_if" => {
+                        if self.attributes.has(identifier.text) {
+                            return Err(self.error_factory.duplicate_attribute(identifier).into());
+                        }
+                        self.attributes.add(identifier, arguments.as_ref());
+                    }
+                    // a request can declare any number of variables and assertions
+                    "var" | "assert_contains" => {
+                        self.attributes.push(identifier, arguments.as_ref())
+                    }
+                    // not just for the next request, but every one until the next group
+                    "group" => {
+                        self.group = Some(self.evaluate_group(identifier, arguments.as_ref())?)
+                    }
+                    _ => {
+                        return Err(self
+                            .error_factory
+                            .unsupported_attribute(identifier)
+                            .with_message(
+                                "@name, @log, @skip, @skip_if, @dbg, @max_events, @repeat, @var, @download, @conditional, @chunked, @order, @each, @idempotency_key, @as, @auth, @capture_env, @timeout, @expect, @assert_contains and @group are the only supported attributes",
+                            )
+                            .into());
+                    }
+                }
+            }
+            Let(VariableDeclaration { identifier, value }) => {
+                let value = match value {
+                    Expression::Request(request) => {
+                        self.evaluate_bound_request(identifier.get()?.text, request)?
+                    }
+                    value => self.evaluate_expression(value)?,
                 };
+                self.let_bindings.insert(identifier.get()?.text, value);
+            }
+            Expr(_) => {}
+            Error(err) => {
+                unreachable!(
+                    "all syntax errors should have been caught, but found {}",
+                    err
+                )
+            }
+        }
+
+        Ok(vec![])
+    }
+
+    /// Evaluates the request once, or once per element of the array given to `@each(..)`, with
+    /// the element bound to `item`.
+    fn evaluate_request_for_each(
+        &mut self,
+        request: &'p ast::Request<'source>,
+    ) -> Result<Vec<RequestItem>> {
+        let Some(att) = self.attributes.get("each") else {
+            return Ok(vec![self.evaluate_request_with_vars(request)?]);
+        };
+
+        let (identifier, params) = (att.identifier, att.params);
+
+        let Some(args) = params else {
+            return Err(self
+                .error_factory
+                .required_args(identifier.span(), 1, 0)
+                .with_message("@each(..) must be given an array, like @each([1, 2, 3])")
+                .into());
+        };
+
+        let [arg] = self.expect_x_args::<1>(args)?;
+
+        let elements = match self.evaluate_expression(arg)? {
+            Value::Array(elements) => elements,
+            val => {
+                return Err(self
+                    .error_factory
+                    .type_mismatch(ValueTag::Array, val, arg.span())
+                    .into())
+            }
+        };
+
+        if elements.len() > MAX_EACH_ITERATIONS {
+            return Err(self
+                .error_factory
+                .other(
+                    arg.span(),
+                    format!(
+                        "@each(..) makes at most {MAX_EACH_ITERATIONS} requests, but was given {} elements",
+                        elements.len()
+                    ),
+                )
+                .into());
+        }
+
+        let shadowed = self.let_bindings.remove("item");
+
+        let items = elements
+            .into_vec()
+            .into_iter()
+            .map(|element| {
+                self.let_bindings.insert("item", element);
+                self.evaluate_request_with_vars(request)
+            })
+            .collect();
+
+        match shadowed {
+            Some(value) => self.let_bindings.insert("item", value),
+            None => self.let_bindings.remove("item"),
+        };
+
+        items
+    }
+
+    /// Sends a request bound with `let`, evaluating to its response body, parsed if it's JSON.
+    fn evaluate_bound_request(
+        &mut self,
+        name: &str,
+        request: &'p ast::Request<'source>,
+    ) -> Result<Value> {
+        let item = self.evaluate_request(request)?;
+
+        let Some(options) = &self.bound_requests else {
+            return Ok(Value::Null);
+        };
+
+        if let Some(used) = &self.bound_requests_used {
+            if !used.contains(name) {
+                return Ok(Value::Null);
+            }
+        }
+
+        let response = send_bound_request(
+            &item.request,
+            options,
+            self.allow_insecure_localhost,
+            self.retry.as_ref(),
+        )
+        .map_err(|error| self.error_factory.other(request.span, error))?;
+
+        let value = match serde_json::from_str::<serde_json::Value>(&response.body) {
+            Ok(json) => json.into(),
+            Err(_) => Value::String(response.body),
+        };
+
+        Ok(value)
+    }
+
+    fn evaluate_request_with_vars(
+        &mut self,
+        request: &'p ast::Request<'source>,
+    ) -> Result<RequestItem> {
+        let shadowed = self.bind_request_vars()?;
 
-                let log_destination = if let Some(att) = self.attributes.get("log") {
-                    if let Some(args) = att.params {
-                        let [arg] = self.expect_x_args::<1>(args)?;
-                        let file_path = match self.evaluate_expression(arg)? {
+        let item = self.evaluate_request(request);
+
+        for (name, value) in shadowed {
+            match value {
+                Some(value) => self.let_bindings.insert(name, value),
+                None => self.let_bindings.remove(name),
+            };
+        }
+
+        item
+    }
+
+    fn evaluate_request(&mut self, request: &'p ast::Request<'source>) -> Result<RequestItem> {
+        let ast::Request {
+            method,
+            endpoint,
+            block,
+            span,
+        } = request;
+
+        let span = span.to_end_of(endpoint.span());
+
+        let path = self.evaluate_request_endpoint(endpoint)?;
+
+        let mut headers = vec![];
+        let mut query_params = vec![];
+        let mut body: Option<String> = None;
+        let mut body_span = None;
+        let mut body_content_type = None;
+        let mut graphql_query: Option<String> = None;
+        let mut graphql_variables: Option<Value> = None;
+
+        if let Some(statements) = block.as_ref().map(|b| &b.statements) {
+            for statement in statements.iter() {
+                match statement {
+                    ast::Statement::Header { name, value } => {
+                        match self.evaluate_expression(value)? {
+                            Value::String(value) => {
+                                headers.push(Header::new(name.get()?.value.to_string(), value))
+                            }
+                            val => {
+                                return Err(self
+                                    .error_factory
+                                    .type_mismatch(ValueTag::String, val, value.span())
+                                    .with_message(
+                                        "maybe you want to stringify it with a json(..) call",
+                                    )
+                                    .into())
+                            }
+                        }
+                    }
+                    ast::Statement::Query { name, value } => {
+                        let value = match self.evaluate_expression(value)? {
                             Value::String(value) => value,
+                            val @ (Value::Number(_) | Value::Bool(_)) => val.to_string(),
                             val => {
                                 return Err(self
                                     .error_factory
-                                    .type_mismatch(ValueTag::String, val, arg.span())
+                                    .type_mismatch(ValueTag::String, val, value.span())
                                     .into())
                             }
                         };
-                        Some(LogDestination::File(file_path.into()))
-                    } else {
+
+                        query_params.push((name.get()?.value.to_string(), value));
+                    }
+                    ast::Statement::Body { value, .. } => {
+                        if body.is_none() {
+                            body_span = Some(value.span());
+                            body =
+                                match self.evaluate_expression(value)? {
+                                    Value::String(value) => Some(value),
+                                    // an explicit null means sending no body at all
+                                    Value::Null => None,
+                                    val => return Err(self
+                                        .error_factory
+                                        .type_mismatch(ValueTag::String, val, value.span())
+                                        .with_message(
+                                            "maybe you want to stringify it with a json(..) call",
+                                        )
+                                        .into()),
+                                };
+                            body_content_type = self.content_type_of_read_call(value);
+                        }
+                    }
+                    ast::Statement::FormBody { value, .. } => {
+                        if body.is_none() {
+                            body_span = Some(value.span());
+                            body = Some(self.evaluate_form_body(value)?);
+                            body_content_type = Some("application/x-www-form-urlencoded");
+                        }
+                    }
+                    ast::Statement::GraphqlQuery { .. }
+                    | ast::Statement::GraphqlVariables { .. }
+                        if *method != RequestMethod::GRAPHQL =>
+                    {
                         return Err(self
                             .error_factory
-                            .required_args(att.identifier.span(), 1, 0)
-                            .with_message("@log(..) must be given a file path argument")
+                            .other(
+                                statement.span(),
+                                "query and variables may only be declared in graphql requests",
+                            )
                             .into());
                     }
+                    ast::Statement::GraphqlQuery { value, .. } => {
+                        graphql_query = match self.evaluate_expression(value)? {
+                            Value::String(query) => Some(query),
+                            val => {
+                                return Err(self
+                                    .error_factory
+                                    .type_mismatch(ValueTag::String, val, value.span())
+                                    .into())
+                            }
+                        }
+                    }
+                    ast::Statement::GraphqlVariables { value, .. } => {
+                        graphql_variables = match self.evaluate_expression(value)? {
+                            val @ Value::Object(_) => Some(val),
+                            val => {
+                                return Err(self
+                                    .error_factory
+                                    .type_mismatch(ValueTag::Object, val, value.span())
+                                    .into())
+                            }
+                        }
+                    }
+                    ast::Statement::LineComment(_) => {}
+                    ast::Statement::Error(err) => {
+                        unreachable!(
+                            "all syntax errors should have been caught, but found {}",
+                            err
+                        )
+                    }
+                }
+            }
+        }
+
+        if let (Some(_), Some(content_type)) = (&body, body_content_type) {
+            let has_content_type = headers
+                .iter()
+                .any(|h| h.name.eq_ignore_ascii_case("content-type"));
+
+            if !has_content_type {
+                headers.push(Header::new(
+                    "Content-Type".to_string(),
+                    content_type.to_string(),
+                ));
+            }
+        }
+
+        if let (Some(body), Some(body_span)) = (&body, body_span) {
+            self.validate_json_body(body, body_span, &headers)?;
+        }
+
+        if *method == RequestMethod::GRAPHQL {
+            let Some(query) = graphql_query else {
+                return Err(self
+                    .error_factory
+                    .other(span, "a graphql request needs a query")
+                    .with_message("try adding a query statement, like query `{ me { id } }`")
+                    .into());
+            };
+
+            if body.is_some() {
+                return Err(self
+                    .error_factory
+                    .other(span, "a graphql request's body is made from its query and variables, so it can't also declare a body")
+                    .into());
+            }
+
+            let mut payload = HashMap::from([("query".to_string(), Value::String(query))]);
+
+            if let Some(variables) = graphql_variables {
+                payload.insert("variables".to_string(), variables);
+            }
+
+            body = Some(Value::Object(payload).to_string());
+
+            let has_content_type = headers
+                .iter()
+                .any(|h| h.name.eq_ignore_ascii_case("content-type"));
+
+            if !has_content_type {
+                headers.push(Header::new(
+                    "Content-Type".to_string(),
+                    "application/json".to_string(),
+                ));
+            }
+        }
+
+        let name_of_request = match self.attributes.get("name") {
+            Some(att) => {
+                if let Some(args) = att.params {
+                    let [arg] = self.expect_x_args::<1>(args)?;
+                    let value = match self.evaluate_expression(arg)? {
+                        Value::String(value) => value,
+                        val => {
+                            return Err(self
+                                .error_factory
+                                .type_mismatch(ValueTag::String, val, arg.span())
+                                .into())
+                        }
+                    };
+                    Some(value)
                 } else {
-                    None
-                };
+                    return Err(self
+                        .error_factory
+                        .required_args(att.identifier.span(), 1, 0)
+                        .with_message("@name(..) must be given an argument, like @name(\"req_1\")")
+                        .into());
+                }
+            }
+            None => None,
+        };
 
-                let r = RequestItem {
-                    name: name_of_request,
-                    dbg: self.attributes.get("dbg").is_some(),
-                    log_destination,
-                    span,
-                    request: super::ir::Request {
-                        method: *method,
-                        url: path,
-                        headers: headers.into(),
-                        body,
-                    },
-                };
+        let log_destination = if let Some(att) = self.attributes.get("log") {
+            if let Some(args) = att.params {
+                let [arg] = self.expect_x_args::<1>(args)?;
 
-                self.attributes.clear();
+                // a bare `stderr` is taken as is, and not as the name of a variable
+                if let Expression::Identifier(ast::result::ParsedNode::Ok(lexer::Token {
+                    text: "stderr",
+                    ..
+                })) = arg
+                {
+                    Some(LogDestination::Stderr)
+                } else {
+                    let file_path = match self.evaluate_expression(arg)? {
+                        Value::String(value) => value,
+                        val => {
+                            return Err(self
+                                .error_factory
+                                .type_mismatch(ValueTag::String, val, arg.span())
+                                .with_message("@log(..) takes a file path, or stderr")
+                                .into())
+                        }
+                    };
+                    Some(LogDestination::File(file_path.into()))
+                }
+            } else {
+                Some(LogDestination::Std)
+            }
+        } else {
+            None
+        };
 
-                return Ok(Some(r));
+        let max_events = if let Some(att) = self.attributes.get("max_events") {
+            if let Some(args) = att.params {
+                let [arg] = self.expect_x_args::<1>(args)?;
+                Some(self.evaluate_count(arg)?)
+            } else {
+                return Err(self
+                    .error_factory
+                    .required_args(att.identifier.span(), 1, 0)
+                    .with_message("@max_events(..) must be given a number, like @max_events(5)")
+                    .into());
             }
-            Set(ConstantDeclaration { identifier, value }) => {
-                let identifier = identifier.get()?;
-                if identifier.text != "BASE_URL" {
-                    return Err(self.error_factory.unknown_constant(identifier).into());
+        } else {
+            None
+        };
+
+        let timeout = if let Some(att) = self.attributes.get("timeout") {
+            if let Some(args) = att.params {
+                let [arg] = self.expect_x_args::<1>(args)?;
+                Some(Duration::from_millis(self.evaluate_count(arg)? as u64))
+            } else {
+                return Err(self
+                    .error_factory
+                    .required_args(att.identifier.span(), 1, 0)
+                    .with_message("@timeout(..) must be given milliseconds, like @timeout(5000)")
+                    .into());
+            }
+        } else {
+            self.default_timeout
+        };
+
+        let expected_status = if let Some(att) = self.attributes.get("expect") {
+            if let Some(args) = att.params {
+                let [arg] = self.expect_x_args::<1>(args)?;
+                match self.evaluate_expression(arg)? {
+                    Value::Number(n) if n.fract() == 0.0 && (100.0..600.0).contains(&n) => {
+                        Some(n as u16)
+                    }
+                    val @ Value::Number(_) => {
+                        return Err(self
+                            .error_factory
+                            .other(arg.span(), format!("{val} is not a status code"))
+                            .into())
+                    }
+                    val => {
+                        return Err(self
+                            .error_factory
+                            .type_mismatch(ValueTag::Number, val, arg.span())
+                            .into())
+                    }
                 }
+            } else {
+                return Err(self
+                    .error_factory
+                    .required_args(att.identifier.span(), 1, 0)
+                    .with_message("@expect(..) must be given a status code, like @expect(200)")
+                    .into());
+            }
+        } else {
+            None
+        };
 
-                self.base_url = match self.evaluate_expression(value)? {
-                    Value::String(s) => Some(s),
-                    expr => {
+        let body_assertions = self
+            .attributes
+            .get_all("assert_contains")
+            .map(|att| self.evaluate_body_assertion(att.identifier, att.params))
+            .collect::<Result<_>>()?;
+
+        let response_format = if let Some(att) = self.attributes.get("as") {
+            if let Some(args) = att.params {
+                let [arg] = self.expect_x_args::<1>(args)?;
+                match self.evaluate_expression(arg)? {
+                    Value::String(format) => match format.as_str() {
+                        "json" => Some(ResponseFormat::Json),
+                        "text" => Some(ResponseFormat::Text),
+                        _ => {
+                            return Err(self
+                                .error_factory
+                                .other(arg.span(), format!("unknown response format {format:?}"))
+                                .with_message("expected \"json\" or \"text\"")
+                                .into())
+                        }
+                    },
+                    val => {
                         return Err(self
                             .error_factory
-                            .type_mismatch(ValueTag::String, expr, value.span())
+                            .type_mismatch(ValueTag::String, val, arg.span())
                             .into())
                     }
-                };
+                }
+            } else {
+                return Err(self
+                    .error_factory
+                    .required_args(att.identifier.span(), 1, 0)
+                    .with_message("@as(..) must be given a format, like @as(\"json\")")
+                    .into());
             }
-            LineComment(_) => {}
-            Attribute(ast::Attribute {
-                identifier,
-                arguments,
-                ..
-            }) => {
-                let identifier = identifier.get()?;
+        } else {
+            None
+        };
 
-                match identifier.text {
-                    "name" | "log" | "dbg" | "skip" => {
-                        if self.attributes.has(identifier.text) {
-                            return Err(self.error_factory.duplicate_attribute(identifier).into());
+        let repeat = if let Some(att) = self.attributes.get("repeat") {
+            if let Some(args) = att.params {
+                let [arg] = self.expect_x_args::<1>(args)?;
+                let repeat = self.evaluate_count(arg)?;
+
+                if repeat > MAX_REPEAT {
+                    return Err(self
+                        .error_factory
+                        .other(
+                            arg.span(),
+                            format!("@repeat(..) sends a request at most {MAX_REPEAT} times, but was given {repeat}"),
+                        )
+                        .into());
+                }
+
+                repeat
+            } else {
+                return Err(self
+                    .error_factory
+                    .required_args(att.identifier.span(), 1, 0)
+                    .with_message("@repeat(..) must be given a number, like @repeat(5)")
+                    .into());
+            }
+        } else {
+            1
+        };
+
+        let download = match self.attributes.get("download") {
+            Some(att) => match att.params {
+                Some(args) => {
+                    let [arg] = self.expect_x_args::<1>(args)?;
+                    match self.evaluate_expression(arg)? {
+                        Value::String(file_path) => Some(DownloadTarget::File(file_path.into())),
+                        val => {
+                            return Err(self
+                                .error_factory
+                                .type_mismatch(ValueTag::String, val, arg.span())
+                                .into())
                         }
-                        self.attributes.add(identifier, arguments.as_ref());
                     }
-                    _ => {
+                }
+                None => Some(DownloadTarget::ContentDisposition),
+            },
+            None => None,
+        };
+
+        let order = if let Some(att) = self.attributes.get("order") {
+            if let Some(args) = att.params {
+                let [arg] = self.expect_x_args::<1>(args)?;
+                match self.evaluate_expression(arg)? {
+                    Value::Number(n) => n,
+                    val => {
+                        return Err(self
+                            .error_factory
+                            .type_mismatch(ValueTag::Number, val, arg.span())
+                            .into())
+                    }
+                }
+            } else {
+                return Err(self
+                    .error_factory
+                    .required_args(att.identifier.span(), 1, 0)
+                    .with_message("@order(..) must be given a number, like @order(10)")
+                    .into());
+            }
+        } else {
+            0.0
+        };
+
+        let chunked = match self.attributes.get("chunked") {
+            Some(att) if body.is_none() => {
+                return Err(self
+                    .error_factory
+                    .other(
+                        att.identifier.span(),
+                        "@chunked requests must have a body to send",
+                    )
+                    .into())
+            }
+            Some(_) => true,
+            None => false,
+        };
+
+        if let Some(att) = self.attributes.get("auth") {
+            let authorization = self.evaluate_auth(att.identifier, att.params)?;
+
+            if !headers
+                .iter()
+                .any(|h| h.name.eq_ignore_ascii_case("authorization"))
+            {
+                headers.push(Header::new("Authorization".to_string(), authorization));
+            }
+        }
+
+        let capture_env = match self.attributes.get("capture_env") {
+            Some(att) => Some(self.evaluate_env_capture(att.identifier, att.params)?),
+            None => None,
+        };
+
+        // generated once here, the same key is sent again on every repeat and retry, so that
+        // the server can tell they're the same request
+        if self.attributes.get("idempotency_key").is_some()
+            && !headers
+                .iter()
+                .any(|h| h.name.eq_ignore_ascii_case("idempotency-key"))
+        {
+            headers.push(Header::new(
+                "Idempotency-Key".to_string(),
+                builtin::uuid_v4(),
+            ));
+        }
+
+        if let Some(user_agent) = &self.user_agent {
+            if !headers
+                .iter()
+                .any(|h| h.name.eq_ignore_ascii_case("user-agent"))
+            {
+                headers.push(Header::new("User-Agent".to_string(), user_agent.clone()));
+            }
+        }
+
+        for default in self.default_headers.iter() {
+            if !headers
+                .iter()
+                .any(|h| h.name.eq_ignore_ascii_case(&default.name))
+            {
+                headers.push(default.clone());
+            }
+        }
+
+        let r = RequestItem {
+            name: name_of_request,
+            dbg: self.attributes.get("dbg").is_some(),
+            log_destination,
+            repeat,
+            order,
+            span,
+            capture_env,
+            expected_status,
+            body_assertions,
+            group: self.group.clone(),
+            request: super::ir::Request {
+                // graphql requests are sent as posts
+                method: match method {
+                    RequestMethod::GRAPHQL => RequestMethod::POST,
+                    method => *method,
+                },
+                url: append_query_params(&path, &query_params),
+                headers: headers.into(),
+                body,
+                max_events,
+                timeout,
+                download,
+                conditional: self.attributes.get("conditional").is_some(),
+                chunked,
+                response_format,
+            },
+        };
+
+        Ok(r)
+    }
+
+    /// Binds the variables declared with `@var(name, value)` on the request about to be
+    /// evaluated, returning the values they shadowed so they can be restored after it.
+    fn bind_request_vars(&mut self) -> Result<Vec<(&'source str, Option<Value>)>> {
+        let vars: Vec<_> = self
+            .attributes
+            .get_all("var")
+            .map(|att| (att.identifier, att.params))
+            .collect();
+
+        let mut bindings = vec![];
+
+        for (identifier, params) in vars {
+            let Some(args) = params else {
+                return Err(self
+                    .error_factory
+                    .required_args(identifier.span(), 2, 0)
+                    .with_message(
+                        "@var(..) must be given a name and a value, like @var(page, \"3\")",
+                    )
+                    .into());
+            };
+
+            let [name, value] = self.expect_x_args::<2>(args)?;
+
+            let name = match name {
+                Expression::Identifier(token) => token.get()?.text,
+                _ => {
+                    return Err(self
+                        .error_factory
+                        .other(name.span(), "expected a variable name")
+                        .with_message("like the page in @var(page, \"3\")")
+                        .into())
+                }
+            };
+
+            // binding the same name twice would restore the first value, not the one before it
+            if bindings.iter().any(|(bound, _)| *bound == name) {
+                return Err(self
+                    .error_factory
+                    .duplicate_attribute(identifier)
+                    .with_message(&format!("{name} is already bound by another @var(..)"))
+                    .into());
+            }
+
+            bindings.push((name, self.evaluate_expression(value)?));
+        }
+
+        // Values are evaluated before any are bound, so they all see the outer bindings
+        Ok(bindings
+            .into_iter()
+            .map(|(name, value)| (name, self.let_bindings.insert(name, value)))
+            .collect())
+    }
+
+    /// The content type of a body that's just a `read(..)` call, going by the file's extension.
+    fn content_type_of_read_call(&self, body: &Expression<'source>) -> Option<&'static str> {
+        let Expression::Call(ast::CallExpr {
+            identifier,
+            arguments,
+        }) = body
+        else {
+            return None;
+        };
+
+        if identifier.get().ok()?.text != "read" {
+            return None;
+        }
+
+        // the body evaluated fine, so the argument will too
+        let [arg] = self.expect_x_args::<1>(arguments).ok()?;
+
+        match self.evaluate_expression(arg).ok()? {
+            Value::String(file_name) => builtin::content_type_of_file(file_name),
+            _ => None,
+        }
+    }
+
+    /// Url-encodes the fields of `body form { "user": "me" }`, in the order they're written.
+    fn evaluate_form_body(&self, value: &Expression<'source>) -> Result<String> {
+        let entries = match value {
+            Expression::Object(entries) => entries,
+            Expression::EmptyObject(_) => return Ok(String::new()),
+            value => {
+                let val = self.evaluate_expression(value)?;
+                return Err(self
+                    .error_factory
+                    .type_mismatch(ValueTag::Object, val, value.span())
+                    .into());
+            }
+        };
+
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+
+        for ast::ObjectEntry { key, value } in entries.entries() {
+            let field = match self.evaluate_expression(value)? {
+                Value::String(field) => field,
+                val @ (Value::Number(_) | Value::Bool(_)) => val.to_string(),
+                val => {
+                    return Err(self
+                        .error_factory
+                        .type_mismatch(ValueTag::String, val, value.span())
+                        .with_message("form fields can't be nested")
+                        .into())
+                }
+            };
+
+            form.append_pair(key.get()?.value, &field);
+        }
+
+        Ok(form.finish())
+    }
+
+    /// Catches malformed JSON in a body that's sent as JSON, going by the `Content-Type` header.
+    /// Without that header, a body that only looks like JSON is just warned about.
+    fn validate_json_body(&self, body: &str, span: Span, headers: &[Header]) -> Result<()> {
+        let content_type = headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case("content-type"))
+            .map(|h| h.value.to_ascii_lowercase());
+
+        let declared_json = content_type.as_deref().map(|value| {
+            let mime = value.split(';').next().unwrap_or_default().trim();
+            mime == "application/json" || mime.ends_with("+json")
+        });
+
+        let looks_like_json = matches!(body.trim_start().chars().next(), Some('{' | '['));
+
+        if declared_json == Some(false) || (declared_json.is_none() && !looks_like_json) {
+            return Ok(());
+        }
+
+        let Err(error) = serde_json::from_str::<serde_json::Value>(body) else {
+            return Ok(());
+        };
+
+        if declared_json.is_none() {
+            warn!(
+                "the body at {} looks like malformed JSON: {error}",
+                Location::from(span.start)
+            );
+            return Ok(());
+        }
+
+        Err(self
+            .error_factory
+            .other(span, format!("malformed JSON body: {error}"))
+            .with_message("the Content-Type header says it's JSON")
+            .into())
+    }
+
+    /// Evaluates the object of `set RETRY { count: 3, backoff: "exponential", base_ms: 200 }`,
+    /// which can also come from a variable.
+    fn evaluate_retry_policy(&self, value: &Expression<'source>) -> Result<RetryPolicy> {
+        let span = value.span();
+
+        let entries = match self.evaluate_expression(value)? {
+            Value::Object(entries) => entries,
+            val => {
+                return Err(self
+                    .error_factory
+                    .type_mismatch(ValueTag::Object, val, span)
+                    .with_message("like { count: 3, backoff: \"exponential\", base_ms: 200 }")
+                    .into())
+            }
+        };
+
+        let mut policy = RetryPolicy {
+            status_codes: [502, 503, 504].into(),
+            retries: 3,
+            backoff: Backoff::None,
+            idempotent_only: false,
+        };
+
+        let mut backoff = None;
+        let mut base = Duration::from_millis(200);
+
+        // in a steady order, so that the same mistake is always the one reported
+        let mut entries: Vec<_> = entries.into_iter().collect();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        // an object literal's mistakes can be pointed at exactly, a variable's only as a whole
+        let spans_of = |key: &str| match value {
+            Expression::Object(literal) => literal
+                .entries()
+                .find_map(|entry| {
+                    let literal_key = entry.key.get().ok()?;
+                    (literal_key.value == key).then(|| (literal_key.span, entry.value.span()))
+                })
+                .unwrap_or((span, span)),
+            _ => (span, span),
+        };
+
+        for (key, value) in entries {
+            let (key_span, span) = spans_of(&key);
+
+            match key.as_str() {
+                "count" => policy.retries = self.count_from(value, span)? as u32,
+                "base_ms" => base = Duration::from_millis(self.count_from(value, span)? as u64),
+                "backoff" => match value {
+                    Value::String(s) if matches!(s.as_str(), "none" | "linear" | "exponential") => {
+                        backoff = Some(s)
+                    }
+                    Value::String(s) => {
                         return Err(self
                             .error_factory
-                            .unsupported_attribute(identifier)
-                            .with_message(
-                                "@name, @log, @skip and @dbg are the only supported attributes",
-                            )
-                            .into());
+                            .other(span, format!("unknown backoff {s:?}"))
+                            .with_message("expected \"none\", \"linear\" or \"exponential\"")
+                            .into())
+                    }
+                    val => {
+                        return Err(self
+                            .error_factory
+                            .type_mismatch(ValueTag::String, val, span)
+                            .into())
+                    }
+                },
+                "idempotent_only" => match value {
+                    Value::Bool(b) => policy.idempotent_only = b,
+                    val => {
+                        return Err(self
+                            .error_factory
+                            .type_mismatch(ValueTag::Bool, val, span)
+                            .into())
                     }
+                },
+                "on" => {
+                    let codes = match value {
+                        Value::Array(codes) => codes,
+                        val => {
+                            return Err(self
+                                .error_factory
+                                .type_mismatch(ValueTag::Array, val, span)
+                                .with_message("expected status codes, like [502, 503]")
+                                .into())
+                        }
+                    };
+
+                    policy.status_codes = codes
+                        .iter()
+                        .map(|code| match code {
+                            Value::Number(n) if n.fract() == 0.0 && (100.0..600.0).contains(n) => {
+                                Ok(*n as u16)
+                            }
+                            _ => Err(self
+                                .error_factory
+                                .other(span, format!("{code} is not a status code"))
+                                .into()),
+                        })
+                        .collect::<Result<_>>()?;
+                }
+                unknown => {
+                    return Err(self
+                        .error_factory
+                        .other(key_span, format!("unknown retry option '{unknown}'"))
+                        .with_message("expected count, backoff, base_ms, on or idempotent_only")
+                        .into())
                 }
             }
-            Let(VariableDeclaration { identifier, value }) => {
-                let value = self.evaluate_expression(value)?;
-                self.let_bindings.insert(identifier.get()?.text, value);
+        }
+
+        policy.backoff = match backoff.as_deref() {
+            Some("linear") => Backoff::Linear(base),
+            Some("exponential") => Backoff::Exponential(base),
+            _ => Backoff::None,
+        };
+
+        Ok(policy)
+    }
+
+    /// The `Authorization` header value for `@auth("bearer", token)` or
+    /// `@auth("basic", username, password)`.
+    fn evaluate_auth(
+        &self,
+        identifier: &lexer::Token<'source>,
+        params: Option<&ast::ExpressionList<'source>>,
+    ) -> Result<String> {
+        let usage =
+            "like @auth(\"bearer\", env(\"TOKEN\")) or @auth(\"basic\", \"user\", \"pass\")";
+
+        let Some(args) = params else {
+            return Err(self
+                .error_factory
+                .required_args(identifier.span(), 2, 0)
+                .with_message(usage)
+                .into());
+        };
+
+        let Some(scheme_arg) = args.expressions().next() else {
+            return Err(self
+                .error_factory
+                .required_args(args.span, 2, 0)
+                .with_message(usage)
+                .into());
+        };
+
+        match self
+            .evaluate_string(scheme_arg)?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "bearer" => {
+                let [_, token] = self.expect_x_args::<2>(args)?;
+                Ok(format!("Bearer {}", self.evaluate_string(token)?))
             }
-            Expr(_) => {}
-            Error(err) => {
-                unreachable!(
-                    "all syntax errors should have been caught, but found {}",
-                    err
-                )
+            "basic" => {
+                use base64::Engine;
+
+                let [_, username, password] = self.expect_x_args::<3>(args)?;
+                let credentials = format!(
+                    "{}:{}",
+                    self.evaluate_string(username)?,
+                    self.evaluate_string(password)?
+                );
+
+                Ok(format!(
+                    "Basic {}",
+                    base64::engine::general_purpose::STANDARD.encode(credentials)
+                ))
             }
+            scheme => Err(self
+                .error_factory
+                .other(scheme_arg.span(), format!("unknown auth scheme {scheme:?}"))
+                .with_message("expected \"bearer\" or \"basic\"")
+                .into()),
+        }
+    }
+
+    /// Evaluates the name of the section in `@group("Auth")`.
+    fn evaluate_group(
+        &self,
+        identifier: &lexer::Token<'source>,
+        params: Option<&ast::ExpressionList<'source>>,
+    ) -> Result<String> {
+        let Some(args) = params else {
+            return Err(self
+                .error_factory
+                .required_args(identifier.span(), 1, 0)
+                .with_message("@group(..) must be given a name, like @group(\"Auth\")")
+                .into());
+        };
+
+        let [name] = self.expect_x_args::<1>(args)?;
+
+        self.evaluate_string(name)
+    }
+
+    /// Evaluates `@capture_env(TOKEN, "$.token")`, where the variable name can be written bare.
+    fn evaluate_env_capture(
+        &self,
+        identifier: &lexer::Token<'source>,
+        params: Option<&ast::ExpressionList<'source>>,
+    ) -> Result<EnvCapture> {
+        let Some(args) = params else {
+            return Err(self
+                .error_factory
+                .required_args(identifier.span(), 2, 0)
+                .with_message("like @capture_env(TOKEN, \"$.token\")")
+                .into());
+        };
+
+        let [variable, path] = self.expect_x_args::<2>(args)?;
+
+        let variable = match variable {
+            Expression::Identifier(ast::result::ParsedNode::Ok(token)) => token.text.to_string(),
+            exp => self.evaluate_string(exp)?,
+        };
+
+        let path = JsonPath::parse(&self.evaluate_string(path)?).map_err(|error| {
+            self.error_factory
+                .other(path.span(), error)
+                .with_message("like \"$.data.token\" or \"$.items[0].id\"")
+        })?;
+
+        Ok(EnvCapture { variable, path })
+    }
+
+    /// Whether the argument of `@skip_if(..)` is truthy, going by [Value::is_truthy], so that
+    /// an empty string, `"false"` or `"0"` skips nothing but any other string does.
+    fn evaluate_skip_if(&self) -> Result<bool> {
+        let Some(att) = self.attributes.get("skip_if") else {
+            return Ok(false);
+        };
+
+        let Some(args) = att.params else {
+            return Err(self
+                .error_factory
+                .required_args(att.identifier.span(), 1, 0)
+                .with_message("like @skip_if(env(\"CI\", \"\"))")
+                .into());
+        };
+
+        let [arg] = self.expect_x_args::<1>(args)?;
+
+        Ok(self.evaluate_expression(arg)?.is_truthy())
+    }
+
+    /// Evaluates ``@assert_contains(`"ok":true`)``, pointing at the whole attribute for when
+    /// the response fails it.
+    fn evaluate_body_assertion(
+        &self,
+        identifier: &lexer::Token<'source>,
+        params: Option<&ast::ExpressionList<'source>>,
+    ) -> Result<BodyAssertion> {
+        let Some(args) = params else {
+            return Err(self
+                .error_factory
+                .required_args(identifier.span(), 1, 0)
+                .with_message("like @assert_contains(`\"ok\":true`)")
+                .into());
+        };
+
+        let [substring] = self.expect_x_args::<1>(args)?;
+
+        Ok(BodyAssertion {
+            substring: self.evaluate_string(substring)?,
+            span: identifier.span().to_end_of(args.span),
+        })
+    }
+
+    fn evaluate_string(&self, exp: &Expression<'source>) -> Result<String> {
+        match self.evaluate_expression(exp)? {
+            Value::String(s) => Ok(s),
+            val => Err(self
+                .error_factory
+                .type_mismatch(ValueTag::String, val, exp.span())
+                .into()),
         }
+    }
+
+    /// Evaluates an expression that should be a positive whole number, like in `@repeat(5)`,
+    /// of at most [u32::MAX].
+    fn evaluate_count(&self, exp: &Expression<'source>) -> Result<usize> {
+        self.count_from(self.evaluate_expression(exp)?, exp.span())
+    }
 
-        Ok(None)
+    /// The positive whole number in `value`, which was evaluated from what's at `span`.
+    fn count_from(&self, value: Value, span: Span) -> Result<usize> {
+        match value {
+            Value::Number(n) if n > u32::MAX as f64 => Err(self
+                .error_factory
+                .other(
+                    span,
+                    format!("expected a number no bigger than {}", u32::MAX),
+                )
+                .into()),
+            Value::Number(n) if n >= 1.0 && n.fract() == 0.0 => Ok(n as usize),
+            Value::Number(n) => Err(self
+                .error_factory
+                .other(
+                    span,
+                    format!("expected a positive whole number, but found {n}"),
+                )
+                .into()),
+            val => Err(self
+                .error_factory
+                .type_mismatch(ValueTag::Number, val, span)
+                .into()),
+        }
     }
 
     fn evaluate_expression(&self, exp: &Expression<'source>) -> Result<Value> {
@@ -261,7 +1428,7 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
 
         let value = match exp {
             Identifier(token) => self.evaluate_identifier(token.get()?)?,
-            String(token) => token.value.into(),
+            String(token) => self.evaluate_string_literal(token)?,
             TemplateStringLiteral { parts, .. } => {
                 self.evaluate_template_string_literal_parts(parts)?
             }
@@ -291,6 +1458,19 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
             EmptyArray(_) => Value::Array(Box::new([])),
             EmptyObject(_) => Value::Object(HashMap::new()),
             Null(_) => Value::Null,
+            Request(request) => {
+                return Err(self
+                    .error_factory
+                    .other(request.span, "a request can only be bound with let")
+                    .into())
+            }
+            Conditional(expr) => {
+                if self.evaluate_expression(&expr.condition)?.is_truthy() {
+                    self.evaluate_expression(&expr.consequent)?
+                } else {
+                    self.evaluate_expression(&expr.alternate)?
+                }
+            }
             Error(err) => unreachable!(
                 "all syntax errors should have been caught, but found {}",
                 err
@@ -309,14 +1489,19 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
         let string_value = match identifier.get()?.text {
             "env" => self.evaluate_env_call(arguments)?,
             "read" => self.evaluate_read_call(arguments)?,
+            "read_bytes_base64" => self.evaluate_read_bytes_base64_call(arguments)?,
+            "stdin" => self.evaluate_stdin_call(arguments)?,
             "escape_new_lines" => self.evaluate_escapes_new_lines_call(arguments)?,
+            "repeat_string" => self.evaluate_repeat_string_call(arguments)?,
             "json" => self.evaluate_json_call(arguments)?,
+            "base64" => self.evaluate_base64_call(arguments)?,
+            "base64_decode" => self.evaluate_base64_decode_call(arguments)?,
             _ => {
                 return Err(self
                     .error_factory
                     .undefined_callable(identifier.get()?)
                     .with_message(
-                        "env(..), read(..), json(..), and escape_new_lines(..) are the only calls supported",
+                        "env(..), read(..), read_bytes_base64(..), stdin(), json(..), base64(..), base64_decode(..), escape_new_lines(..) and repeat_string(..) are the only calls supported",
                     )
                     .into())
             }
@@ -325,15 +1510,35 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
         Ok(string_value)
     }
 
+    /// Evaluates `env("NAME")`, or `env("NAME", "default")` to fall back on the second argument
+    /// when the variable isn't defined.
     fn evaluate_env_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
-        let [arg] = self.expect_x_args::<1>(arguments)?;
+        let args = arguments.expressions().collect::<Vec<_>>();
 
-        let value = match self.evaluate_expression(arg)? {
-            Value::String(variable) => builtin::call_env(self.env, &variable).ok_or_else(|| {
-                return self
+        let (arg, fallback) = match args[..] {
+            [arg] => (arg, None),
+            [arg, fallback] => (arg, Some(fallback)),
+            _ => {
+                return Err(self
                     .error_factory
-                    .env_variable_not_found(variable, arg.span());
-            })?,
+                    .required_args(arguments.span, 1, args.len())
+                    .with_message("expected a variable name, and optionally a default value")
+                    .into())
+            }
+        };
+
+        let value = match self.evaluate_expression(arg)? {
+            Value::String(variable) => match (builtin::call_env(self.env, &variable), fallback) {
+                (Some(value), _) => value,
+                (None, Some(fallback)) => self.evaluate_expression(fallback)?,
+                (None, None) if self.partial => Value::String(format!("${{env:{variable}}}")),
+                (None, None) => {
+                    return Err(self
+                        .error_factory
+                        .env_variable_not_found(variable, arg.span())
+                        .into())
+                }
+            },
             value => {
                 return Err(self
                     .error_factory
@@ -345,11 +1550,21 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
         Ok(value)
     }
 
+    /// Resolves a relative path against the folder of the script, when there is one.
+    fn resolve_path(&self, file_name: String) -> PathBuf {
+        let path = PathBuf::from(file_name);
+
+        match &self.script_dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path,
+        }
+    }
+
     fn evaluate_read_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
         let [arg] = self.expect_x_args::<1>(arguments)?;
 
         let value = match self.evaluate_expression(arg)? {
-            Value::String(file_name) => builtin::read_file(file_name)
+            Value::String(file_name) => builtin::read_file(self.resolve_path(file_name))
                 .map_err(|e| self.error_factory.other(arg.span(), e))?,
             value => {
                 return Err(self
@@ -362,6 +1577,50 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
         Ok(value)
     }
 
+    fn evaluate_stdin_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
+        let [] = self.expect_x_args::<0>(arguments)?;
+
+        match self.stdin {
+            Stdin::Empty => return Ok(Value::String(String::new())),
+            Stdin::Script => {
+                return Err(self
+                    .error_factory
+                    .other(
+                        arguments.span,
+                        "stdin was already read for the script itself",
+                    )
+                    .with_message("pass the script as a file to pipe something else into stdin()")
+                    .into())
+            }
+            Stdin::Read => {}
+        }
+
+        builtin::read_stdin().map_err(|e| {
+            self.error_factory
+                .other(arguments.span, format!("{e:#}"))
+                .into()
+        })
+    }
+
+    fn evaluate_read_bytes_base64_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
+        let [arg] = self.expect_x_args::<1>(arguments)?;
+
+        let value = match self.evaluate_expression(arg)? {
+            Value::String(file_name) => {
+                builtin::read_file_bytes_base64(self.resolve_path(file_name))
+                    .map_err(|e| self.error_factory.other(arg.span(), format!("{e:#}")))?
+            }
+            value => {
+                return Err(self
+                    .error_factory
+                    .type_mismatch(ValueTag::String, value, arg.span())
+                    .into())
+            }
+        };
+
+        Ok(value)
+    }
+
     fn evaluate_escapes_new_lines_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
         let [arg] = self.expect_x_args::<1>(arguments)?;
 
@@ -378,23 +1637,111 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
         Ok(v)
     }
 
-    fn evaluate_json_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
+    fn evaluate_base64_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
+        let [arg] = self.expect_x_args::<1>(arguments)?;
+
+        let v = match self.evaluate_expression(arg)? {
+            Value::String(s) => builtin::base64_encode(&s),
+            value => {
+                return Err(self
+                    .error_factory
+                    .type_mismatch(ValueTag::String, value, arg.span())
+                    .into())
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn evaluate_base64_decode_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
         let [arg] = self.expect_x_args::<1>(arguments)?;
 
+        let v = match self.evaluate_expression(arg)? {
+            Value::String(s) => builtin::base64_decode(&s)
+                .map_err(|e| self.error_factory.other(arg.span(), format!("{e:#}")))?,
+            value => {
+                return Err(self
+                    .error_factory
+                    .type_mismatch(ValueTag::String, value, arg.span())
+                    .into())
+            }
+        };
+
+        Ok(v)
+    }
+
+    fn evaluate_repeat_string_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
+        let [text, count] = self.expect_x_args::<2>(arguments)?;
+
+        let text = match self.evaluate_expression(text)? {
+            Value::String(s) => s,
+            value => {
+                return Err(self
+                    .error_factory
+                    .type_mismatch(ValueTag::String, value, text.span())
+                    .into())
+            }
+        };
+
+        let count_span = count.span();
+        let count = match self.evaluate_expression(count)? {
+            Value::Number(n) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+            Value::Number(n) => {
+                return Err(self
+                    .error_factory
+                    .other(
+                        count_span,
+                        format!("expected a whole number, but found {n}"),
+                    )
+                    .into())
+            }
+            value => {
+                return Err(self
+                    .error_factory
+                    .type_mismatch(ValueTag::Number, value, count_span)
+                    .into())
+            }
+        };
+
+        builtin::repeat_string(&text, count)
+            .map_err(|e| self.error_factory.other(count_span, e).into())
+    }
+
+    /// Evaluates `json(value)`, or `json(value, true)` to pretty-print it.
+    fn evaluate_json_call(&self, arguments: &ast::ExpressionList) -> Result<Value> {
+        let args = arguments.expressions().collect::<Vec<_>>();
+
+        let (arg, pretty) = match args[..] {
+            [arg] => (arg, false),
+            [arg, pretty] => match self.evaluate_expression(pretty)? {
+                Value::Bool(pretty) => (arg, pretty),
+                val => {
+                    return Err(self
+                        .error_factory
+                        .type_mismatch(ValueTag::Bool, val, pretty.span())
+                        .into())
+                }
+            },
+            _ => {
+                return Err(self
+                    .error_factory
+                    .required_args(arguments.span, 1, args.len())
+                    .with_message("expected a value, and optionally whether to pretty-print it")
+                    .into())
+            }
+        };
+
         let value = self.evaluate_expression(arg)?;
 
-        Ok(builtin::json_stringify(value))
+        Ok(builtin::json_stringify(value, pretty))
     }
 
     fn evaluate_request_endpoint(&self, endpoint: &Endpoint) -> Result<String> {
         let url = match endpoint {
             Endpoint::Url(url) => url.value.to_string(),
             Endpoint::Pathname(pn) => {
-                if let Some(mut base_url) = self.base_url.clone() {
-                    if pn.value.len() > 1 {
-                        base_url.push_str(pn.value);
-                    }
-                    base_url
+                if let Some(base_url) = self.base_url.as_deref() {
+                    join_base_url(base_url, pn.value)
                 } else {
                     return Err(self.error_factory.unset_base_url(pn.span).into());
                 }
@@ -422,6 +1769,16 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
         Ok(value.to_owned())
     }
 
+    fn evaluate_string_literal(&self, string: &ast::StringLiteral<'source>) -> Result<Value> {
+        if !string.raw.starts_with(['"', '\'']) {
+            return Ok(string.value.into());
+        }
+
+        lexer::unescape_string(string.value)
+            .map(Value::String)
+            .map_err(|message| self.error_factory.other(string.span, message).into())
+    }
+
     fn evaluate_template_string_literal_parts(
         &self,
         parts: &[TemplateStringPart<'source>],
@@ -444,7 +1801,9 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
                         }
                     }
                 }
-                TemplateStringPart::StringPart(string) => string.value.into(),
+                TemplateStringPart::StringPart(string) => {
+                    lexer::unescape_template_string(string.value)
+                }
             };
 
             strings.push(value.to_string());
@@ -456,25 +1815,104 @@ impl<'source, 'p, 'env> Evaluator<'source, 'p, 'env> {
     fn expect_x_args<'a, const N: usize>(
         &self,
         args: &'a ast::ExpressionList<'source>,
-    ) -> Result<[&'a ast::Expression; N]> {
-        if args.items.len() != N {
-            return Err(self
-                .error_factory
-                .required_args(args.span, N, args.items.len())
-                .into());
-        };
+    ) -> Result<[&'a ast::Expression<'source>; N]> {
+        let arguments = args.expressions().collect::<Vec<_>>();
 
-        // SAFETY: we're checking above N equals how many args we got
-        // so there will be no nulls in the returned value.
-        let mut arguments = unsafe {
-            let null: *const ast::Expression = std::ptr::null();
-            [&*null; N]
-        };
+        arguments.try_into().map_err(|arguments: Vec<_>| {
+            self.error_factory
+                .required_args(args.span, N, arguments.len())
+                .into()
+        })
+    }
+}
 
-        for (i, arg) in args.expressions().enumerate() {
-            arguments[i] = arg;
-        }
+/// Joins a pathname onto the base url with exactly one slash between them, regardless of
+/// whether the base url ends with one. A bare `/` leaves the base url as it was written.
+fn join_base_url(base_url: &str, pathname: &str) -> String {
+    let pathname = pathname.trim_start_matches('/');
+
+    if pathname.is_empty() {
+        return base_url.to_string();
+    }
+
+    format!("{}/{}", base_url.trim_end_matches('/'), pathname)
+}
+
+/// Appends the url-encoded parameters to the query string of the url, after any it already
+/// has, keeping repeated names as repeated parameters.
+fn append_query_params(url: &str, params: &[(String, String)]) -> String {
+    if params.is_empty() {
+        return url.to_string();
+    }
+
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(params)
+        .finish();
+
+    let (url, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    };
+
+    let separator = match url.split_once('?') {
+        None => "?",
+        Some((_, "")) => "",
+        Some((_, existing)) if existing.ends_with('&') => "",
+        Some(_) => "&",
+    };
+
+    match fragment {
+        Some(fragment) => format!("{url}{separator}{query}#{fragment}"),
+        None => format!("{url}{separator}{query}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{append_query_params, join_base_url};
+
+    #[test]
+    fn appends_query_params_after_any_existing_ones() {
+        let params = [
+            ("sort".to_string(), "asc".to_string()),
+            ("tag".to_string(), "a b&c".to_string()),
+            ("tag".to_string(), "d".to_string()),
+        ];
+
+        assert_eq!(
+            append_query_params("http://x/api", &params),
+            "http://x/api?sort=asc&tag=a+b%26c&tag=d"
+        );
+        assert_eq!(
+            append_query_params("http://x/api?page=2", &params[..1]),
+            "http://x/api?page=2&sort=asc"
+        );
+        assert_eq!(
+            append_query_params("http://x/api?", &params[..1]),
+            "http://x/api?sort=asc"
+        );
+        assert_eq!(
+            append_query_params("http://x/api#top", &params[..1]),
+            "http://x/api?sort=asc#top"
+        );
+        assert_eq!(append_query_params("http://x/api", &[]), "http://x/api");
+    }
+
+    #[test]
+    fn joins_with_exactly_one_slash() {
+        assert_eq!(join_base_url("http://x", "/api"), "http://x/api");
+        assert_eq!(join_base_url("http://x/", "/api"), "http://x/api");
+        assert_eq!(join_base_url("http://x", "api"), "http://x/api");
+        assert_eq!(join_base_url("http://x/", "api"), "http://x/api");
+        assert_eq!(
+            join_base_url("http://x/v1/", "/api/users/"),
+            "http://x/v1/api/users/"
+        );
+    }
 
-        Ok(arguments)
+    #[test]
+    fn a_bare_slash_leaves_the_base_url_as_is() {
+        assert_eq!(join_base_url("http://x", "/"), "http://x");
+        assert_eq!(join_base_url("http://x/", "/"), "http://x/");
     }
 }
@@ -1,5 +1,8 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
+use crate::interpreter::json_path::JsonPath;
+use crate::interpreter::runner::RetryPolicy;
 use crate::interpreter::value::Value;
 use crate::lexer::locations::Span;
 pub use crate::parser::ast::RequestMethod;
@@ -9,6 +12,10 @@ pub struct Program<'source> {
     pub source: &'source str,
     pub items: Box<[RequestItem]>,
     pub let_bindings: HashMap<Box<str>, Value>,
+    /// From `set RETRY { .. }`, for every request.
+    pub retry: Option<RetryPolicy>,
+    /// From `set ALLOW_INSECURE_LOCALHOST true`, to skip verifying certificates of local servers.
+    pub allow_insecure_localhost: bool,
 }
 
 impl<'source> Program<'source> {
@@ -21,6 +28,8 @@ impl<'source> Program<'source> {
             source,
             items,
             let_bindings,
+            retry: None,
+            allow_insecure_localhost: false,
         }
     }
 }
@@ -32,14 +41,44 @@ pub struct RequestItem {
     pub span: Span,
     pub request: Request,
     pub log_destination: Option<LogDestination>,
+    /// How many times to send the request, at least once.
+    pub repeat: usize,
+    /// Requests are run from the lowest order to the highest, in document order when tied.
+    pub order: f64,
+    /// Where to save a value out of the response into the environment, from `@capture_env(..)`.
+    pub capture_env: Option<EnvCapture>,
+    /// The status the response must have, from `@expect(..)`.
+    pub expected_status: Option<u16>,
+    /// What the response body must contain, from each `@assert_contains(..)`.
+    pub body_assertions: Box<[BodyAssertion]>,
+    /// The section it's in, from the last `@group(..)` before it.
+    pub group: Option<String>,
+}
+
+/// A substring that the response body must contain.
+#[derive(Debug)]
+pub struct BodyAssertion {
+    pub substring: String,
+    /// Of the attribute, to point at when the response fails it.
+    pub span: Span,
+}
+
+/// Saves the value at `path` in a JSON response as the environment variable `variable`, for
+/// later runs and commands.
+#[derive(Debug)]
+pub struct EnvCapture {
+    pub variable: String,
+    pub path: JsonPath,
 }
 
 #[derive(Debug)]
 pub enum LogDestination {
+    Std,
+    Stderr,
     File(std::path::PathBuf),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Header {
     pub name: String,
     pub value: String,
@@ -49,12 +88,79 @@ impl Header {
     pub fn new(name: String, value: String) -> Self {
         Self { name, value }
     }
+
+    /// Rewrites the name in Train-Case, e.g. `content-type` becomes `Content-Type`.
+    pub fn canonicalize_name(&mut self) {
+        self.name = self
+            .name
+            .split('-')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => {
+                        first.to_ascii_uppercase().to_string()
+                            + &chars.as_str().to_ascii_lowercase()
+                    }
+                    None => String::new(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("-");
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Request {
     pub method: RequestMethod,
     pub url: String,
     pub headers: Box<[Header]>,
     pub body: Option<String>,
+    /// How many server-sent events to read from a `text/event-stream` response
+    /// before closing the connection.
+    pub max_events: Option<usize>,
+    /// How long to wait for the whole request, from `@timeout(ms)`.
+    pub timeout: Option<Duration>,
+    /// Where to save the raw response body, instead of printing it out.
+    pub download: Option<DownloadTarget>,
+    /// Send the validators of the last response to the same url, if any, so that the server
+    /// can respond with `304 Not Modified` when nothing changed.
+    pub conditional: bool,
+    /// Send the body with `Transfer-Encoding: chunked`, instead of a `Content-Length`.
+    pub chunked: bool,
+    /// How to print the response body, whatever its `Content-Type` says.
+    pub response_format: Option<ResponseFormat>,
+}
+
+/// From `@as("json")`, for servers that mislabel their responses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseFormat {
+    Json,
+    Text,
+}
+
+#[derive(Debug, Clone)]
+pub enum DownloadTarget {
+    File(std::path::PathBuf),
+    /// The file name suggested by the response's `Content-Disposition` header.
+    ContentDisposition,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Header;
+
+    fn canonical(name: &str) -> String {
+        let mut header = Header::new(name.to_string(), String::new());
+        header.canonicalize_name();
+        header.name
+    }
+
+    #[test]
+    fn canonicalizes_header_names_to_train_case() {
+        assert_eq!(canonical("content-type"), "Content-Type");
+        assert_eq!(canonical("CONTENT-TYPE"), "Content-Type");
+        assert_eq!(canonical("x-rEqUeSt-id"), "X-Request-Id");
+        assert_eq!(canonical("Authorization"), "Authorization");
+        assert_eq!(canonical("x--weird-"), "X--Weird-");
+    }
 }
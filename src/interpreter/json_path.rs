@@ -0,0 +1,130 @@
+/// A path into a JSON value, like `$.data.items[0]["display name"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl JsonPath {
+    /// Parses a path starting at `$`, followed by any number of `.key`, `["key"]` or `[index]`.
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let mut rest = path
+            .trim()
+            .strip_prefix('$')
+            .ok_or_else(|| format!("{path:?} must start with $"))?;
+
+        let mut segments = vec![];
+
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let end = after_dot.find(['.', '[']).unwrap_or(after_dot.len());
+                let key = &after_dot[..end];
+
+                if key.is_empty() {
+                    return Err(format!("expected a key after the '.' in {path:?}"));
+                }
+
+                segments.push(Segment::Key(key.to_string()));
+                rest = &after_dot[end..];
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = after_bracket
+                    .find(']')
+                    .ok_or_else(|| format!("missing a closing ']' in {path:?}"))?;
+                let inside = after_bracket[..end].trim();
+
+                let segment = match inside
+                    .strip_prefix('"')
+                    .and_then(|key| key.strip_suffix('"'))
+                {
+                    Some(key) => Segment::Key(key.to_string()),
+                    None => Segment::Index(inside.parse().map_err(|_| {
+                        format!(
+                            "expected an index or a quoted key, but found {inside:?} in {path:?}"
+                        )
+                    })?),
+                };
+
+                segments.push(segment);
+                rest = &after_bracket[end + 1..];
+            } else {
+                return Err(format!(
+                    "expected '.' or '[' in {path:?}, but found {rest:?}"
+                ));
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// The value at this path, if there's one.
+    pub fn select<'v>(&self, value: &'v serde_json::Value) -> Option<&'v serde_json::Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                Segment::Key(key) => value.get(key),
+                Segment::Index(index) => value.get(index),
+            })
+    }
+}
+
+impl std::fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "$")?;
+
+        for segment in &self.segments {
+            match segment {
+                Segment::Key(key) if key.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+                    write!(f, ".{key}")?
+                }
+                Segment::Key(key) => write!(f, "[{key:?}]")?,
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::JsonPath;
+
+    #[test]
+    fn selects_keys_and_indices() {
+        let value = json!({ "data": { "items": [{ "id": 1 }, { "display name": "two" }] } });
+
+        let select = |path| JsonPath::parse(path).unwrap().select(&value).cloned();
+
+        assert_eq!(select("$"), Some(value.clone()));
+        assert_eq!(select("$.data.items[0].id"), Some(json!(1)));
+        assert_eq!(
+            select("$.data.items[1][\"display name\"]"),
+            Some(json!("two"))
+        );
+        assert_eq!(select("$.data.items[2]"), None);
+        assert_eq!(select("$.missing"), None);
+    }
+
+    #[test]
+    fn prints_paths_back_out() {
+        let path = "$.data.items[1][\"display name\"]";
+
+        assert_eq!(JsonPath::parse(path).unwrap().to_string(), path);
+    }
+
+    #[test]
+    fn rejects_malformed_paths() {
+        assert!(JsonPath::parse("data.items").is_err());
+        assert!(JsonPath::parse("$.").is_err());
+        assert!(JsonPath::parse("$.items[0").is_err());
+        assert!(JsonPath::parse("$.items[first]").is_err());
+        assert!(JsonPath::parse("$items").is_err());
+    }
+}
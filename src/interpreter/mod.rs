@@ -1,28 +1,125 @@
 mod attributes;
 mod builtin;
+pub mod cassette_runner;
+mod conditional_cache;
+pub mod dry_runner;
 pub mod environment;
 pub mod error;
 mod eval;
 pub mod ir;
+pub mod json_path;
 pub mod runner;
 pub mod ureq_runner;
 pub mod value;
 
 use std::io::{stdin, Read};
+use std::path::{Path, PathBuf};
 
 use anyhow::anyhow;
 use environment::Environment;
 use error::InterpreterError;
 
 use crate::error::ColoredMetaError;
+use crate::lexer::locations::Position;
 use crate::parser::ast::{self};
 
 use crate::parser::error::ParserErrors;
 
+/// How to interpret a program, past the defaults of [ast::Program::interpret].
+#[derive(Debug, Default)]
+pub struct InterpretOptions {
+    /// Resolve pathnames against this, whatever the program sets as its `BASE_URL`.
+    pub base_url: Option<String>,
+    /// Bound to `input`, like data given on the command line.
+    pub input: Option<value::Value>,
+    /// Evaluate `env(..)` of missing variables to `${env:NAME}` placeholders.
+    pub partial: bool,
+    /// The folder of the script, which relative paths given to `read(..)` are resolved
+    /// against, instead of the current directory.
+    pub script_dir: Option<PathBuf>,
+    /// Send the requests bound with `let` like these options send every other request,
+    /// binding their responses; they're bound as null otherwise.
+    pub send_bound_requests: Option<runner::RunOptions>,
+    /// Only send the bound requests that the requests starting at these positions use, binding
+    /// the rest as null; all of them are sent when this is `None`.
+    pub only_send_bound_requests_used_by: Option<Vec<Position>>,
+    /// What `stdin()` evaluates to.
+    pub stdin: Stdin,
+}
+
+/// What `stdin()` evaluates to.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Stdin {
+    /// An empty string, so that the language server and the like don't wait on input that
+    /// never comes.
+    #[default]
+    Empty,
+    /// All of standard input.
+    Read,
+    /// Nothing, it's an error, since standard input was already read for the script itself.
+    Script,
+}
+
 impl<'source> ast::Program<'source> {
+    /// Requests bound with `let` aren't sent, their responses are bound as null;
+    /// [InterpretOptions::send_bound_requests] is what sends them.
     pub fn interpret(
         &self,
         env: &Environment,
+    ) -> std::result::Result<ir::Program<'source>, InterpreterError<'source>> {
+        self.interpret_with(env, InterpretOptions::default())
+    }
+
+    /// Like [Self::interpret], but with pathnames resolved against `base_url`, whatever the
+    /// program sets as its `BASE_URL`.
+    pub fn interpret_with_base_url(
+        &self,
+        env: &Environment,
+        base_url: &str,
+    ) -> std::result::Result<ir::Program<'source>, InterpreterError<'source>> {
+        self.interpret_with(
+            env,
+            InterpretOptions {
+                base_url: Some(base_url.to_string()),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Like [Self::interpret], with `input` bound to the given value.
+    pub fn interpret_with_input(
+        &self,
+        env: &Environment,
+        input: value::Value,
+    ) -> std::result::Result<ir::Program<'source>, InterpreterError<'source>> {
+        self.interpret_with(
+            env,
+            InterpretOptions {
+                input: Some(input),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Like [Self::interpret], but `env(..)` of missing variables evaluates to `${env:NAME}`
+    /// placeholders instead of failing.
+    pub fn interpret_partially(
+        &self,
+        env: &Environment,
+    ) -> std::result::Result<ir::Program<'source>, InterpreterError<'source>> {
+        self.interpret_with(
+            env,
+            InterpretOptions {
+                partial: true,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn interpret_with(
+        &self,
+        env: &Environment,
+        options: InterpretOptions,
     ) -> std::result::Result<ir::Program<'source>, InterpreterError<'source>> {
         let parse_errors = self.errors();
 
@@ -32,11 +129,37 @@ impl<'source> ast::Program<'source> {
 
         let mut interpreter = eval::Evaluator::new(self, env);
 
+        if let Some(base_url) = options.base_url {
+            interpreter = interpreter.with_base_url(base_url);
+        }
+
+        if let Some(input) = options.input {
+            interpreter = interpreter.with_input(input);
+        }
+
+        if options.partial {
+            interpreter = interpreter.partial();
+        }
+
+        if let Some(script_dir) = options.script_dir {
+            interpreter = interpreter.with_script_dir(script_dir);
+        }
+
+        if let Some(run_options) = options.send_bound_requests {
+            interpreter = interpreter.running_bound_requests(run_options);
+        }
+
+        if let Some(requests) = options.only_send_bound_requests_used_by {
+            interpreter = interpreter.only_running_bound_requests_used_by(&requests);
+        }
+
+        interpreter = interpreter.with_stdin(options.stdin);
+
         let items = interpreter
             .evaluate()
             .map_err(InterpreterError::EvalErrors)?;
 
-        Ok(ir::Program::new(
+        let mut program = ir::Program::new(
             self.source,
             items.into(),
             interpreter
@@ -44,14 +167,82 @@ impl<'source> ast::Program<'source> {
                 .into_iter()
                 .map(|(key, value)| (key.into(), value))
                 .collect(),
-        ))
+        );
+
+        program.retry = interpreter.retry;
+        program.allow_insecure_localhost = interpreter.allow_insecure_localhost;
+
+        Ok(program)
     }
 }
 
-pub fn interpret_program(code: &str, env: Environment) -> anyhow::Result<ir::Program<'_>> {
+/// Interprets the program, with its errors colored for printing out. Requests bound with `let`
+/// are only sent with [InterpretOptions::send_bound_requests].
+pub fn interpret_program<'source>(
+    code: &'source str,
+    env: &Environment,
+) -> anyhow::Result<ir::Program<'source>> {
+    interpret_program_with_options(code, env, InterpretOptions::default())
+}
+
+/// Same as [interpret_program], with pathnames resolved against `base_url`.
+pub fn interpret_program_with_base_url<'source>(
+    code: &'source str,
+    env: &Environment,
+    base_url: &str,
+) -> anyhow::Result<ir::Program<'source>> {
+    let options = InterpretOptions {
+        base_url: Some(base_url.to_string()),
+        ..Default::default()
+    };
+
+    interpret_program_with_options(code, env, options)
+}
+
+/// Same as [interpret_program], with `input` bound to the given value.
+pub fn interpret_program_with_input<'source>(
+    code: &'source str,
+    env: &Environment,
+    input: value::Value,
+) -> anyhow::Result<ir::Program<'source>> {
+    let options = InterpretOptions {
+        input: Some(input),
+        ..Default::default()
+    };
+
+    interpret_program_with_options(code, env, options)
+}
+
+/// Same as [interpret_program], with placeholders for the env variables that are missing.
+pub fn interpret_program_partially<'source>(
+    code: &'source str,
+    env: &Environment,
+) -> anyhow::Result<ir::Program<'source>> {
+    let options = InterpretOptions {
+        partial: true,
+        ..Default::default()
+    };
+
+    interpret_program_with_options(code, env, options)
+}
+
+/// Same as [interpret_program], with any of the [InterpretOptions].
+pub fn interpret_program_with_options<'source>(
+    code: &'source str,
+    env: &Environment,
+    options: InterpretOptions,
+) -> anyhow::Result<ir::Program<'source>> {
     let program = ast::Program::from(code);
 
-    let program = program.interpret(&env).map_err(|value| match value {
+    let program = program
+        .interpret_with(env, options)
+        .map_err(into_colored_error)?;
+
+    Ok(program)
+}
+
+fn into_colored_error(error: InterpreterError) -> anyhow::Error {
+    match error {
         InterpreterError::ParseErrors(p) => {
             let error_string: String = p
                 .errors
@@ -69,9 +260,13 @@ pub fn interpret_program(code: &str, env: Environment) -> anyhow::Result<ir::Pro
 
             return anyhow!(error_string);
         }
-    })?;
+    }
+}
 
-    Ok(program)
+/// The folder of the script at `file`, or none for a script read from stdin.
+pub fn script_dir(file: Option<&Path>) -> Option<PathBuf> {
+    file.and_then(|path| path.canonicalize().ok())
+        .and_then(|path| path.parent().map(Path::to_path_buf))
 }
 
 pub fn read_program_text(file: Option<std::path::PathBuf>) -> anyhow::Result<String> {
@@ -4,20 +4,64 @@ use crate::{
     error::ColoredMetaError,
     error_meta::ToContextualError,
     interpreter::{
+        cassette_runner::{CassetteRun, Cassettes},
+        conditional_cache::ConditionalCache,
+        dry_runner::DryRun,
+        environment::Environment,
         ir::{self, *},
-        ureq_runner::UreqRun,
+        ureq_runner::{ResponseErrorString, UreqRun},
+        value::Value,
     },
 };
 use string_utils::*;
 
 use std::error::Error;
+use std::time::{Duration, Instant};
 
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 #[derive(Debug)]
 pub enum RunResponse {
     Success(String),
+    /// The server responded with an error status, 4xx or 5xx.
+    ErrorStatus {
+        status: u16,
+        body: String,
+    },
+    /// No response came, e.g. the host couldn't be resolved, the connection was refused, or
+    /// it timed out.
+    Transport(String),
+    /// A response came, but its body couldn't be read.
+    BodyRead(String),
+    /// The request went through, but handling its response failed, e.g. saving it to a file.
     Failure(String),
+    /// The response failed an assertion, like the status that `@expect(..)` asked for.
+    Assertion(String),
+}
+
+impl RunResponse {
+    pub fn is_success(&self) -> bool {
+        matches!(self, RunResponse::Success(_))
+    }
+
+    /// Sorts out why running a request failed.
+    fn from_error(error: &(dyn Error + 'static)) -> Self {
+        if let Some(e) = error.downcast_ref::<ResponseErrorString>() {
+            return match (e.status, &e.body) {
+                (Some(status), body) => RunResponse::ErrorStatus {
+                    status,
+                    body: body.clone().unwrap_or_default(),
+                },
+                (None, _) => RunResponse::Transport(e.to_string()),
+            };
+        }
+
+        if error.is::<std::io::Error>() || error.is::<serde_json::Error>() {
+            return RunResponse::BodyRead(error.to_string());
+        }
+
+        RunResponse::Failure(error.to_string())
+    }
 }
 
 impl<'source> ir::Program<'source> {
@@ -25,23 +69,201 @@ impl<'source> ir::Program<'source> {
         self,
         request_names: Option<&[String]>,
     ) -> Vec<(request_id::RequestId, RunResponse)> {
-        Runner::new(self, Box::new(UreqRun)).run(request_names)
+        self.run_ureq_with_options(request_names, RunOptions::default())
+    }
+
+    pub fn run_ureq_with_options(
+        self,
+        request_names: Option<&[String]>,
+        options: RunOptions,
+    ) -> Vec<(request_id::RequestId, RunResponse)> {
+        let strategy = run_strategy(&options, self.allow_insecure_localhost);
+
+        Runner::new(self, strategy, options).run(request_names)
+    }
+}
+
+/// How requests are sent with these options.
+fn run_strategy(options: &RunOptions, allow_insecure_localhost: bool) -> Box<dyn RunStrategy> {
+    let ureq_run = if allow_insecure_localhost {
+        UreqRun::allowing_insecure_localhost()
+    } else {
+        UreqRun::default()
+    };
+
+    match options.cassettes.clone() {
+        _ if options.dry_run => Box::new(DryRun),
+        Some(cassettes) => Box::new(CassetteRun::new(cassettes, ureq_run)),
+        None => Box::new(ureq_run),
+    }
+}
+
+/// Sends a request bound with `let` the way every other request is sent with these options,
+/// with the retry policy of the script, unless the options have one of their own.
+pub fn send_bound_request(
+    request: &Request,
+    options: &RunOptions,
+    allow_insecure_localhost: bool,
+    retry: Option<&RetryPolicy>,
+) -> std::result::Result<Response, Box<dyn Error>> {
+    let mut request = request.clone();
+
+    if options.canonicalize_headers {
+        for header in request.headers.iter_mut() {
+            header.canonicalize_name();
+        }
+    }
+
+    if options.trace_requests {
+        eprintln!("{}", wire::raw_request(&request).dimmed());
+    }
+
+    let mut strategy = run_strategy(options, allow_insecure_localhost);
+
+    let result = run_request_with_retries(
+        strategy.as_mut(),
+        options.retry.as_ref().or(retry),
+        &request,
+    );
+
+    if let (true, Some(head)) = (options.trace_requests, wire::raw_response_head_of(&result)) {
+        eprintln!("{}", head.dimmed());
+    }
+
+    result
+}
+
+/// Knobs that apply to every request in a run.
+#[derive(Debug, Default, Clone)]
+pub struct RunOptions {
+    pub retry: Option<RetryPolicy>,
+    /// How many lines of a response body to print out, the rest is cut off.
+    pub max_body_lines: Option<usize>,
+    /// Send header names in Train-Case, e.g. `content-type` as `Content-Type`.
+    pub canonicalize_headers: bool,
+    /// Where to keep the validators for `@conditional` requests, instead of next to the
+    /// config file.
+    pub conditional_cache_path: Option<std::path::PathBuf>,
+    /// Don't print response bodies, only return them.
+    pub quiet: bool,
+    /// Record responses to, or replay them from, a folder of cassettes.
+    pub cassettes: Option<Cassettes>,
+    /// Print each request, and the status line and headers of its response, as they'd look
+    /// over the wire, with credentials masked.
+    pub trace_requests: bool,
+    /// Where `@capture_env(..)` saves the values it captures.
+    pub env: Option<Environment>,
+    /// Print each request instead of sending it.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RetryPolicy {
+    /// Response status codes that warrant another attempt.
+    pub status_codes: Box<[u16]>,
+    /// How many more times to send a request after the first failed attempt.
+    pub retries: u32,
+    /// How long to wait before each retry.
+    pub backoff: Backoff,
+    /// Only retry GET, PUT, DELETE, HEAD and OPTIONS requests, which can safely be sent more than once.
+    pub idempotent_only: bool,
+}
+
+impl RetryPolicy {
+    fn should_retry(&self, request: &Request, error: &(dyn Error + 'static)) -> bool {
+        if self.idempotent_only
+            && !matches!(
+                request.method,
+                RequestMethod::GET
+                    | RequestMethod::PUT
+                    | RequestMethod::DELETE
+                    | RequestMethod::HEAD
+                    | RequestMethod::OPTIONS
+            )
+        {
+            return false;
+        }
+
+        error
+            .downcast_ref::<ResponseErrorString>()
+            .and_then(|e| e.status)
+            .is_some_and(|status| self.status_codes.contains(&status))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Backoff {
+    /// Retry right away.
+    #[default]
+    None,
+    /// Wait the base duration, then twice that, then three times that, and so on.
+    Linear(Duration),
+    /// Wait the base duration, then twice that, then four times that, and so on.
+    Exponential(Duration),
+}
+
+impl Backoff {
+    /// How long to wait before the nth retry, counting from 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        match self {
+            Backoff::None => Duration::ZERO,
+            Backoff::Linear(base) => base.saturating_mul(retry),
+            Backoff::Exponential(base) => {
+                base.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            }
+        }
     }
 }
 
 use colored::Colorize;
 pub trait RunStrategy {
-    fn run_request(&mut self, request: &Request) -> std::result::Result<String, Box<dyn Error>>;
+    fn run_request(&mut self, request: &Request) -> std::result::Result<Response, Box<dyn Error>>;
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Box<[Header]>,
+    pub body: String,
+    /// Whether the body was already printed out as it was being received.
+    pub streamed: bool,
+    /// The raw body, only kept for requests that download it.
+    pub bytes: Option<Vec<u8>>,
+    /// The file name suggested by the `Content-Disposition` header, if any.
+    pub filename: Option<String>,
 }
 
 struct Runner<'source> {
     program: ir::Program<'source>,
     strategy: Box<dyn RunStrategy>,
+    options: RunOptions,
+    /// Loaded once the first `@conditional` request is run.
+    conditional_cache: Option<ConditionalCache>,
 }
 
 impl<'source> Runner<'source> {
-    pub fn new(program: ir::Program<'source>, strategy: Box<dyn RunStrategy>) -> Self {
-        Self { program, strategy }
+    pub fn new(
+        mut program: ir::Program<'source>,
+        strategy: Box<dyn RunStrategy>,
+        options: RunOptions,
+    ) -> Self {
+        // a stable sort, so that requests of the same order keep to the document order
+        program.items.sort_by(|a, b| a.order.total_cmp(&b.order));
+
+        if options.canonicalize_headers {
+            for item in program.items.iter_mut() {
+                for header in item.request.headers.iter_mut() {
+                    header.canonicalize_name();
+                }
+            }
+        }
+
+        Self {
+            program,
+            strategy,
+            options,
+            conditional_cache: None,
+        }
     }
 
     pub fn run(
@@ -57,66 +279,369 @@ impl<'source> Runner<'source> {
 
         let mut responses = Vec::with_capacity(request_names.map(|names| names.len()).unwrap_or(2));
 
+        let mut current_group = None;
+
         for item in requests {
-            let request_id = request_id::RequestId::from(item);
+            if let Some(group) = item.group.as_deref().filter(|g| current_group != Some(*g)) {
+                if !self.options.quiet {
+                    eprintln!("\n{}", format!("=== {group} ===").bold());
+                }
+                current_group = Some(group);
+            }
+
             let RequestItem {
                 span,
                 request,
                 dbg,
                 log_destination,
+                repeat,
+                capture_env,
+                expected_status,
+                body_assertions,
                 ..
             } = item;
 
-            info!(
-                "sending {} request to {}",
-                request.method.to_string().yellow().bold(),
-                request.url.bold()
-            );
+            let mut durations = vec![];
 
-            if *dbg {
-                eprintln!("{}", &format!("{:#?}", request));
-            }
+            for run in 1..=*repeat {
+                let request_id = request_id::RequestId::from(item);
+
+                info!(
+                    "sending {} request to {}{}",
+                    request.method.to_string().yellow().bold(),
+                    request.url.bold(),
+                    if *repeat > 1 {
+                        format!(" (run {run}/{repeat})")
+                    } else {
+                        String::new()
+                    }
+                );
+
+                if *dbg {
+                    eprintln!("{}", &format!("{:#?}", request));
+                }
+
+                let conditional_request = if request.conditional {
+                    let cache = self.conditional_cache.get_or_insert_with(|| {
+                        ConditionalCache::load(
+                            self.options
+                                .conditional_cache_path
+                                .clone()
+                                .or_else(ConditionalCache::default_path),
+                        )
+                    });
+
+                    let mut request = request.clone();
+                    request.headers = [
+                        request.headers.as_ref(),
+                        &cache.conditional_headers(&request.url),
+                    ]
+                    .concat()
+                    .into();
+                    Some(request)
+                } else {
+                    None
+                };
+
+                let sent_request = conditional_request.as_ref().unwrap_or(request);
+
+                if self.options.trace_requests {
+                    eprintln!("{}", wire::raw_request(sent_request).dimmed());
+                }
+
+                let start = Instant::now();
+
+                let result = run_request_with_retries(
+                    self.strategy.as_mut(),
+                    // the command line takes precedence over the script's `set RETRY`
+                    self.options.retry.as_ref().or(self.program.retry.as_ref()),
+                    sent_request,
+                );
+
+                let elapsed = start.elapsed();
+                durations.push(elapsed);
+
+                if self.options.dry_run {
+                    // there's no response to time, assert on, or save anywhere
+                    match result {
+                        Ok(res) => {
+                            if !self.options.quiet {
+                                println!("{}", res.body.dimmed());
+                            }
+                            responses.push((request_id, RunResponse::Success(res.body)));
+                        }
+                        Err(error) => {
+                            responses.push((request_id, RunResponse::from_error(error.as_ref())))
+                        }
+                    }
+                    continue;
+                }
+
+                if self.options.trace_requests {
+                    if let Some(head) = wire::raw_response_head_of(&result) {
+                        eprintln!("{}", head.dimmed());
+                    }
+                }
+
+                match (self.options.trace_requests, &result) {
+                    // the trace has the status line already
+                    (true, Ok(_)) => info!("  ↳ {}ms", elapsed.as_millis()),
+                    (false, Ok(res)) => {
+                        info!(
+                            "  ↳ {} in {}ms",
+                            res.status.to_string().green(),
+                            elapsed.as_millis()
+                        )
+                    }
+                    (_, Err(_)) => info!("  ↳ failed after {}ms", elapsed.as_millis()),
+                }
 
-            let res = match self.strategy.run_request(request) {
-                Ok(res) => res,
-                Err(error) => {
-                    let err = &error::RunError(error.to_string())
+                if let Some(expected) = *expected_status {
+                    let status = match &result {
+                        Ok(res) => Some(res.status),
+                        Err(error) => error
+                            .downcast_ref::<ResponseErrorString>()
+                            .and_then(|e| e.status),
+                    };
+
+                    if let Some(status) = status.filter(|status| *status != expected) {
+                        let err = &error::RunError(format!(
+                            "expected status {expected} but got {status}"
+                        ))
                         .to_contextual_error(*span, self.program.source);
+                        let err = ColoredMetaError(err);
+                        error!("{err:#}");
+                        responses.push((request_id, RunResponse::Assertion(format!("{err:#}"))));
+                        continue;
+                    }
+                }
+
+                let res = match result {
+                    Ok(res) => res,
+                    Err(error) => match error.downcast_ref::<ResponseErrorString>() {
+                        // an error status is as good as any other, when it's the one expected
+                        Some(ResponseErrorString {
+                            status: Some(status),
+                            body,
+                            ..
+                        }) if *expected_status == Some(*status) => Response {
+                            status: *status,
+                            headers: [].into(),
+                            body: body.clone().unwrap_or_default(),
+                            streamed: false,
+                            bytes: None,
+                            filename: None,
+                        },
+                        _ => {
+                            let err = &error::RunError(error.to_string())
+                                .to_contextual_error(*span, self.program.source);
+                            let err = ColoredMetaError(err);
+                            error!("{err:#}");
+                            responses.push((request_id, RunResponse::from_error(error.as_ref())));
+                            continue;
+                        }
+                    },
+                };
+
+                if let Some(cache) = self
+                    .conditional_cache
+                    .as_mut()
+                    .filter(|_| request.conditional)
+                {
+                    if res.status == 304 {
+                        info!("{}", "not modified since the last response".blue());
+                        responses.push((request_id, RunResponse::Success(res.body)));
+                        continue;
+                    }
+
+                    cache.remember(&request.url, &res.headers);
+
+                    if let Err(error) = cache.save() {
+                        warn!("failed to save the conditional requests cache: {error:#}");
+                    }
+                }
+
+                if let Some(failed) = body_assertions
+                    .iter()
+                    .find(|assertion| !res.body.contains(&assertion.substring))
+                {
+                    let err = &error::RunError(format!(
+                        "expected the response body to contain {:?}",
+                        failed.substring
+                    ))
+                    .to_contextual_error(failed.span, self.program.source);
                     let err = ColoredMetaError(err);
                     error!("{err:#}");
-                    responses.push((request_id, RunResponse::Failure(format!("{err:#}"))));
+                    responses.push((request_id, RunResponse::Assertion(format!("{err:#}"))));
                     continue;
                 }
-            };
 
-            if let Some(log_destination) = log_destination {
-                match log_destination {
-                    LogDestination::File(file_path) => match log(&res, file_path) {
-                        Ok(_) => {
-                            info!("{}", format!("saved response to {:?}", file_path).blue());
+                if let Some(log_destination) = log_destination {
+                    match log_destination {
+                        // the body is printed out below, to one or the other
+                        LogDestination::Std | LogDestination::Stderr => {}
+                        LogDestination::File(file_path) => {
+                            // each run of a repeated request gets a file of its own
+                            let file_path = if *repeat > 1 {
+                                numbered_path(file_path, run)
+                            } else {
+                                file_path.clone()
+                            };
+
+                            match log(&res.body, &file_path) {
+                                Ok(_) => {
+                                    info!(
+                                        "{}",
+                                        format!("saved response to {:?}", file_path).blue()
+                                    );
+                                }
+                                Err(error) => {
+                                    error!(
+                                        "{:#}",
+                                        ColoredMetaError(
+                                            &error::RunError(error.to_string())
+                                                .to_contextual_error(*span, self.program.source)
+                                        )
+                                    )
+                                }
+                            }
                         }
+                    }
+                }
+
+                if let Some(capture) = capture_env {
+                    match capture_into_env(capture, &res.body, self.options.env.as_mut()) {
+                        Ok(saved) => info!("{}", saved.blue()),
                         Err(error) => {
-                            error!(
-                                "{:#}",
-                                ColoredMetaError(
-                                    &error::RunError(error.to_string())
-                                        .to_contextual_error(*span, self.program.source)
-                                )
-                            )
+                            let err = &error::RunError(error)
+                                .to_contextual_error(*span, self.program.source);
+                            let err = ColoredMetaError(err);
+                            error!("{err:#}");
+                            responses.push((request_id, RunResponse::Failure(format!("{err:#}"))));
+                            continue;
                         }
-                    },
+                    }
                 }
-            }
 
-            println!("{res}");
+                if let (Some(target), Some(bytes)) = (&request.download, &res.bytes) {
+                    let result = download_path(target, res.filename.as_deref()).and_then(|path| {
+                        save(bytes, &path).map(|_| path).map_err(|e| e.to_string())
+                    });
+
+                    match result {
+                        Ok(path) => {
+                            info!(
+                                "{}",
+                                format!("saved {} bytes to {:?}", bytes.len(), path).blue()
+                            );
+                            responses.push((request_id, RunResponse::Success(res.body)));
+                        }
+                        Err(error) => {
+                            let err = &error::RunError(error)
+                                .to_contextual_error(*span, self.program.source);
+                            let err = ColoredMetaError(err);
+                            error!("{err:#}");
+                            responses.push((request_id, RunResponse::Failure(format!("{err:#}"))));
+                        }
+                    }
+
+                    continue;
+                }
+
+                if !res.streamed && !self.options.quiet {
+                    let body = match self.options.max_body_lines {
+                        Some(max_lines) => truncate_lines(&res.body, max_lines),
+                        None => res.body.as_str().into(),
+                    };
+
+                    match log_destination {
+                        Some(LogDestination::Stderr) => eprintln!("{body}"),
+                        _ => println!("{body}"),
+                    }
+                }
 
-            responses.push((request_id, RunResponse::Success(res)));
+                responses.push((request_id, RunResponse::Success(res.body)));
+            }
+
+            if *repeat > 1 && !self.options.dry_run {
+                info!("{}", timing_summary(&durations).blue());
+            }
         }
 
         return responses;
     }
 }
 
+pub fn run_request_with_retries(
+    strategy: &mut dyn RunStrategy,
+    retry: Option<&RetryPolicy>,
+    request: &Request,
+) -> std::result::Result<Response, Box<dyn Error>> {
+    let mut attempts = 0;
+
+    loop {
+        let result = strategy.run_request(request);
+
+        let Some(policy) = retry else {
+            return result;
+        };
+
+        match result {
+            Err(error)
+                if attempts < policy.retries && policy.should_retry(request, error.as_ref()) =>
+            {
+                attempts += 1;
+                warn!("{error}");
+                warn!(
+                    "retrying {} request to {} ({}/{})",
+                    request.method, request.url, attempts, policy.retries
+                );
+                std::thread::sleep(policy.backoff.delay(attempts));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Saves the captured value into the environment, returning where it was saved.
+fn capture_into_env(
+    capture: &EnvCapture,
+    body: &str,
+    env: Option<&mut Environment>,
+) -> std::result::Result<String, String> {
+    let EnvCapture { variable, path } = capture;
+
+    let json: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| format!("can't capture {variable} from a response that isn't JSON: {e}"))?;
+
+    let value = match path.select(&json) {
+        Some(value) => Value::from(value.clone()).to_string(),
+        None => {
+            return Err(format!(
+                "nothing at {path} in the response to capture as {variable}"
+            ))
+        }
+    };
+
+    let env = env.ok_or_else(|| format!("there's no environment to save {variable} to"))?;
+
+    env.set_variable(variable.clone(), value)
+        .map_err(|e| format!("{e:#}"))?;
+
+    if env.is_persisted() {
+        Ok(format!(
+            "saved {variable} to the '{}' namespace in {}",
+            env.selected_namespace(),
+            env.env_file_name.display()
+        ))
+    } else {
+        Ok(format!(
+            "set {variable} in the '{}' namespace for this run only, since the environment isn't saved to a file",
+            env.selected_namespace()
+        ))
+    }
+}
+
 mod error {
     use std::error::Error;
 
@@ -139,9 +664,17 @@ mod string_utils {
     use std::{
         fs,
         io::{self, Write},
+        path::{Path, PathBuf},
+        time::Duration,
     };
 
-    pub fn log(content: &str, to_file: &std::path::PathBuf) -> std::io::Result<()> {
+    use crate::interpreter::ir::DownloadTarget;
+
+    pub fn log(content: &str, to_file: &Path) -> std::io::Result<()> {
+        save(content.as_bytes(), to_file)
+    }
+
+    pub fn save(bytes: &[u8], to_file: &Path) -> std::io::Result<()> {
         if let Some(dir_path) = to_file.parent() {
             fs::create_dir_all(dir_path)?
         };
@@ -154,7 +687,242 @@ mod string_utils {
 
         let mut w = io::BufWriter::new(file);
 
-        write!(w, "{content}")
+        w.write_all(bytes)
+    }
+
+    /// Resolves where to save a download. A file name suggested by the server is only ever
+    /// used as a name in the current directory, never as a path elsewhere.
+    pub fn download_path(
+        target: &DownloadTarget,
+        suggested_filename: Option<&str>,
+    ) -> Result<PathBuf, String> {
+        match target {
+            DownloadTarget::File(path) => Ok(path.clone()),
+            DownloadTarget::ContentDisposition => suggested_filename
+                .and_then(|name| Path::new(name).file_name())
+                .map(PathBuf::from)
+                .ok_or_else(|| {
+                    "the response has no Content-Disposition file name to save it as, try giving one, like @download(\"out.bin\")".to_string()
+                }),
+        }
+    }
+
+    /// Numbers the file name, before its extension, like `out.json` as `out.2.json`.
+    pub fn numbered_path(path: &Path, number: usize) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        let file_name = match path.extension() {
+            Some(extension) => format!("{stem}.{number}.{}", extension.to_string_lossy()),
+            None => format!("{stem}.{number}"),
+        };
+
+        path.with_file_name(file_name)
+    }
+
+    pub fn timing_summary(durations: &[Duration]) -> String {
+        let total: Duration = durations.iter().sum();
+        let min = durations.iter().min().copied().unwrap_or_default();
+        let max = durations.iter().max().copied().unwrap_or_default();
+        let avg = total / durations.len().max(1) as u32;
+
+        format!(
+            "sent {} times in {:.2?} (min {:.2?}, avg {:.2?}, max {:.2?})",
+            durations.len(),
+            total,
+            min,
+            avg,
+            max
+        )
+    }
+
+    pub fn truncate_lines(content: &str, max_lines: usize) -> std::borrow::Cow<'_, str> {
+        let mut lines = content.lines();
+        let kept = lines.by_ref().take(max_lines).collect::<Vec<_>>();
+
+        if lines.next().is_none() {
+            return content.into();
+        }
+
+        format!("{}\n... (truncated)", kept.join("\n")).into()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::path::{Path, PathBuf};
+
+        use super::{numbered_path, truncate_lines};
+
+        #[test]
+        fn numbers_file_names_before_their_extension() {
+            assert_eq!(
+                numbered_path(Path::new("output/yams.json"), 2),
+                PathBuf::from("output/yams.2.json")
+            );
+            assert_eq!(numbered_path(Path::new("yams"), 3), PathBuf::from("yams.3"));
+        }
+
+        #[test]
+        fn truncates_only_longer_content() {
+            assert_eq!(truncate_lines("a\nb", 2), "a\nb");
+            assert_eq!(truncate_lines("a\nb\nc", 2), "a\nb\n... (truncated)");
+        }
+    }
+}
+
+/// Requests and responses written out like HTTP/1.1 messages. ureq doesn't give out the bytes
+/// it sends and receives, so these are put together from what it's given, and what it returns.
+mod wire {
+    use std::error::Error;
+
+    use super::{Request, Response, ResponseErrorString};
+    use crate::interpreter::ir::Header;
+
+    const MASK: &str = "********";
+
+    pub fn raw_request(request: &Request) -> String {
+        let (target, host) = match url::Url::parse(&request.url) {
+            Ok(url) => {
+                let mut target = url.path().to_string();
+                if let Some(query) = url.query() {
+                    target.push('?');
+                    target.push_str(query);
+                }
+
+                let host = match url.port() {
+                    Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+                    None => url.host_str().unwrap_or_default().to_string(),
+                };
+
+                (target, host)
+            }
+            Err(_) => (request.url.clone(), String::new()),
+        };
+
+        let mut lines = vec![
+            format!("{} {target} HTTP/1.1", request.method),
+            format!("Host: {host}"),
+        ];
+
+        lines.extend(request.headers.iter().map(header_line));
+
+        if let Some(body) = &request.body {
+            if request.chunked {
+                lines.push("Transfer-Encoding: chunked".to_string());
+            } else {
+                lines.push(format!("Content-Length: {}", body.len()));
+            }
+        }
+
+        lines.push(String::new());
+
+        if let Some(body) = &request.body {
+            lines.push(body.clone());
+        }
+
+        lines.join("\n")
+    }
+
+    pub fn raw_response_head(response: &Response) -> String {
+        let mut lines = vec![status_line(response.status)];
+
+        lines.extend(response.headers.iter().map(header_line));
+
+        lines.join("\n")
+    }
+
+    /// The head of whatever response came back, only its status line for an error status,
+    /// whose headers aren't kept; nothing when no response came at all.
+    pub fn raw_response_head_of(result: &Result<Response, Box<dyn Error>>) -> Option<String> {
+        match result {
+            Ok(response) => Some(raw_response_head(response)),
+            Err(error) => error
+                .downcast_ref::<ResponseErrorString>()
+                .and_then(|e| e.status)
+                .map(status_line),
+        }
+    }
+
+    fn status_line(status: u16) -> String {
+        format!("HTTP/1.1 {status}")
+    }
+
+    fn header_line(header: &Header) -> String {
+        format!("{}: {}", header.name, masked_value(header))
+    }
+
+    /// Credentials are hidden, save for the scheme of `Authorization` headers.
+    fn masked_value(header: &Header) -> String {
+        let name = header.name.to_ascii_lowercase();
+
+        if name == "authorization" || name == "proxy-authorization" {
+            return match header.value.split_once(' ') {
+                Some((scheme, _)) => format!("{scheme} {MASK}"),
+                None => MASK.to_string(),
+            };
+        }
+
+        let sensitive = ["cookie", "token", "secret", "api-key", "apikey", "password"];
+
+        if sensitive.iter().any(|word| name.contains(word)) {
+            return MASK.to_string();
+        }
+
+        header.value.clone()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::error::Error;
+
+        use super::{raw_request, raw_response_head_of};
+        use crate::interpreter::ir::{Header, Request, RequestMethod};
+        use crate::interpreter::ureq_runner::ResponseErrorString;
+
+        #[test]
+        fn writes_out_the_status_line_of_error_responses() {
+            let response = ureq::Response::new(503, "Service Unavailable", "down").unwrap();
+            let error: Box<dyn Error> = Box::new(ResponseErrorString::from(ureq::Error::Status(
+                503, response,
+            )));
+
+            assert_eq!(
+                raw_response_head_of(&Err(error)).as_deref(),
+                Some("HTTP/1.1 503")
+            );
+        }
+
+        #[test]
+        fn writes_requests_out_with_credentials_masked() {
+            let request = Request {
+                method: RequestMethod::POST,
+                url: "http://localhost:8080/todos?done=true".to_string(),
+                headers: [
+                    Header::new("Authorization".into(), "Bearer abc".into()),
+                    Header::new("X-Api-Key".into(), "abc".into()),
+                    Header::new("Accept".into(), "application/json".into()),
+                ]
+                .into(),
+                body: Some("{}".to_string()),
+                max_events: None,
+                timeout: None,
+                download: None,
+                conditional: false,
+                chunked: false,
+                response_format: None,
+            };
+
+            assert_eq!(
+                raw_request(&request),
+                "POST /todos?done=true HTTP/1.1\n\
+                 Host: localhost:8080\n\
+                 Authorization: Bearer ********\n\
+                 X-Api-Key: ********\n\
+                 Accept: application/json\n\
+                 Content-Length: 2\n\
+                 \n\
+                 {}"
+            );
+        }
     }
 }
 
@@ -189,13 +957,9 @@ pub mod request_id {
         type Err = anyhow::Error;
 
         fn from_str(s: &str) -> Result<Self, Self::Err> {
-            let mut split = s.split("::");
-
-            let m = split
-                .next()
-                .context("can't get a prompt entry from an empty string")?;
-            let n = split
-                .next()
+            // names can have '::' in them too, but methods can't
+            let (m, n) = s
+                .split_once("::")
                 .context("failed to get url or name from string")?;
 
             return Ok(RequestId {
@@ -210,4 +974,40 @@ pub mod request_id {
             return format!("{}::{}", self.method, self.url_or_name);
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use std::str::FromStr;
+
+        use super::RequestId;
+
+        #[test]
+        fn names_with_colons_round_trip() {
+            let id = RequestId::from_str("GET::users::by-id").unwrap();
+
+            assert_eq!(id.method, "GET");
+            assert_eq!(id.url_or_name, "users::by-id");
+            assert_eq!(id.as_string(), "GET::users::by-id");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Backoff;
+
+    #[test]
+    fn backoff_delays_grow_per_retry() {
+        let base = Duration::from_millis(100);
+
+        assert_eq!(Backoff::None.delay(3), Duration::ZERO);
+        assert_eq!(Backoff::Linear(base).delay(3), Duration::from_millis(300));
+        assert_eq!(Backoff::Exponential(base).delay(1), base);
+        assert_eq!(
+            Backoff::Exponential(base).delay(3),
+            Duration::from_millis(400)
+        );
+    }
 }
@@ -1,79 +1,405 @@
 use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use super::runner::RunStrategy;
+use tracing::warn;
 
-use super::ir::RequestMethod;
+use super::runner::{Response, RunStrategy};
+
+use super::ir::{RequestMethod, ResponseFormat};
 
 use super::ir::{Header, Request};
 
-pub struct UreqRun;
+#[derive(Default)]
+pub struct UreqRun {
+    /// Sends requests to local servers without verifying their certificates.
+    localhost_agent: Option<ureq::Agent>,
+}
+
+impl UreqRun {
+    /// Skip verifying the certificates of `localhost`, `127.0.0.1` and `::1`, for self-signed
+    /// ones in local development, still verifying those of every other host.
+    pub fn allowing_insecure_localhost() -> Self {
+        let tls_config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(LocalhostCertificateVerification::new()))
+            .with_no_client_auth();
+
+        Self {
+            localhost_agent: Some(
+                ureq::AgentBuilder::new()
+                    .tls_config(Arc::new(tls_config))
+                    .build(),
+            ),
+        }
+    }
+}
 
 impl RunStrategy for UreqRun {
-    fn run_request(&mut self, request: &Request) -> std::result::Result<String, Box<dyn Error>> {
+    fn run_request(&mut self, request: &Request) -> std::result::Result<Response, Box<dyn Error>> {
         let path = &request.url;
 
-        let mut req = match request.method {
-            RequestMethod::GET => ureq::get(path),
-            RequestMethod::POST => ureq::post(path),
-            RequestMethod::PUT => ureq::put(path),
-            RequestMethod::PATCH => ureq::patch(path),
-            RequestMethod::DELETE => ureq::delete(path),
+        let method = match request.method {
+            RequestMethod::GET => "GET",
+            RequestMethod::POST | RequestMethod::GRAPHQL => "POST",
+            RequestMethod::PUT => "PUT",
+            RequestMethod::PATCH => "PATCH",
+            RequestMethod::DELETE => "DELETE",
+            RequestMethod::HEAD => "HEAD",
+            RequestMethod::OPTIONS => "OPTIONS",
+        };
+
+        let mut req = match self.localhost_agent.as_ref().filter(|_| is_localhost(path)) {
+            Some(agent) => agent.request(method, path),
+            None => ureq::request(method, path),
         };
 
         for Header { name, value } in request.headers.iter() {
             req = req.set(name, value);
         }
 
-        let res = if let Some(value) = request.body.clone() {
-            let res = req.send_string(&value).map_err(ResponseErrorString::from)?;
+        if let Some(timeout) = request.timeout {
+            req = req.timeout(timeout);
+        }
 
-            if res.content_type() == "application/json" {
-                let string = &res.into_string()?;
-                prettify_json_string(string)?
+        let into_error = |err: ureq::Error| match request.timeout {
+            Some(timeout) if is_timeout(&err) => ResponseErrorString {
+                message: format!("{path}: timed out after {}ms", timeout.as_millis()),
+                status: None,
+                body: None,
+            },
+            _ => ResponseErrorString::from(err),
+        };
+
+        let res = if let Some(value) = request.body.clone() {
+            if request.chunked {
+                // without a known length, ureq sends the body in chunks
+                req.send(value.as_bytes()).map_err(into_error)?
             } else {
-                res.into_string()?
+                req.send_string(&value).map_err(into_error)?
             }
         } else {
-            req.call()?.into_string()?
+            req.call().map_err(into_error)?
+        };
+
+        let status = res.status();
+        let headers = response_headers(&res);
+
+        // a response to HEAD has headers like the response to GET would, but never a body
+        if request.method == RequestMethod::HEAD {
+            return Ok(Response {
+                status,
+                headers,
+                body: String::new(),
+                streamed: false,
+                bytes: None,
+                filename: None,
+            });
+        }
+
+        if request.download.is_some() {
+            let filename = res
+                .header("content-disposition")
+                .and_then(content_disposition_filename);
+
+            let mut bytes = vec![];
+            res.into_reader().read_to_end(&mut bytes)?;
+
+            return Ok(Response {
+                status,
+                headers,
+                body: String::from_utf8_lossy(&bytes).into_owned(),
+                streamed: false,
+                bytes: Some(bytes),
+                filename,
+            });
+        }
+
+        let body = match (res.content_type(), request.response_format) {
+            ("text/event-stream", _) => {
+                return Ok(Response {
+                    status,
+                    headers,
+                    body: stream_events(res, request.max_events)?,
+                    streamed: true,
+                    bytes: None,
+                    filename: None,
+                })
+            }
+            (_, Some(ResponseFormat::Text)) => res.into_string()?,
+            (_, Some(ResponseFormat::Json)) => {
+                let body = res.into_string()?;
+                prettify_json_string(&body).unwrap_or_else(|e| {
+                    warn!(
+                        "the response from {} isn't JSON after all: {e}",
+                        request.url
+                    );
+                    body
+                })
+            }
+            (content_type, None) if is_json(content_type) => {
+                let body = res.into_string()?;
+                prettify_json_string(&body).unwrap_or(body)
+            }
+            _ => res.into_string()?,
         };
 
-        Ok(res)
+        Ok(Response {
+            status,
+            headers,
+            body,
+            streamed: false,
+            bytes: None,
+            filename: None,
+        })
+    }
+}
+
+/// Whether the mime type is `application/json`, or a flavor of it like `application/problem+json`.
+fn is_json(content_type: &str) -> bool {
+    content_type == "application/json"
+        || content_type.starts_with("application/") && content_type.ends_with("+json")
+}
+
+/// Whether sending the request failed for taking longer than its timeout.
+fn is_timeout(err: &ureq::Error) -> bool {
+    let ureq::Error::Transport(transport) = err else {
+        return false;
+    };
+
+    Error::source(transport)
+        .and_then(|source| source.downcast_ref::<std::io::Error>())
+        .is_some_and(|error| {
+            matches!(
+                error.kind(),
+                std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+            )
+        })
+}
+
+/// Whether the url is to `localhost`, or a loopback address like `127.0.0.1` or `::1`.
+fn is_localhost(url: &str) -> bool {
+    match url::Url::parse(url).as_ref().map(url::Url::host) {
+        Ok(Some(url::Host::Domain(domain))) => domain.eq_ignore_ascii_case("localhost"),
+        Ok(Some(url::Host::Ipv4(ip))) => ip.is_loopback(),
+        Ok(Some(url::Host::Ipv6(ip))) => ip.is_loopback(),
+        _ => false,
     }
 }
 
+/// Accepts any certificate of a local server, but verifies those of every other host like
+/// any other agent would, since a local server may redirect to one.
+struct LocalhostCertificateVerification {
+    webpki: rustls::client::WebPkiVerifier,
+}
+
+impl LocalhostCertificateVerification {
+    fn new() -> Self {
+        // the same roots that ureq trusts for every other request
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+            rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                ta.subject,
+                ta.spki,
+                ta.name_constraints,
+            )
+        }));
+
+        Self {
+            webpki: rustls::client::WebPkiVerifier::new(roots, None),
+        }
+    }
+}
+
+impl rustls::client::ServerCertVerifier for LocalhostCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::Certificate,
+        intermediates: &[rustls::Certificate],
+        server_name: &rustls::ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        if is_loopback_server_name(server_name) {
+            return Ok(rustls::client::ServerCertVerified::assertion());
+        }
+
+        self.webpki.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )
+    }
+}
+
+fn is_loopback_server_name(server_name: &rustls::ServerName) -> bool {
+    match server_name {
+        rustls::ServerName::DnsName(name) => name.as_ref().eq_ignore_ascii_case("localhost"),
+        rustls::ServerName::IpAddress(ip) => ip.is_loopback(),
+        _ => false,
+    }
+}
+
+fn response_headers(res: &ureq::Response) -> Box<[Header]> {
+    res.headers_names()
+        .into_iter()
+        .filter_map(|name| {
+            let value = res.header(&name)?.to_string();
+            Some(Header::new(name, value))
+        })
+        .collect()
+}
+
+/// Reads the `filename` parameter out of a `Content-Disposition` header value, like
+/// `attachment; filename="report.pdf"`.
+fn content_disposition_filename(value: &str) -> Option<String> {
+    value.split(';').find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+
+        if !name.trim().eq_ignore_ascii_case("filename") {
+            return None;
+        }
+
+        let value = value.trim().trim_matches('"');
+
+        (!value.is_empty()).then(|| value.to_string())
+    })
+}
+
+/// Prints out server-sent events line by line as they come in, until the server closes
+/// the connection or `max_events` events have been received.
+fn stream_events(res: ureq::Response, max_events: Option<usize>) -> std::io::Result<String> {
+    let reader = BufReader::new(res.into_reader());
+    let mut stdout = std::io::stdout().lock();
+
+    let mut received = String::new();
+    let mut events = 0;
+    let mut in_event = false;
+
+    for line in reader.lines() {
+        let line = line?;
+
+        writeln!(stdout, "{line}")?;
+        stdout.flush()?;
+
+        // events are separated by blank lines
+        if line.is_empty() {
+            if in_event {
+                events += 1;
+            }
+            in_event = false;
+        } else {
+            in_event = true;
+        }
+
+        received.push_str(&line);
+        received.push('\n');
+
+        if max_events.is_some_and(|max| events >= max) {
+            break;
+        }
+    }
+
+    Ok(received)
+}
+
 pub fn prettify_json_string(string: &str) -> serde_json::Result<String> {
     serde_json::to_string_pretty(&serde_json::from_str::<serde_json::Value>(string)?)
 }
 
 #[derive(Debug)]
-pub struct ResponseErrorString(String);
+pub struct ResponseErrorString {
+    message: String,
+    /// The status code of the response, if the server responded at all.
+    pub status: Option<u16>,
+    /// The body of the response with the error status.
+    pub body: Option<String>,
+}
 
 impl std::error::Error for ResponseErrorString {}
 
 impl std::fmt::Display for ResponseErrorString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        self.message.fmt(f)
     }
 }
 
 impl From<ureq::Error> for ResponseErrorString {
     fn from(err: ureq::Error) -> Self {
-        let value = match err {
+        match err {
             ureq::Error::Status(status, response) => {
-                format!(
-                    "{}: status code {}: {} {:#}",
-                    response.get_url().to_owned(),
-                    status,
-                    response.status_text().to_owned(),
-                    match response.into_string() {
-                        Ok(r) => r,
-                        Err(err) => return ResponseErrorString(err.to_string()),
-                    }
-                )
+                let url = response.get_url().to_owned();
+                let status_text = response.status_text().to_owned();
+                let body = match response.into_string() {
+                    Ok(r) => r,
+                    Err(err) => err.to_string(),
+                };
+
+                ResponseErrorString {
+                    message: format!("{url}: status code {status}: {status_text} {body:#}"),
+                    status: Some(status),
+                    body: Some(body),
+                }
             }
-            ureq::Error::Transport(_) => err.to_string(),
-        };
+            ureq::Error::Transport(_) => ResponseErrorString {
+                message: err.to_string(),
+                status: None,
+                body: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{content_disposition_filename, is_localhost, is_loopback_server_name};
+
+    #[test]
+    fn reads_the_filename_from_content_disposition() {
+        assert_eq!(
+            content_disposition_filename("attachment; filename=\"report.pdf\"").as_deref(),
+            Some("report.pdf")
+        );
+        assert_eq!(
+            content_disposition_filename("attachment;filename=data.bin").as_deref(),
+            Some("data.bin")
+        );
+        assert_eq!(content_disposition_filename("inline"), None);
+        assert_eq!(
+            content_disposition_filename("attachment; filename=\"\""),
+            None
+        );
+    }
+
+    #[test]
+    fn only_loopback_hosts_are_localhost() {
+        assert!(is_localhost("https://localhost:8443/api"));
+        assert!(is_localhost("https://LOCALHOST/api"));
+        assert!(is_localhost("https://127.0.0.1/api"));
+        assert!(is_localhost("https://[::1]:8443/api"));
+
+        assert!(!is_localhost("https://localhost.example.com/api"));
+        assert!(!is_localhost("https://example.com/?next=https://localhost"));
+        assert!(!is_localhost("https://10.0.0.1/api"));
+        assert!(!is_localhost("not a url"));
+    }
+
+    #[test]
+    fn only_certificates_of_loopback_servers_go_unverified() {
+        let name = |name: &str| rustls::ServerName::try_from(name).unwrap();
+
+        assert!(is_loopback_server_name(&name("localhost")));
+        assert!(is_loopback_server_name(&name("127.0.0.1")));
+        assert!(is_loopback_server_name(&name("::1")));
 
-        ResponseErrorString(value)
+        // like the hosts a local server might redirect to
+        assert!(!is_loopback_server_name(&name("example.com")));
+        assert!(!is_loopback_server_name(&name("localhost.example.com")));
+        assert!(!is_loopback_server_name(&name("10.0.0.1")));
     }
 }
@@ -22,3 +22,137 @@ impl From<String> for Value {
         Self::String(value)
     }
 }
+
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => n.as_f64().map(Value::Number).unwrap_or(Value::Null),
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Array(values) => {
+                Value::Array(values.into_iter().map(Value::from).collect())
+            }
+            serde_json::Value::Object(entries) => Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Value {
+    /// Converts to json, where whole numbers are integers, so `1` isn't written as `1.0`, and
+    /// object keys are sorted so that the same object is always written the same way.
+    pub fn to_json_value(&self) -> serde_json::Value {
+        match self {
+            Value::Null => serde_json::Value::Null,
+            Value::String(s) => serde_json::Value::String(s.clone()),
+            Value::Bool(b) => serde_json::Value::Bool(*b),
+            Value::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                serde_json::Value::from(*n as i64)
+            }
+            // NaN and the infinities have no json representation, so they're null
+            Value::Number(n) => serde_json::Number::from_f64(*n)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Value::Array(values) => values.iter().map(Value::to_json_value).collect(),
+            Value::Object(entries) => serde_json::Value::Object(
+                entries
+                    .iter()
+                    .map(|(key, value)| (key.clone(), value.to_json_value()))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// `null`, `false`, `0`, and `""` are falsy, every other value is truthy. So are the strings
+    /// `"false"` and `"0"`, in any case, since that's how environment variables are turned off.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::String(s) => !(s.is_empty() || s == "0" || s.eq_ignore_ascii_case("false")),
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::Array(_) | Value::Object(_) => true,
+        }
+    }
+}
+
+/// Strings as they are, arrays and objects as compact JSON, or pretty-printed with `{:#}`.
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => f.write_str("null"),
+            Value::String(s) => f.write_str(s),
+            Value::Bool(b) => b.fmt(f),
+            Value::Number(n) => n.fmt(f),
+            Value::Array(_) | Value::Object(_) => self.to_json_value().fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Value;
+
+    #[test]
+    fn it_displays_scalars_naturally() {
+        assert_eq!(Value::Null.to_string(), "null");
+        assert_eq!(Value::String("hello".into()).to_string(), "hello");
+        assert_eq!(Value::Bool(true).to_string(), "true");
+        assert_eq!(Value::Number(1337.0).to_string(), "1337");
+        assert_eq!(Value::Number(-0.5).to_string(), "-0.5");
+    }
+
+    #[test]
+    fn it_displays_arrays_as_compact_json() {
+        let value = Value::Array(Box::new([Value::String("a".into()), Value::Null]));
+        assert_eq!(value.to_string(), r#"["a",null]"#);
+    }
+
+    #[test]
+    fn it_displays_objects_as_compact_json() {
+        let value = Value::Object(HashMap::from([("key".to_string(), Value::Bool(false))]));
+        assert_eq!(value.to_string(), r#"{"key":false}"#);
+    }
+
+    #[test]
+    fn it_pretty_prints_arrays_and_objects_with_the_alternate_flag() {
+        let value = Value::Array(Box::new([Value::Number(1.0)]));
+        assert_eq!(format!("{value:#}"), "[\n  1\n]");
+    }
+
+    #[test]
+    fn it_writes_whole_numbers_as_json_integers() {
+        let value = Value::Array(Box::new([Value::Number(1.0), Value::Number(-2.5)]));
+        assert_eq!(value.to_string(), "[1,-2.5]");
+    }
+
+    #[test]
+    fn it_writes_object_keys_in_order() {
+        let value = Value::Object(HashMap::from([
+            ("b".to_string(), Value::Null),
+            ("a".to_string(), Value::Null),
+            ("c".to_string(), Value::Null),
+        ]));
+        assert_eq!(value.to_string(), r#"{"a":null,"b":null,"c":null}"#);
+    }
+
+    #[test]
+    fn it_round_trips_nested_json() {
+        let json = serde_json::json!({
+            "name": "rested",
+            "version": 1,
+            "ratio": 0.5,
+            "tags": ["http", null, true, { "nested": [1, 2, { "deep": "yes" }] }],
+            "empty": {}
+        });
+
+        assert_eq!(Value::from(json.clone()).to_json_value(), json);
+    }
+}
@@ -8,10 +8,14 @@ use tracing::debug;
 use crate::{
     interpreter::environment::Environment,
     language_server::position::ContainsPosition,
-    lexer::{self, locations::GetSpan},
+    lexer::{
+        self,
+        locations::{GetSpan, Span},
+    },
     parser::{
         ast::{
             self, result::ParsedNode, Attribute, ConstantDeclaration, Expression, Item, Statement,
+            TemplateStringPart,
         },
         ast_visit::{self, VisitWith},
         error::ParseError,
@@ -22,6 +26,8 @@ use crate::{
 pub enum SuggestionKind {
     Nothing,
     Identifiers,
+    /// Identifiers, plus the `true`, `false` and `null` literals, where any value will do.
+    Literals,
     SetIdentifiers,
     Functions,
     StatementKeywords,
@@ -61,39 +67,72 @@ impl<'source> Suggestions<'source> {
     fn comps_from_kind(&self, kind: &SuggestionKind) -> Vec<CompletionItem> {
         let mut comps = match kind {
             SuggestionKind::Nothing => vec![],
-            SuggestionKind::Identifiers => builtin_functions_completions(),
+            SuggestionKind::Identifiers | SuggestionKind::Literals => {
+                builtin_functions_completions()
+            }
             SuggestionKind::Functions => builtin_functions_completions(),
-            SuggestionKind::StatementKeywords => header_body_keyword_completions(),
+            SuggestionKind::StatementKeywords => {
+                let mut comps = header_body_keyword_completions();
+                comps.extend(request_block_snippets_completions());
+                comps
+            }
             SuggestionKind::ItemKeywords => item_keywords(),
             SuggestionKind::EnvVars => env_args_completions(&self.env).unwrap_or_default(),
-            SuggestionKind::SetIdentifiers => {
-                vec![CompletionItem {
-                    label: "BASE_URL".to_string(),
-                    kind: Some(CompletionItemKind::CONSTANT),
-                    ..CompletionItem::default()
-                }]
-            }
+            SuggestionKind::SetIdentifiers => [
+                "BASE_URL",
+                "RETRY",
+                "ALLOW_INSECURE_LOCALHOST",
+                "DEFAULT_TIMEOUT",
+                "USER_AGENT",
+            ]
+            .into_iter()
+            .map(|label| CompletionItem {
+                label: label.to_string(),
+                kind: Some(CompletionItemKind::CONSTANT),
+                ..CompletionItem::default()
+            })
+            .collect(),
             SuggestionKind::Attributes => attributes_completions(),
             SuggestionKind::Headers => http_headers_completions(),
         };
 
-        if let SuggestionKind::Identifiers = kind {
+        if let SuggestionKind::Identifiers | SuggestionKind::Literals = kind {
             debug!("adding variables to {:?}", kind);
-            comps.extend(self.variables.iter().map(|var| CompletionItem {
-                label: var.text.to_string(),
-                kind: Some(CompletionItemKind::VARIABLE),
-                insert_text: Some(var.text.to_string()),
-                ..CompletionItem::default()
-            }));
+            comps.extend(self.variables_by_proximity().into_iter().enumerate().map(
+                |(rank, var)| CompletionItem {
+                    label: var.text.to_string(),
+                    kind: Some(CompletionItemKind::VARIABLE),
+                    insert_text: Some(var.text.to_string()),
+                    // keeps the nearest declarations on top, ahead of the builtins
+                    sort_text: Some(format!("0{rank:04}")),
+                    ..CompletionItem::default()
+                },
+            ));
+        }
+
+        if let SuggestionKind::Literals = kind {
+            comps.extend(literals_completions());
         }
         comps
     }
+
+    /// The variables declared before the cursor, nearest first, keeping only the nearest
+    /// declaration of each name.
+    fn variables_by_proximity(&self) -> Vec<&lexer::Token<'source>> {
+        let mut variables: Vec<_> = self.variables.iter().collect();
+        variables.sort_by_key(|var| std::cmp::Reverse(var.start.value));
+
+        let mut seen = HashSet::new();
+        variables.retain(|var| seen.insert(var.text));
+        variables
+    }
 }
 
 #[derive(Debug)]
 pub struct CompletionsCollector<'source> {
     suggestions: Suggestions<'source>,
     position: Position,
+    source: &'source str,
 }
 
 impl<'source> CompletionsCollector<'source> {
@@ -114,7 +153,55 @@ impl<'source> CompletionsCollector<'source> {
                     .collect(),
             },
             position,
+            source: program.source,
+        }
+    }
+
+    /// Whether the cursor is in a `${..}` of the template string with this span, going by its
+    /// text, since an empty one like `${ }` isn't kept as a part.
+    fn is_in_interpolation(&self, span: Span) -> bool {
+        let Some(text) = self.source.get(span.start.value..=span.end.value) else {
+            return false;
+        };
+
+        let (mut line, mut col) = (span.start.line, span.start.col);
+        let cursor = (
+            self.position.line as usize,
+            self.position.character as usize,
+        );
+
+        let mut cursor_offset = None;
+
+        for (i, c) in text.char_indices() {
+            if (line, col) == cursor {
+                cursor_offset = Some(i);
+                break;
+            }
+
+            if c == '\n' {
+                (line, col) = (line + 1, 0);
+            } else {
+                col += c.len_utf8();
+            }
+        }
+
+        let Some(cursor_offset) = cursor_offset else {
+            return false;
+        };
+
+        // counting braces, for objects in the interpolated expression
+        let mut depth = 0;
+        let before = &text[..cursor_offset];
+
+        for (i, c) in before.char_indices() {
+            match c {
+                '{' if before[..i].ends_with('$') || depth > 0 => depth += 1,
+                '}' if depth > 0 => depth -= 1,
+                _ => {}
+            }
         }
+
+        depth > 0
     }
 
     pub fn suggest(&mut self, kind: SuggestionKind) {
@@ -162,7 +249,7 @@ impl<'source> ast_visit::Visitor<'source> for CompletionsCollector<'source> {
 
                 self.visit_expr(value);
 
-                self.suggest(SuggestionKind::Identifiers);
+                self.suggest(SuggestionKind::Literals);
             }
             Item::Request(ast::Request {
                 block: Some(block),
@@ -189,6 +276,17 @@ impl<'source> ast_visit::Visitor<'source> for CompletionsCollector<'source> {
                 self.visit_endpoint(endpoint);
                 self.suggest(SuggestionKind::Identifiers);
             }
+            Item::Headers(ast::HeadersDeclaration { block, .. }) => {
+                if !block.span.contains(&self.position) {
+                    return;
+                }
+
+                for st in block.statements.iter() {
+                    self.visit_statement(st);
+                }
+
+                self.suggest(SuggestionKind::StatementKeywords);
+            }
             Item::Attribute(Attribute {
                 identifier,
                 arguments,
@@ -228,13 +326,16 @@ impl<'source> ast_visit::Visitor<'source> for CompletionsCollector<'source> {
                 }
 
                 if value.span().is_after(&self.position) {
-                    return self.suggest(SuggestionKind::Identifiers);
+                    return self.suggest(SuggestionKind::Literals);
                 }
 
                 self.visit_expr(value)
             }
-            Statement::Body { .. } => {
-                self.suggest(SuggestionKind::Identifiers);
+            Statement::Body { .. }
+            | Statement::FormBody { .. }
+            | Statement::GraphqlQuery { .. }
+            | Statement::GraphqlVariables { .. } => {
+                self.suggest(SuggestionKind::Literals);
             }
             _ => {}
         }
@@ -247,6 +348,11 @@ impl<'source> ast_visit::Visitor<'source> for CompletionsCollector<'source> {
             return;
         }
 
+        // An endpoint can be a variable, but never one of the literals
+        if self.suggestions.list.last() == Some(&SuggestionKind::Literals) {
+            return self.suggest_over_previous(SuggestionKind::Identifiers);
+        }
+
         self.suggest(SuggestionKind::Identifiers)
     }
 
@@ -282,12 +388,11 @@ impl<'source> ast_visit::Visitor<'source> for CompletionsCollector<'source> {
                             Some(Expression::Error(err))
                                 if matches!(
                                     err.inner_error,
-                                    ParseError::ExpectedEitherOfTokens {
+                                    ParseError::UnterminatedString {
                                         found: lexer::Token {
                                             kind: lexer::TokenKind::UnfinishedStringLiteral,
                                             ..
                                         },
-                                        ..
                                     }
                                 ) =>
                             {
@@ -302,35 +407,51 @@ impl<'source> ast_visit::Visitor<'source> for CompletionsCollector<'source> {
                 ParsedNode::Error(_) => self.suggest(SuggestionKind::Functions),
                 _ => {
                     if arguments.span.contains(&self.position) {
-                        self.suggest(SuggestionKind::Identifiers);
+                        self.suggest(SuggestionKind::Literals);
                     }
                 }
             },
             Expression::Array(_) | Expression::EmptyArray(_) => {
-                self.suggest(SuggestionKind::Identifiers);
+                self.suggest(SuggestionKind::Literals);
             }
             Expression::EmptyObject(_) => self.suggest(SuggestionKind::Nothing),
+            Expression::Conditional(_) => expr.visit_children_with(self),
             Expression::Object(entry_list) => {
                 for entry in entry_list.entries() {
                     if let Expression::Error(_) = entry.value {
-                        self.suggest(SuggestionKind::Identifiers)
+                        self.suggest(SuggestionKind::Literals)
                     } else {
                         self.visit_expr(&entry.value)
                     }
                 }
                 self.suggest(SuggestionKind::Nothing)
             }
-            Expression::Identifier(_) => self.suggest(SuggestionKind::Identifiers),
+            Expression::Identifier(_) => self.suggest(SuggestionKind::Literals),
             Expression::String(_) => self.suggest(SuggestionKind::Nothing),
+            Expression::TemplateStringLiteral { span, parts } => {
+                let in_expression_part = parts.iter().any(|part| {
+                    matches!(part, TemplateStringPart::ExpressionPart(e) if e.span().contains(&self.position))
+                });
+
+                // an expression part already suggested for itself
+                if in_expression_part {
+                    return;
+                }
+
+                if self.is_in_interpolation(*span) {
+                    self.suggest(SuggestionKind::Identifiers)
+                } else {
+                    self.suggest(SuggestionKind::Nothing)
+                }
+            }
             Expression::Error(err)
                 if matches!(
                     err.inner_error,
-                    ParseError::ExpectedEitherOfTokens {
+                    ParseError::UnterminatedString {
                         found: lexer::Token {
                             kind: lexer::TokenKind::UnfinishedStringLiteral,
                             ..
                         },
-                        ..
                     }
                 ) =>
             {
@@ -342,19 +463,42 @@ impl<'source> ast_visit::Visitor<'source> for CompletionsCollector<'source> {
 }
 
 fn builtin_functions_completions() -> Vec<CompletionItem> {
-    ["env", "read", "json", "escape_new_lines"]
+    [
+        "env",
+        "read",
+        "read_bytes_base64",
+        "stdin",
+        "json",
+        "base64",
+        "base64_decode",
+        "escape_new_lines",
+        "repeat_string",
+    ]
+    .map(|keyword| CompletionItem {
+        label: format!("{}(..)", keyword),
+        kind: Some(CompletionItemKind::FUNCTION),
+        insert_text: Some(format!("{}(${{1:argument}})", keyword)),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..CompletionItem::default()
+    })
+    .to_vec()
+}
+
+fn literals_completions() -> Vec<CompletionItem> {
+    ["true", "false", "null"]
         .map(|keyword| CompletionItem {
-            label: format!("{}(..)", keyword),
-            kind: Some(CompletionItemKind::FUNCTION),
-            insert_text: Some(format!("{}(${{1:argument}})", keyword)),
-            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            label: keyword.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            insert_text: Some(keyword.to_string()),
             ..CompletionItem::default()
         })
         .to_vec()
 }
 
 pub fn item_keywords() -> Vec<CompletionItem> {
-    let methods = vec!["get", "post", "put", "patch", "delete"];
+    let methods = vec![
+        "get", "post", "put", "patch", "delete", "head", "options", "graphql",
+    ];
 
     [vec!["let", "set"], methods]
         .concat()
@@ -369,7 +513,7 @@ pub fn item_keywords() -> Vec<CompletionItem> {
 }
 
 fn header_body_keyword_completions() -> Vec<CompletionItem> {
-    ["header", "body"]
+    ["header", "body", "query", "variables"]
         .map(|kw| kw.to_string())
         .map(|keyword| CompletionItem {
             label: keyword.clone(),
@@ -380,26 +524,69 @@ fn header_body_keyword_completions() -> Vec<CompletionItem> {
         .to_vec()
 }
 
+fn request_block_snippets_completions() -> Vec<CompletionItem> {
+    [
+        ("json body", "body `${1:{\\}}`"),
+        (
+            "bearer auth",
+            "header \"Authorization\" `Bearer ${1:token}`",
+        ),
+    ]
+    .map(|(label, snippet)| CompletionItem {
+        label: label.to_string(),
+        kind: Some(CompletionItemKind::SNIPPET),
+        insert_text: Some(snippet.to_string()),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..CompletionItem::default()
+    })
+    .to_vec()
+}
+
 fn attributes_completions() -> Vec<CompletionItem> {
-    let mut comp = ["log", "name"]
-        .map(|keyword| CompletionItem {
-            label: format!("{}(..)", keyword),
-            kind: Some(CompletionItemKind::FUNCTION),
-            insert_text: Some(format!("{}(${{1:argument}})", keyword)),
-            insert_text_format: Some(InsertTextFormat::SNIPPET),
-            ..CompletionItem::default()
-        })
-        .to_vec();
+    let mut comp = [
+        "log",
+        "name",
+        "max_events",
+        "repeat",
+        "var",
+        "download",
+        "order",
+        "each",
+        "as",
+        "auth",
+        "capture_env",
+        "group",
+        "timeout",
+        "expect",
+        "assert_contains",
+        "skip_if",
+    ]
+    .map(|keyword| CompletionItem {
+        label: format!("{}(..)", keyword),
+        kind: Some(CompletionItemKind::FUNCTION),
+        insert_text: Some(format!("{}(${{1:argument}})", keyword)),
+        insert_text_format: Some(InsertTextFormat::SNIPPET),
+        ..CompletionItem::default()
+    })
+    .to_vec();
 
     comp.extend_from_slice(
-        &["log", "dbg", "skip"]
-            .map(|kw| kw.to_string())
-            .map(|keyword| CompletionItem {
-                label: keyword.clone(),
-                kind: Some(CompletionItemKind::KEYWORD),
-                insert_text: Some(keyword),
-                ..CompletionItem::default()
-            }),
+        &[
+            "log",
+            "dbg",
+            "skip",
+            "download",
+            "conditional",
+            "chunked",
+            "idempotency_key",
+        ]
+        .map(|kw| kw.to_string())
+        .map(|keyword| CompletionItem {
+            label: keyword.clone(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            insert_text: Some(keyword),
+            ..CompletionItem::default()
+        }),
     );
 
     comp
@@ -464,3 +651,162 @@ fn http_headers_completions() -> Vec<CompletionItem> {
         })
         .to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{CompletionItemKind, Position};
+
+    use super::{CompletionsCollector, SuggestionKind};
+    use crate::{
+        interpreter::environment::Environment,
+        language_server::position::ContainsPosition,
+        lexer::locations::GetSpan,
+        parser::{ast::Program, ast_visit::VisitWith},
+    };
+
+    fn first_suggestion(code: &str, line: u32, character: u32) -> Option<SuggestionKind> {
+        let program = Program::from(code);
+        let position = Position { line, character };
+        let env = Environment::in_memory();
+
+        let mut collector = CompletionsCollector::new(&program, position, env);
+
+        program
+            .items
+            .iter()
+            .find(|i| i.span().contains(&position))?
+            .visit_with(&mut collector);
+
+        collector.suggestions.list.into_iter().next()
+    }
+
+    #[test]
+    fn suggests_attributes_right_after_a_bare_at_sign() {
+        assert_eq!(
+            first_suggestion("get /a\n\n@\n", 2, 1),
+            Some(SuggestionKind::Attributes)
+        );
+
+        assert_eq!(
+            first_suggestion("get /a\n@\nget /b", 1, 1),
+            Some(SuggestionKind::Attributes)
+        );
+
+        assert_eq!(
+            first_suggestion("@\nget /a", 0, 1),
+            Some(SuggestionKind::Attributes)
+        );
+    }
+
+    #[test]
+    fn ranks_variables_nearest_first_without_duplicates() {
+        let code = "let a = 1\nlet b = 2\nlet a = 3\nlet c = 4\n\nget /a {\n  body \n}";
+        let program = Program::from(code);
+        let position = Position {
+            line: 6,
+            character: 7,
+        };
+
+        let collector = CompletionsCollector::new(&program, position, Environment::in_memory());
+
+        let labels: Vec<_> = collector
+            .suggestions
+            .comps_from_kind(&SuggestionKind::Identifiers)
+            .into_iter()
+            .filter(|c| c.kind == Some(CompletionItemKind::VARIABLE))
+            .map(|c| c.label)
+            .collect();
+
+        assert_eq!(labels, ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn suggests_identifiers_and_functions_in_template_interpolations() {
+        assert_eq!(
+            first_suggestion("get `url/${ }`", 0, 11),
+            Some(SuggestionKind::Identifiers)
+        );
+
+        assert_eq!(
+            first_suggestion("let a = `url/${ x }`", 0, 16),
+            Some(SuggestionKind::Literals)
+        );
+
+        assert_eq!(
+            first_suggestion("get /a {\n  header \"x\" `Bearer ${}`\n}", 1, 23),
+            Some(SuggestionKind::Identifiers)
+        );
+
+        assert_eq!(
+            first_suggestion("get /a {\n  body `{ \"a\": ${ x }, ${ } }`\n}", 1, 25),
+            Some(SuggestionKind::Identifiers)
+        );
+    }
+
+    #[test]
+    fn suggests_nothing_in_the_text_of_template_strings() {
+        assert_eq!(
+            first_suggestion("get `url/${ }`", 0, 7),
+            Some(SuggestionKind::Nothing)
+        );
+
+        assert_eq!(
+            first_suggestion("get /a {\n  body `{ \"a\": ${ x } }`\n}", 1, 22),
+            Some(SuggestionKind::Nothing)
+        );
+    }
+
+    #[test]
+    fn suggests_in_headers_declarations_like_in_requests() {
+        assert_eq!(
+            first_suggestion("headers {\n  \n}", 1, 2),
+            Some(SuggestionKind::StatementKeywords)
+        );
+
+        assert_eq!(
+            first_suggestion("headers {\n  header \"\" \"x\"\n}", 1, 10),
+            Some(SuggestionKind::Headers)
+        );
+    }
+
+    #[test]
+    fn suggests_literals_where_any_value_will_do() {
+        assert_eq!(
+            first_suggestion("let a = n", 0, 8),
+            Some(SuggestionKind::Literals)
+        );
+
+        assert_eq!(
+            first_suggestion("get /a {\n  body n\n}", 1, 7),
+            Some(SuggestionKind::Literals)
+        );
+
+        let program = Program::from("let a = n");
+        let collector = CompletionsCollector::new(
+            &program,
+            Position {
+                line: 0,
+                character: 8,
+            },
+            Environment::in_memory(),
+        );
+
+        let keywords: Vec<_> = collector
+            .suggestions
+            .comps_from_kind(&SuggestionKind::Literals)
+            .into_iter()
+            .filter(|c| c.kind == Some(CompletionItemKind::KEYWORD))
+            .map(|c| c.label)
+            .collect();
+
+        assert_eq!(keywords, ["true", "false", "null"]);
+    }
+
+    #[test]
+    fn doesnt_suggest_attributes_in_expressions() {
+        assert_ne!(
+            first_suggestion("get /a {\n  body @\n}", 1, 8),
+            Some(SuggestionKind::Attributes)
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use self_cell::self_cell;
+use tower_lsp::lsp_types::Url;
+
+use crate::parser::{self, ast, ast::Program};
+
+/// Open documents by uri, each parsed once per version.
+///
+/// Hovers, completions, code lenses and document symbols all read the same
+/// [ast::Program], so they reuse the one parsed when the document last changed instead of
+/// re-parsing the whole text on every request. Re-parsing a document of 100 requests, about
+/// 1800 lines, takes around 2.3ms (the `parse ast of 100 requests` bench), on every keystroke's
+/// completions, where reusing it is a map lookup.
+///
+/// Interpreting isn't cached since it depends on the env file, which can change independently
+/// of the document.
+#[derive(Debug)]
+pub struct TextDocuments {
+    inner: Mutex<HashMap<Url, Arc<ParsedDocument>>>,
+}
+
+impl TextDocuments {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, uri: &Url) -> Option<Arc<ParsedDocument>> {
+        match self.inner.lock() {
+            Ok(map) => map.get(uri).cloned(),
+            Err(_) => None,
+        }
+    }
+
+    /// Parses `text` and caches it as the latest version of the document at `url`.
+    pub fn put(&self, url: Url, text: String, version: Option<i32>) -> Arc<ParsedDocument> {
+        let document = Arc::new(ParsedDocument::new(text, version));
+
+        if let Ok(mut map) = self.inner.lock() {
+            map.insert(url, document.clone());
+        }
+
+        document
+    }
+
+    pub fn remove(&self, uri: &Url) {
+        if let Ok(mut map) = self.inner.lock() {
+            map.remove(uri);
+        }
+    }
+}
+
+self_cell!(
+    /// Text, and the [ast::Program] that borrows from it.
+    struct ParsedText {
+        owner: String,
+
+        #[covariant]
+        dependent: Program,
+    }
+);
+
+/// A document's text together with the [ast::Program] parsed from it.
+pub struct ParsedDocument {
+    parsed: ParsedText,
+    pub version: Option<i32>,
+}
+
+impl ParsedDocument {
+    fn new(text: String, version: Option<i32>) -> Self {
+        Self {
+            parsed: ParsedText::new(text, |text| parser::Parser::new(text).parse()),
+            version,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        self.parsed.borrow_owner()
+    }
+
+    pub fn program(&self) -> &ast::Program<'_> {
+        self.parsed.borrow_dependent()
+    }
+}
+
+impl std::fmt::Debug for ParsedDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParsedDocument")
+            .field("version", &self.version)
+            .field("text", &self.text())
+            .finish()
+    }
+}
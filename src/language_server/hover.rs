@@ -18,6 +18,7 @@ pub struct HoverDocsResolver<'source> {
     pub docs: Option<String>,
     is_in_env_call: bool,
     env: Environment,
+    request_docs: Option<String>,
 }
 
 impl<'source> HoverDocsResolver<'source> {
@@ -32,8 +33,15 @@ impl<'source> HoverDocsResolver<'source> {
             docs: None,
             is_in_env_call: false,
             env,
+            request_docs: None,
         }
     }
+
+    /// Documentation (from leading comments) of the request being hovered.
+    pub fn with_request_docs(mut self, docs: Option<String>) -> Self {
+        self.request_docs = docs;
+        self
+    }
 }
 
 impl<'source> ast_visit::Visitor<'source> for HoverDocsResolver<'source> {
@@ -51,15 +59,16 @@ impl<'source> ast_visit::Visitor<'source> for HoverDocsResolver<'source> {
                         "Read env file to grab values.",
                         "Read `.env.rd.json` from the current workspace if there is one,",
                         "otherwise read that in the home directory.",
+                        "Falls back on `fallback`, if given, when the variable isn't defined.",
                         "```typescript",
-                        "(builtin) env(variable: string): string",
+                        "(builtin) env(variable: string, fallback?: any): string",
                         "```",
                     ]
                     .join("\n"),
                     "json" => [
-                        "Convert any value to a json string.",
+                        "Convert any value to a json string, indented when `pretty` is true.",
                         "```typescript",
-                        "(builtin) json(value: any): string",
+                        "(builtin) json(value: any, pretty?: boolean): string",
                         "```",
                     ]
                     .join("\n"),
@@ -70,6 +79,42 @@ impl<'source> ast_visit::Visitor<'source> for HoverDocsResolver<'source> {
                         "```",
                     ]
                     .join("\n"),
+                    "read_bytes_base64" => [
+                        "Read a file's raw bytes, which need not be text, and return them base64 encoded.",
+                        "```typescript",
+                        "(builtin) read_bytes_base64(filename: string): string",
+                        "```",
+                    ]
+                    .join("\n"),
+                    "stdin" => [
+                        "Read all of standard input, like data piped into `rstd run`; every call gets the same string.",
+                        "Evaluates to an empty string wherever nothing is being run, like in the editor.",
+                        "```typescript",
+                        "(builtin) stdin(): string",
+                        "```",
+                    ]
+                    .join("\n"),
+                    "base64" => [
+                        "Encode a string as standard base64, like for a Basic auth header.",
+                        "```typescript",
+                        "(builtin) base64(value: string): string",
+                        "```",
+                    ]
+                    .join("\n"),
+                    "base64_decode" => [
+                        "Decode standard base64 into the string it encodes.",
+                        "```typescript",
+                        "(builtin) base64_decode(value: string): string",
+                        "```",
+                    ]
+                    .join("\n"),
+                    "repeat_string" => [
+                        "Repeat a string some whole number of times, up to 64MiB in total.",
+                        "```typescript",
+                        "(builtin) repeat_string(value: string, count: number): string",
+                        "```",
+                    ]
+                    .join("\n"),
                     "escape_new_lines" => [
                         "Escape the '\\n' characters in a string.",
                         "```typescript",
@@ -142,7 +187,11 @@ impl<'source> ast_visit::Visitor<'source> for HoverDocsResolver<'source> {
 
             match item_at_position {
                 Some(item) => {
-                    self.docs = Some(item.request.url.clone());
+                    let url = &item.request.url;
+                    self.docs = Some(match &self.request_docs {
+                        Some(docs) => format!("{url}\n\n{docs}"),
+                        None => url.clone(),
+                    });
                     return;
                 }
                 None => {
@@ -153,6 +202,16 @@ impl<'source> ast_visit::Visitor<'source> for HoverDocsResolver<'source> {
         endpoint.visit_children_with(self);
     }
 
+    fn visit_request(&mut self, request: &ast::Request<'source>) {
+        request.visit_children_with(self);
+
+        let is_on_request_line = request.span.start.line == self.position.line as usize;
+
+        if self.docs.is_none() && is_on_request_line {
+            self.docs = self.request_docs.clone();
+        }
+    }
+
     fn visit_variable_declaration(&mut self, declaration: &ast::VariableDeclaration<'source>) {
         if declaration.identifier.span().contains(&self.position) {
             if let ParsedNode::Ok(ident) = &declaration.identifier {
@@ -161,17 +220,7 @@ impl<'source> ast_visit::Visitor<'source> for HoverDocsResolver<'source> {
                     .as_ref()
                     .and_then(|program| program.let_bindings.get(ident.text))
                 {
-                    let _type = typeof_value(value);
-
-                    self.docs = Some(
-                        [
-                            "```typescript",
-                            &format!("let {}: {_type}", ident.text),
-                            "```",
-                        ]
-                        .join("\n"),
-                    );
-
+                    self.docs = Some(let_binding_docs(ident.text, value));
                     return;
                 }
             }
@@ -188,21 +237,8 @@ impl<'source> ast_visit::Visitor<'source> for HoverDocsResolver<'source> {
                     .as_ref()
                     .and_then(|program| program.let_bindings.get(ident.text))
                 {
-                    let _type = typeof_value(value);
-                    if let Ok(value) = serde_json::to_string_pretty(value) {
-                        self.docs = Some(
-                            [
-                                "```typescript",
-                                &format!("let {}: {_type}", ident.text),
-                                "```",
-                                "```json",
-                                &value,
-                                "```",
-                            ]
-                            .join("\n"),
-                        );
-                        return;
-                    };
+                    self.docs = Some(let_binding_docs(ident.text, value));
+                    return;
                 }
             }
         }
@@ -211,13 +247,92 @@ impl<'source> ast_visit::Visitor<'source> for HoverDocsResolver<'source> {
     }
 }
 
-fn typeof_value(value: &Value) -> &str {
+/// Values previewed in more lines than this only have their type shown.
+const MAX_PREVIEW_LINES: usize = 20;
+/// Longer strings are cut off in the preview.
+const MAX_PREVIEW_STRING_LEN: usize = 200;
+/// An object's type lists at most this many of its keys.
+const MAX_TYPE_KEYS: usize = 5;
+
+/// The type of a let bound value, followed by a preview of the value unless it's too big.
+fn let_binding_docs(name: &str, value: &Value) -> String {
+    let mut docs = vec![
+        "```typescript".to_string(),
+        format!("let {name}: {}", typeof_value(value)),
+        "```".to_string(),
+    ];
+
+    let (preview, fence) = match value {
+        Value::String(s) if s.chars().count() > MAX_PREVIEW_STRING_LEN => {
+            let truncated: String = s.chars().take(MAX_PREVIEW_STRING_LEN).collect();
+            (format!("{truncated}..."), "```text")
+        }
+        Value::String(s) => (s.clone(), "```text"),
+        value => (format!("{value:#}"), "```json"),
+    };
+
+    if preview.lines().count() <= MAX_PREVIEW_LINES {
+        docs.extend([fence.to_string(), preview, "```".to_string()]);
+    }
+
+    docs.join("\n")
+}
+
+/// Like `array[3]` for arrays, and `object{a, b}` for objects, listing a few of the keys.
+fn typeof_value(value: &Value) -> String {
     match value {
-        Value::Null => "null",
-        Value::String(_) => "string",
-        Value::Bool(_) => "boolean",
-        Value::Number(_) => "number",
-        Value::Array(_) => "any[]",
-        Value::Object(_) => "object",
+        Value::Null => "null".to_string(),
+        Value::String(_) => "string".to_string(),
+        Value::Bool(_) => "boolean".to_string(),
+        Value::Number(_) => "number".to_string(),
+        Value::Array(values) => format!("array[{}]", values.len()),
+        Value::Object(entries) => {
+            let mut keys: Vec<_> = entries.keys().map(String::as_str).collect();
+            keys.sort();
+
+            if keys.len() > MAX_TYPE_KEYS {
+                keys.truncate(MAX_TYPE_KEYS);
+                keys.push("...");
+            }
+
+            format!("object{{{}}}", keys.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{let_binding_docs, typeof_value};
+    use crate::interpreter::value::Value;
+
+    #[test]
+    fn types_describe_the_shape_of_values() {
+        assert_eq!(typeof_value(&Value::Number(1.0)), "number");
+        assert_eq!(
+            typeof_value(&Value::Array([Value::Null, Value::Bool(true)].into())),
+            "array[2]"
+        );
+
+        let object = Value::Object(HashMap::from_iter(
+            ["f", "e", "d", "c", "b", "a"].map(|k| (k.to_string(), Value::Null)),
+        ));
+        assert_eq!(typeof_value(&object), "object{a, b, c, d, e, ...}");
+    }
+
+    #[test]
+    fn big_values_only_show_their_type() {
+        let small = Value::Array([Value::Number(1.0)].into());
+        assert!(let_binding_docs("a", &small).contains("```json"));
+
+        let string = Value::String("hi".into());
+        assert!(let_binding_docs("a", &string).ends_with("```text\nhi\n```"));
+
+        let big = Value::Array(vec![Value::Number(1.0); 50].into());
+        assert_eq!(
+            let_binding_docs("a", &big),
+            "```typescript\nlet a: array[50]\n```"
+        );
     }
 }
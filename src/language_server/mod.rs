@@ -1,7 +1,6 @@
-use std::collections::HashMap;
 use std::str::FromStr;
-use std::sync::Mutex;
 mod completions;
+mod documents;
 mod hover;
 mod position;
 mod warnings;
@@ -11,8 +10,8 @@ use crate::interpreter::environment::Environment;
 use crate::interpreter::{self, runner};
 use crate::lexer;
 use crate::lexer::locations::{GetSpan, Location};
+use crate::parser::ast;
 use crate::parser::ast_visit::VisitWith;
-use crate::parser::{self, ast};
 use anyhow::{anyhow, Context};
 use completions::*;
 use tower_lsp::jsonrpc::Result;
@@ -20,6 +19,7 @@ use tower_lsp::{lsp_types::*, LspService, Server};
 use tower_lsp::{Client, LanguageServer};
 use tracing::{debug, error, info, warn};
 
+use self::documents::TextDocuments;
 use self::position::ContainsPosition;
 
 trait IntoPosition {
@@ -50,32 +50,6 @@ struct Backend {
     pub documents: TextDocuments,
 }
 
-#[derive(Debug)]
-struct TextDocuments {
-    pub inner: Mutex<HashMap<Url, String>>,
-}
-
-impl TextDocuments {
-    fn new() -> Self {
-        Self {
-            inner: Mutex::new(HashMap::new()),
-        }
-    }
-
-    fn get(&self, uri: &Url) -> Option<String> {
-        match self.inner.lock() {
-            Ok(map) => map.get(uri).cloned(),
-            Err(_) => None,
-        }
-    }
-
-    fn put(&self, url: Url, text: String) {
-        if let Ok(mut map) = self.inner.lock() {
-            map.insert(url, text);
-        }
-    }
-}
-
 struct ChangedDocumentItem {
     pub uri: Url,
 
@@ -125,6 +99,10 @@ impl Backend {
     }
 
     async fn on_change(&self, params: ChangedDocumentItem) {
+        let document = self
+            .documents
+            .put(params.uri.clone(), params.text, params.version);
+
         let Ok(env) = self.get_env().await else {
             self.client
                 .log_message(MessageType::ERROR, "failed to initialize the environment")
@@ -138,21 +116,25 @@ impl Backend {
 
         // Handle warnings...
 
-        let program = parser::Parser::new(&params.text).parse();
+        let program = document.program();
 
         let mut w = warnings::EnvVarsNotInAllNamespaces::new(&env);
+        let mut base_url_warnings = warnings::BaseUrlSetAfterRequests::new(params.uri.clone());
+        let mut method_warnings = warnings::InferredRequestMethods::new();
 
         for item in program.items.iter() {
-            item.visit_with(&mut w)
+            item.visit_with(&mut w);
+            item.visit_with(&mut base_url_warnings);
+            item.visit_with(&mut method_warnings);
         }
 
         let mut diagnostics = w.warnings;
+        diagnostics.extend(base_url_warnings.warnings);
+        diagnostics.extend(method_warnings.warnings);
 
         // Done handling warnings
 
         let Err(interp_errors) = program.interpret(&env) else {
-            self.documents.put(params.uri.clone(), params.text);
-
             return self
                 .client
                 .publish_diagnostics(params.uri, diagnostics, params.version)
@@ -162,19 +144,10 @@ impl Backend {
         match interp_errors {
             interpreter::error::InterpreterError::ParseErrors(p) => {
                 for err in p.errors.iter() {
+                    let found = err.inner_error.found();
                     let range = Range {
-                        start: match &err.inner_error {
-                            parser::error::ParseError::ExpectedToken { found, .. }
-                            | parser::error::ParseError::ExpectedEitherOfTokens { found, .. } => {
-                                found.start.into_position()
-                            }
-                        },
-                        end: match &err.inner_error {
-                            parser::error::ParseError::ExpectedToken { found, .. }
-                            | parser::error::ParseError::ExpectedEitherOfTokens { found, .. } => {
-                                found.span().end.into_position()
-                            }
-                        },
+                        start: found.start.into_position(),
+                        end: found.span().end.into_position(),
                     };
 
                     diagnostics.push(Diagnostic::new_simple(range, err.inner_error.to_string()));
@@ -200,8 +173,6 @@ impl Backend {
             }
         }
 
-        self.documents.put(params.uri.clone(), params.text);
-
         diagnostics.reverse();
 
         self.client
@@ -224,6 +195,7 @@ impl LanguageServer for Backend {
                 }),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 document_formatting_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
                 code_lens_provider: Some(CodeLensOptions {
                     resolve_provider: None,
                 }),
@@ -248,7 +220,7 @@ impl LanguageServer for Backend {
 
         debug!("cursor position -> {:?}", current_position);
 
-        let Some(text) = self.documents.get(&uri) else {
+        let Some(document) = self.documents.get(&uri) else {
             error!("failed to get the text by uri: {}", uri);
 
             debug!("{:?}", self.documents);
@@ -256,7 +228,7 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
-        let program = parser::Parser::new(&text).parse();
+        let program = document.program();
 
         let env = match self.get_env().await {
             Ok(env) => env,
@@ -278,6 +250,11 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
+        let request_docs = match current_item {
+            ast::Item::Request(request) => program.request_docs(request),
+            _ => None,
+        };
+
         let program = match program.interpret(&env) {
             Ok(program) => Some(program),
             Err(err) => {
@@ -288,7 +265,8 @@ impl LanguageServer for Backend {
             }
         };
 
-        let mut hover = hover::HoverDocsResolver::new(program, current_position, env);
+        let mut hover = hover::HoverDocsResolver::new(program, current_position, env)
+            .with_request_docs(request_docs);
 
         current_item.visit_with(&mut hover);
 
@@ -306,7 +284,7 @@ impl LanguageServer for Backend {
 
         debug!("cursor position -> {:?}", position);
 
-        let Some(text) = self
+        let Some(document) = self
             .documents
             .get(&params.text_document_position.text_document.uri)
         else {
@@ -320,7 +298,7 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
-        let program = parser::Parser::new(&text).parse();
+        let program = document.program();
 
         let env = match self.get_env().await {
             Ok(env) => env,
@@ -332,7 +310,7 @@ impl LanguageServer for Backend {
             }
         };
 
-        let mut completions_collector = CompletionsCollector::new(&program, position, env);
+        let mut completions_collector = CompletionsCollector::new(program, position, env);
 
         let Some(current_item) = program.items.iter().find(|i| i.span().contains(&position)) else {
             debug!("cursor is apparently not on any items");
@@ -389,16 +367,12 @@ impl LanguageServer for Backend {
     }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        self.documents
-            .inner
-            .lock()
-            .expect("failed to get lock for text documents")
-            .remove(&params.text_document.uri);
+        self.documents.remove(&params.text_document.uri);
     }
 
     async fn formatting(&self, params: DocumentFormattingParams) -> Result<Option<Vec<TextEdit>>> {
         let uri = params.text_document.uri;
-        let Some(text) = self.documents.get(&uri) else {
+        let Some(document) = self.documents.get(&uri) else {
             warn!(
                 "formatting request for an unknown document, by uri: {}",
                 uri
@@ -406,7 +380,7 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
-        let program = ast::Program::from(&text);
+        let program = document.program();
         let formatted_text = match program.to_formatted_string() {
             Ok(formatted_text) => formatted_text,
             Err(err) => {
@@ -434,6 +408,55 @@ impl LanguageServer for Backend {
         }]))
     }
 
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = params.text_document.uri;
+        let Some(document) = self.documents.get(&uri) else {
+            warn!(
+                "documentSymbol request for an unknown document, by uri: {}",
+                uri
+            );
+            return Ok(None);
+        };
+
+        let program = document.program();
+        let text = document.text();
+
+        let symbols = program
+            .requests()
+            .map(|request| {
+                let range = Range {
+                    start: request.span.start.into_position(),
+                    end: request.span.end.into_position(),
+                };
+
+                let endpoint_span = request.endpoint.span();
+                let endpoint = text
+                    .get(endpoint_span.start.value..=endpoint_span.end.value)
+                    .unwrap_or_default();
+
+                #[allow(deprecated)]
+                DocumentSymbol {
+                    name: format!("{} {}", request.method, endpoint),
+                    detail: program.request_docs(request),
+                    kind: SymbolKind::FUNCTION,
+                    tags: None,
+                    deprecated: None,
+                    range,
+                    selection_range: Range {
+                        start: endpoint_span.start.into_position(),
+                        end: endpoint_span.end.into_position(),
+                    },
+                    children: None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
     async fn code_lens(&self, params: CodeLensParams) -> Result<Option<Vec<CodeLens>>> {
         let env = match self.get_env().await {
             Ok(env) => env,
@@ -446,12 +469,12 @@ impl LanguageServer for Backend {
         };
 
         let uri = params.text_document.uri;
-        let Some(text) = self.documents.get(&uri) else {
+        let Some(document) = self.documents.get(&uri) else {
             warn!("codeLens request for an unknown document, by uri: {}", uri);
             return Ok(None);
         };
 
-        let program = parser::Parser::new(&text).parse();
+        let program = document.program();
 
         let program = match program.interpret(&env) {
             Ok(p) => p,
@@ -464,7 +487,14 @@ impl LanguageServer for Backend {
         let codelenses = program
             .items
             .iter()
-            .map(|item| {
+            .enumerate()
+            .map(|(index, item)| {
+                // every iteration of an `@each` request starts at the same place
+                let iteration = program.items[..index]
+                    .iter()
+                    .filter(|other| other.span.start == item.span.start)
+                    .count();
+
                 let range = Range {
                     start: item.span.start.into_position(),
                     end: item.span.end.into_position(),
@@ -479,6 +509,10 @@ impl LanguageServer for Backend {
                         arguments: Some(vec![
                             serde_json::Value::String(uri.to_string()),
                             serde_json::Value::String(arg.as_string()),
+                            // names can be dynamic, e.g. read from the env, and so not the same
+                            // by the time the request is run. Where the request is doesn't change.
+                            serde_json::Value::from(item.span.start.line),
+                            serde_json::Value::from(iteration),
                         ]),
                     }),
                     data: None,
@@ -495,9 +529,22 @@ impl LanguageServer for Backend {
     ) -> Result<Option<serde_json::Value>> {
         match params.command.as_ref() {
             "run" => {
+                let line = params
+                    .arguments
+                    .get(2)
+                    .and_then(|arg| arg.as_u64())
+                    .map(|line| line as usize);
+
+                let iteration = params
+                    .arguments
+                    .get(3)
+                    .and_then(|arg| arg.as_u64())
+                    .unwrap_or_default() as usize;
+
                 let args = params
                     .arguments
                     .into_iter()
+                    .take(2)
                     .map(|arg| {
                         arg.as_str()
                             .expect("we should have passed args from the code_lens method")
@@ -537,15 +584,71 @@ impl LanguageServer for Backend {
                     }
                 };
 
-                let Ok(program) = interpreter::interpret_program(&code, env) else {
+                let interpret = |send_bound_requests, only_send_bound_requests_used_by| {
+                    let options = interpreter::InterpretOptions {
+                        send_bound_requests,
+                        only_send_bound_requests_used_by,
+                        ..Default::default()
+                    };
+
+                    interpreter::interpret_program_with_options(&code, &env, options)
+                };
+
+                let Ok(program) = interpret(None, None) else {
+                    self.log_error(anyhow!("failed to interpret program")).await;
+                    return Ok(None);
+                };
+
+                // a dynamic name may have changed since the code lens was made, and names are
+                // shared by every iteration of an `@each` request
+                let selected = line.and_then(|line| {
+                    program
+                        .items
+                        .iter()
+                        .filter(|item| item.span.start.line == line)
+                        .nth(iteration)
+                });
+
+                let request_id = selected
+                    .map(|item| runner::request_id::RequestId::from(item).url_or_name)
+                    .unwrap_or(request_id);
+
+                let used_by = program
+                    .items
+                    .iter()
+                    .filter(|item| {
+                        runner::request_id::RequestId::from(*item).url_or_name == request_id
+                    })
+                    .map(|item| item.span.start)
+                    .collect();
+
+                let selected_line = selected.map(|item| item.span.start.line);
+
+                // only the bound requests that the request uses are sent
+                let Ok(mut program) = interpret(Some(Default::default()), Some(used_by)) else {
                     self.log_error(anyhow!("failed to interpret program")).await;
                     return Ok(None);
                 };
 
                 info!("running request, id: {}", request_id);
 
+                // just the selected iteration, rather than every request of its name
+                let requests = match selected_line {
+                    Some(line) => {
+                        program.items = std::mem::take(&mut program.items)
+                            .into_vec()
+                            .into_iter()
+                            .filter(|item| item.span.start.line == line)
+                            .nth(iteration)
+                            .into_iter()
+                            .collect();
+                        None
+                    }
+                    None => Some(vec![request_id]),
+                };
+
                 let response = program
-                    .run_ureq(Some(&[request_id]))
+                    .run_ureq(requests.as_deref())
                     .iter()
                     .map(|(id, res)| {
                         let mut text = String::new();
@@ -559,10 +662,30 @@ impl LanguageServer for Backend {
                                 text.push_str("```json\n");
                                 s
                             }
+                            runner::RunResponse::ErrorStatus { status, body } => {
+                                text.push_str(&format!("**{status}** error status\n"));
+                                text.push_str("```json\n");
+                                body
+                            }
+                            runner::RunResponse::Transport(s) => {
+                                text.push_str("**no response**\n");
+                                text.push_str("```sh\n");
+                                s
+                            }
+                            runner::RunResponse::BodyRead(s) => {
+                                text.push_str("**failed to read the response body**\n");
+                                text.push_str("```sh\n");
+                                s
+                            }
                             runner::RunResponse::Failure(s) => {
                                 text.push_str("```sh\n");
                                 s
                             }
+                            runner::RunResponse::Assertion(s) => {
+                                text.push_str("**failed assertion**\n");
+                                text.push_str("```sh\n");
+                                s
+                            }
                         };
 
                         text.push_str(res);
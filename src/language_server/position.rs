@@ -9,10 +9,10 @@ pub trait ContainsPosition {
 
 impl ContainsPosition for Span {
     fn contains(&self, position: &Position) -> bool {
-        if self.start.line == self.end.line && self.start.line == position.line as usize {
-            return (self.start.col..=self.end.col).contains(&(position.character as usize));
-        }
-        (self.start.line..=self.end.line).contains(&(position.line as usize))
+        let position = (position.line as usize, position.character as usize);
+
+        // lines first, then columns, so columns only matter on the first and last lines
+        ((self.start.line, self.start.col)..=(self.end.line, self.end.col)).contains(&position)
     }
 
     fn is_after(&self, position: &Position) -> bool {
@@ -102,6 +102,35 @@ mod tests {
         }))
     }
 
+    const MULTI_LINE_SPAN: Span = Span {
+        start: Position {
+            value: 4,
+            line: 0,
+            col: 4,
+        },
+        end: Position {
+            value: 30,
+            line: 2,
+            col: 1,
+        },
+    };
+
+    #[test]
+    fn test_contains_multi_line() {
+        let contains =
+            |line, character| MULTI_LINE_SPAN.contains(&lsp_types::Position { line, character });
+
+        assert!(!contains(0, 3));
+        assert!(contains(0, 4));
+        assert!(contains(0, 20));
+        assert!(contains(1, 0));
+        assert!(contains(1, 40));
+        assert!(contains(2, 0));
+        assert!(contains(2, 1));
+        assert!(!contains(2, 2));
+        assert!(!contains(3, 0));
+    }
+
     #[test]
     fn test_is_after() {
         assert!(SPAN.is_after(&lsp_types::Position {
@@ -1,6 +1,9 @@
 use crate::{
     interpreter,
-    lexer::Token,
+    lexer::{
+        locations::{GetSpan, Span},
+        Token,
+    },
     parser::{
         ast::{self, result::ParsedNode, Expression},
         ast_visit::{self, VisitWith},
@@ -32,6 +35,11 @@ impl<'env, 'source> ast_visit::Visitor<'source> for EnvVarsNotInAllNamespaces<'e
             identifier: ParsedNode::Ok(Token { text: "env", .. }),
         } = expr
         {
+            if arguments.expressions().nth(1).is_some() {
+                // a default value covers the namespaces missing the variable
+                return;
+            }
+
             if let Some(Expression::String(value)) = &arguments.expressions().nth(0) {
                 let namespaces_from_which_var_is_missing = self
                     .env
@@ -61,3 +69,164 @@ impl<'env, 'source> ast_visit::Visitor<'source> for EnvVarsNotInAllNamespaces<'e
         };
     }
 }
+
+fn range_of(span: Span) -> Range {
+    Range {
+        start: span.start.into_position(),
+        end: span.end.into_position(),
+    }
+}
+
+/// Flags a `set BASE_URL` that comes after requests to pathnames, since those are evaluated
+/// before the base url is set, and fail for it being unset.
+pub struct BaseUrlSetAfterRequests {
+    uri: Url,
+    base_url_is_set: bool,
+    requests_to_pathnames: Vec<Span>,
+    pub warnings: Vec<tower_lsp::lsp_types::Diagnostic>,
+}
+
+impl BaseUrlSetAfterRequests {
+    pub fn new(uri: Url) -> Self {
+        Self {
+            uri,
+            base_url_is_set: false,
+            requests_to_pathnames: vec![],
+            warnings: vec![],
+        }
+    }
+}
+
+impl<'source> ast_visit::Visitor<'source> for BaseUrlSetAfterRequests {
+    fn visit_item(&mut self, item: &ast::Item<'source>) {
+        if self.base_url_is_set {
+            return;
+        }
+
+        match item {
+            ast::Item::Request(ast::Request {
+                endpoint: endpoint @ ast::Endpoint::Pathname(_),
+                span,
+                ..
+            }) => self
+                .requests_to_pathnames
+                .push(span.to_end_of(endpoint.span())),
+            ast::Item::Set(ast::ConstantDeclaration {
+                identifier:
+                    ParsedNode::Ok(Token {
+                        text: "BASE_URL", ..
+                    }),
+                ..
+            }) => {
+                self.base_url_is_set = true;
+
+                if self.requests_to_pathnames.is_empty() {
+                    return;
+                }
+
+                self.warnings.push(Diagnostic {
+                    range: range_of(item.span()),
+                    message: format!(
+                        "BASE_URL is set after {} request(s) to pathnames, which will fail for it being unset; try moving this to the top",
+                        self.requests_to_pathnames.len()
+                    ),
+                    severity: Some(DiagnosticSeverity::WARNING),
+                    related_information: Some(
+                        self.requests_to_pathnames
+                            .iter()
+                            .map(|span| DiagnosticRelatedInformation {
+                                location: Location::new(self.uri.clone(), range_of(*span)),
+                                message: "this request is evaluated before BASE_URL is set"
+                                    .to_string(),
+                            })
+                            .collect(),
+                    ),
+                    ..Default::default()
+                });
+
+                for span in self.requests_to_pathnames.iter() {
+                    self.warnings.push(Diagnostic {
+                        range: range_of(*span),
+                        message: "this request is evaluated before BASE_URL is set, further down"
+                            .to_string(),
+                        severity: Some(DiagnosticSeverity::WARNING),
+                        ..Default::default()
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Points out requests that were written without a method, so they're sent as GETs.
+#[derive(Default)]
+pub struct InferredRequestMethods {
+    pub warnings: Vec<tower_lsp::lsp_types::Diagnostic>,
+}
+
+impl InferredRequestMethods {
+    pub fn new() -> Self {
+        Self { warnings: vec![] }
+    }
+}
+
+impl<'source> ast_visit::Visitor<'source> for InferredRequestMethods {
+    fn visit_item(&mut self, item: &ast::Item<'source>) {
+        let ast::Item::Request(request) = item else {
+            return;
+        };
+
+        if !request.method_is_inferred() {
+            return;
+        }
+
+        let span = request.endpoint.span();
+
+        self.warnings.push(Diagnostic {
+            range: range_of(span),
+            message: "no method given, so this request is sent as a GET".to_string(),
+            severity: Some(DiagnosticSeverity::WARNING),
+            ..Default::default()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Url;
+
+    use super::BaseUrlSetAfterRequests;
+    use crate::parser::{ast::Program, ast_visit::VisitWith};
+
+    fn base_url_warnings(code: &str) -> Vec<String> {
+        let program = Program::from(code);
+        let mut warnings = BaseUrlSetAfterRequests::new(Url::parse("file:///a.rd").unwrap());
+
+        for item in program.items.iter() {
+            item.visit_with(&mut warnings);
+        }
+
+        warnings.warnings.into_iter().map(|w| w.message).collect()
+    }
+
+    #[test]
+    fn warns_of_base_url_set_after_requests_to_pathnames() {
+        let warnings = base_url_warnings("get /a\nset BASE_URL \"http://localhost\"\nget /b");
+
+        assert_eq!(
+            warnings,
+            [
+                "BASE_URL is set after 1 request(s) to pathnames, which will fail for it being unset; try moving this to the top",
+                "this request is evaluated before BASE_URL is set, further down"
+            ]
+        );
+    }
+
+    #[test]
+    fn doesnt_warn_of_base_url_set_before_requests() {
+        let warnings = base_url_warnings("set BASE_URL \"http://localhost\"\nget /a\nget /b");
+
+        assert!(warnings.is_empty());
+    }
+}
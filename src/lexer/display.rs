@@ -9,19 +9,26 @@ impl std::fmt::Display for TokenKind {
             Put => "put",
             Patch => "patch",
             Delete => "delete",
+            Head => "head",
+            Options => "options",
+            Graphql => "graphql",
             Header => "header",
             Body => "body",
+            Query => "query",
+            Variables => "variables",
             Set => "set",
             Let => "let",
             Ident => "identifier",
             Boolean => "boolean",
             Number => "number",
             StringLiteral => "string",
+            RawStringLiteral => "raw string",
             Pathname => "pathname",
             Url => "url",
             Linecomment => "comment",
             Shebang => "#!...",
             Assign => "=",
+            QuestionMark => "?",
             DollarSignLBracket => "${",
             LParen => "(",
             RParen => ")",
@@ -66,19 +66,6 @@ impl Span {
             end: other_span.end,
         }
     }
-
-    pub fn width(&self) -> usize {
-        let left = self.start.col;
-        let right = self.end.col;
-
-        if right > left {
-            return right - left + 1;
-        }
-
-        return left - right + 1;
-        // The + 1's are because the col positions are zero-based, but we need the absolute
-        // length
-    }
 }
 
 pub trait GetSpan {
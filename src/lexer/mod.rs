@@ -15,8 +15,13 @@ pub enum TokenKind {
     Put,
     Patch,
     Delete,
+    Head,
+    Options,
+    Graphql,
     Header,
     Body,
+    Query,
+    Variables,
     Set,
     Let,
     Null,
@@ -27,6 +32,8 @@ pub enum TokenKind {
     Boolean,
     Number,
     StringLiteral,
+    /// A string like r`...`, in which `${` and `\` are just characters.
+    RawStringLiteral,
     Url,
     Pathname,
 
@@ -35,6 +42,7 @@ pub enum TokenKind {
 
     // operators
     Assign,
+    QuestionMark,
 
     // special characters
     DollarSignLBracket,
@@ -57,6 +65,18 @@ pub enum TokenKind {
     IllegalToken,
 }
 
+impl TokenKind {
+    /// Whether this kind marks text that couldn't be lexed into a proper token.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::UnfinishedStringLiteral
+                | TokenKind::UnfinishedMultiLineStringLiteral
+                | TokenKind::IllegalToken
+        )
+    }
+}
+
 #[derive(PartialEq, Clone, serde::Serialize)]
 pub struct Token<'t> {
     pub kind: TokenKind,
@@ -64,6 +84,34 @@ pub struct Token<'t> {
     pub start: Position,
 }
 
+/// A [Token] as it's dumped for tooling that only wants the token stream: with the inclusive
+/// `end` position of its last character, and whether it's an `error` token.
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct TokenRecord<'t> {
+    pub kind: TokenKind,
+    pub text: &'t str,
+    pub start: Position,
+    pub end: Position,
+    pub error: bool,
+}
+
+impl<'t> From<Token<'t>> for TokenRecord<'t> {
+    fn from(token: Token<'t>) -> Self {
+        Self {
+            kind: token.kind,
+            text: token.text,
+            start: token.start,
+            end: token.end_position(),
+            error: token.kind.is_error(),
+        }
+    }
+}
+
+/// Lexes all of `code`, up to but excluding the [TokenKind::End] token.
+pub fn tokenize(code: &str) -> Vec<TokenRecord<'_>> {
+    Lexer::new(code).map(TokenRecord::from).collect()
+}
+
 impl<'source> Token<'source> {
     pub fn end_location(&self) -> Location {
         Location {
@@ -191,12 +239,7 @@ impl<'i> Lexer<'i> {
     }
 
     pub fn next_token(&mut self) -> Token<'i> {
-        if !self.template_str_token_buffer.is_empty() {
-            let t = self
-                .template_str_token_buffer
-                .pop_front()
-                .expect("we checked that isn't empty");
-
+        if let Some(t) = self.template_str_token_buffer.pop_front() {
             return t;
         }
 
@@ -217,7 +260,8 @@ impl<'i> Lexer<'i> {
 
         let t = match ch {
             b'"' if self.peek_char().is(b'"') => self.empty_string_literal(),
-            b'"' => self.string_literal(),
+            b'"' => self.string_literal(b'"'),
+            b'\'' => self.string_literal(b'\''),
             b'`' => self.multiline_string_literal(),
             b'$' if self.peek_char().is(b'{') => {
                 let token = Token {
@@ -274,6 +318,11 @@ impl<'i> Lexer<'i> {
                 text: "=",
                 start: self.position,
             },
+            b'?' => Token {
+                kind: QuestionMark,
+                text: "?",
+                start: self.position,
+            },
             b'@' => Token {
                 kind: AttributePrefix,
                 text: "@",
@@ -282,16 +331,11 @@ impl<'i> Lexer<'i> {
             b'/' if self.peek_char().is(b'/') => self.line_comment(),
             b'/' => self.pathname(),
             b'#' if self.peek_char().is(b'!') => self.shebang(),
+            b'r' if self.peek_char().is(b'`') => self.raw_string_literal(),
             c if c.is_ascii_alphabetic() => self.keyword_or_identifier(),
             c if c.is_ascii_digit() => self.number(),
-            _ => Token {
-                kind: IllegalToken,
-                text: std::str::from_utf8(
-                    &self.input[self.position.value..self.position.value + 1],
-                )
-                .unwrap(),
-                start: self.position,
-            },
+            b'-' if self.peek_char().passes(|c| c.is_ascii_digit()) => self.number(),
+            _ => self.illegal_character(),
         };
 
         self.step();
@@ -299,6 +343,35 @@ impl<'i> Lexer<'i> {
         t
     }
 
+    /// The whole character, which may take up more than a byte.
+    fn illegal_character(&mut self) -> Token<'i> {
+        let start = self.position;
+        let len = self.input()[start.value..]
+            .chars()
+            .next()
+            .map_or(1, char::len_utf8);
+
+        // onto the last byte of the character, the caller steps past it
+        for _ in 1..len {
+            self.step();
+        }
+
+        Token {
+            kind: TokenKind::IllegalToken,
+            text: self.input_slice(start.value..start.value + len),
+            start,
+        }
+    }
+
+    /// The first of the tokens buffered for a template string, which was just pushed, or an
+    /// illegal token if somehow it wasn't.
+    /// Only called right after pushing at least one token of the template string.
+    fn pop_template_str_token(&mut self) -> Token<'i> {
+        self.template_str_token_buffer
+            .pop_front()
+            .expect("a template string token should have been pushed before popping one")
+    }
+
     fn multiline_string_literal(&mut self) -> Token<'i> {
         match self.ch() {
             Some(b'`') if self.peek_char().is(b'`') => return self.empty_string_literal(),
@@ -321,9 +394,7 @@ impl<'i> Lexer<'i> {
 
                 self.step();
 
-                return self.template_str_token_buffer.pop_front().expect(
-                    "there must be a token in the template_str_token_buffer at this point",
-                );
+                return self.pop_template_str_token();
             }
             Some(b'`') => {
                 self.template_str_depth += 1;
@@ -353,9 +424,7 @@ impl<'i> Lexer<'i> {
                     text: "`",
                 });
 
-                return self.template_str_token_buffer.pop_front().expect(
-                    "there must be a token in the template_str_token_buffer at this point",
-                );
+                return self.pop_template_str_token();
             }
             // End of expression part. Here we know that we've tokenized an expression
             // and are proceeding to the rest of the template string
@@ -375,6 +444,20 @@ impl<'i> Lexer<'i> {
 
         let (s, e) = loop {
             match self.ch() {
+                // An escaped backtick, dollar sign or backslash is part of the string, see [unescape_template_string]
+                Some(b'\\')
+                    if self
+                        .peek_char()
+                        .passes(|&c| matches!(c, b'`' | b'$' | b'\\')) =>
+                {
+                    self.step(); // onto the escaped character
+
+                    if self.peek_char().is(b'$') && self.peek_n_char(1).is(b'{') {
+                        break (start_pos, self.position.value + 1);
+                    }
+
+                    self.step();
+                }
                 _ if self.peek_char().is(b'$') && self.peek_n_char(1).is(b'{') => {
                     break (start_pos, self.position.value + 1);
                 }
@@ -396,9 +479,7 @@ impl<'i> Lexer<'i> {
                         start: self.position,
                     });
 
-                    return self.template_str_token_buffer.pop_front().expect(
-                        "there must be a token in the template_str_token_buffer at this point",
-                    );
+                    return self.pop_template_str_token();
                 }
                 None => {
                     self.template_str_token_buffer.push_back(Token {
@@ -407,9 +488,7 @@ impl<'i> Lexer<'i> {
                         text: self.input_slice(start_pos.value..self.position.value),
                     });
 
-                    return self.template_str_token_buffer.pop_front().expect(
-                        "there must be a token in the template_str_token_buffer at this point",
-                    );
+                    return self.pop_template_str_token();
                 }
                 _ => self.step(),
             }
@@ -423,16 +502,25 @@ impl<'i> Lexer<'i> {
             text: string,
         });
 
-        return self
-            .template_str_token_buffer
-            .pop_front()
-            .expect("there must be a token in the template_str_token_buffer at this point");
+        return self.pop_template_str_token();
     }
 
-    fn string_literal(&mut self) -> Token<'i> {
+    fn string_literal(&mut self, quote: u8) -> Token<'i> {
         let start_pos = self.position;
 
-        let (s, e) = self.read_while(|&c| c != b'"' && c != b'\n');
+        let is_unescaped_text = |&c: &u8| c != quote && c != b'\n' && c != b'\\';
+
+        let (s, mut e) = self.read_while(is_unescaped_text);
+
+        while self.peek_char().is(b'\\') {
+            self.step(); // onto the backslash
+
+            if self.peek_char().passes(|&c| c != b'\n') {
+                self.step(); // onto the escaped character, which might be the quote
+            }
+
+            (_, e) = self.read_while(is_unescaped_text);
+        }
 
         match self.peek_char() {
             Some(b'\n') | None => {
@@ -456,6 +544,33 @@ impl<'i> Lexer<'i> {
         }
     }
 
+    fn raw_string_literal(&mut self) -> Token<'i> {
+        let start_pos = self.position;
+
+        self.step(); // onto the opening backtick
+        self.step();
+
+        loop {
+            match self.ch() {
+                Some(b'`') => break,
+                None => {
+                    return Token {
+                        kind: TokenKind::UnfinishedMultiLineStringLiteral,
+                        start: start_pos,
+                        text: self.input_slice(start_pos.value..self.position.value),
+                    }
+                }
+                _ => self.step(),
+            }
+        }
+
+        Token {
+            kind: TokenKind::RawStringLiteral,
+            start: start_pos,
+            text: self.input_slice(start_pos.value..self.position.value + 1),
+        }
+    }
+
     fn empty_string_literal(&mut self) -> Token<'i> {
         let location = self.position;
         self.step();
@@ -468,7 +583,8 @@ impl<'i> Lexer<'i> {
 
     fn keyword_or_identifier(&mut self) -> Token<'i> {
         let location = self.position;
-        let (s, e) = self.read_while(|&c| c.is_ascii_alphabetic() || c == b'_');
+        // the first character is a letter, digits can only come after it
+        let (s, e) = self.read_while(|&c| c.is_ascii_alphanumeric() || c == b'_');
         let string = self.input_slice(s..e);
 
         use TokenKind::*;
@@ -504,11 +620,36 @@ impl<'i> Lexer<'i> {
                 start: location,
                 text: string,
             },
+            "head" => Token {
+                kind: Head,
+                start: location,
+                text: string,
+            },
+            "options" => Token {
+                kind: Options,
+                start: location,
+                text: string,
+            },
+            "graphql" => Token {
+                kind: Graphql,
+                start: location,
+                text: string,
+            },
             "header" => Token {
                 kind: Header,
                 start: location,
                 text: string,
             },
+            "query" => Token {
+                kind: Query,
+                start: location,
+                text: string,
+            },
+            "variables" => Token {
+                kind: Variables,
+                start: location,
+                text: string,
+            },
             "set" => Token {
                 kind: Set,
                 start: location,
@@ -563,30 +704,53 @@ impl<'i> Lexer<'i> {
         }
     }
 
+    /// Like `42`, `-42`, `3.14`, `2e10` or `1.5E-3`.
     fn number(&mut self) -> Token<'i> {
         let location = self.position;
-        let (s, e) = self.read_while(|&c| c.is_ascii_digit());
-        let string = self.input_slice(s..e);
+        let s = location.value;
 
-        if self.peek_char().is(b'.') {
+        // onto the first digit, past a minus sign
+        if self.ch().is(b'-') {
             self.step();
-            if self.peek_char().passes(|c| c.is_ascii_digit()) {
-                self.step();
-                let (.., e) = self.read_while(|&c| c.is_ascii_digit());
-                let string = self.input_slice(s..e);
+        }
 
+        let (.., mut e) = self.read_while(|&c| c.is_ascii_digit());
+
+        if self.peek_char().is(b'.') {
+            self.step();
+            if !self.peek_char().passes(|c| c.is_ascii_digit()) {
                 return Token {
                     kind: TokenKind::Number,
                     start: location,
-                    text: string,
+                    text: self.input_slice(s..e),
                 };
             }
+
+            self.step();
+            (.., e) = self.read_while(|&c| c.is_ascii_digit());
+        }
+
+        let is_sign = |c: &u8| matches!(c, b'+' | b'-');
+
+        let has_exponent = self.peek_char().passes(|c| matches!(c, b'e' | b'E'))
+            && (self.peek_n_char(1).passes(|c| c.is_ascii_digit())
+                || self.peek_n_char(1).passes(is_sign)
+                    && self.peek_n_char(2).passes(|c| c.is_ascii_digit()));
+
+        if has_exponent {
+            self.step();
+            if self.peek_char().passes(is_sign) {
+                self.step();
+            }
+
+            self.step();
+            (.., e) = self.read_while(|&c| c.is_ascii_digit());
         }
 
         Token {
             kind: TokenKind::Number,
             start: location,
-            text: string,
+            text: self.input_slice(s..e),
         }
     }
 
@@ -613,6 +777,74 @@ impl<'i> Lexer<'i> {
     }
 }
 
+/// Resolves the escapes allowed in the string parts of a template string, where a backslash
+/// makes the backtick, dollar sign or backslash after it literal, so that `\${` is just text,
+/// and `\\` is a backslash that escapes nothing. Any other backslash is kept as it is.
+pub fn unescape_template_string(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&escaped @ ('`' | '$' | '\\')) if c == '\\' => {
+                unescaped.push(escaped);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+/// Resolves the escapes allowed in quoted strings, `\n`, `\t`, `\r`, `\\`, `\"`, `\'` and
+/// `\u{..}` with the hex code of a unicode character, or says why an escape is invalid.
+pub fn unescape_string(text: &str) -> Result<String, String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        let c = match chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('u') => {
+                let rest = chars.as_str();
+
+                let Some((hex, rest)) = rest.strip_prefix('{').and_then(|r| r.split_once('}'))
+                else {
+                    return Err(
+                        "expected the hex code of a unicode character, like \\u{1F600}".into(),
+                    );
+                };
+
+                let c = u32::from_str_radix(hex, 16)
+                    .ok()
+                    .filter(|_| (1..=6).contains(&hex.len()))
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("\\u{{{hex}}} is not a valid unicode character"))?;
+
+                chars = rest.chars();
+                c
+            }
+            Some(c) => return Err(format!("\\{c} is not a valid escape")),
+            None => return Err("expected a character to escape after the backslash".into()),
+        };
+
+        unescaped.push(c);
+    }
+
+    Ok(unescaped)
+}
+
 impl<'source> Iterator for Lexer<'source> {
     type Item = Token<'source>;
 
@@ -1,12 +1,15 @@
 mod cli;
 
 use anyhow::Context;
-use clap::{CommandFactory, Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use cli::config::ConfigArgs;
+use cli::curl::CurlArgs;
+use cli::diff::DiffArgs;
 use cli::format::FormatArgs;
-use cli::run::RunArgs;
+use cli::run::{changed_scripts, RunArgs, RunFailure};
 use cli::scratch::ScratchCommandArgs;
 use cli::snapshot::SnapshotArgs;
+use cli::tokens::TokensArgs;
 use rested::config::{
     get_env_from_dir_path, get_env_from_dir_path_or_from_home_dir, get_env_from_home_dir,
 };
@@ -17,6 +20,8 @@ use tracing::{error, info};
 
 use std::collections::HashMap;
 use std::fs;
+use std::io::IsTerminal;
+use std::process::ExitCode;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -28,18 +33,58 @@ struct Cli {
     /// Set log level, one of trace, debug, info, warn, error
     #[arg(short, long, default_value = "info", global = true)]
     level: tracing::Level,
+
+    /// When to color the output, errors and logs included
+    #[arg(long, value_enum, default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    Always,
+    /// Only when printing to a terminal, unless NO_COLOR or CLICOLOR say otherwise
+    Auto,
+    Never,
+}
+
+impl ColorChoice {
+    /// Overrides whether [colored] colors anything, and returns whether logs should be colored.
+    fn apply(self) -> bool {
+        match self {
+            ColorChoice::Always => colored::control::set_override(true),
+            ColorChoice::Never => colored::control::set_override(false),
+            // colored already takes NO_COLOR and CLICOLOR into account, but not whether it's
+            // printing to a terminal
+            ColorChoice::Auto if !std::io::stdout().is_terminal() => {
+                colored::control::set_override(false)
+            }
+            ColorChoice::Auto => {}
+        }
+
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => std::io::stderr().is_terminal(),
+        }
+    }
 }
 
 #[derive(Debug, Subcommand)]
 enum Command {
     /// Run a script written in the language
     Run(RunArgs),
+    /// Run a script against two base urls, and diff the responses
+    Diff(DiffArgs),
     /// Format a script written in the language
     Fmt(FormatArgs),
     /// Open your default editor to start editing a temporary file
     Scratch(ScratchCommandArgs),
     /// Generate a static snapshot of the requests with all dynamic values evaluated.
     Snap(SnapshotArgs),
+    /// Print a curl command for each request in a script, instead of sending them
+    Curl(CurlArgs),
+    /// Print the tokens of a script as json, for tooling like syntax highlighters
+    Tokens(TokensArgs),
     /// Operate on the environment variables available in the runtime.
     /// Looking into the `.env.rd.json` in the current directory, or that in the home directory.
     Env {
@@ -66,8 +111,21 @@ enum Command {
 
 #[derive(Debug, Subcommand)]
 enum EnvCommand {
-    /// View environment variables available in the runtime
-    Show,
+    /// View environment variables available in the runtime, as `key=value` lines grouped by
+    /// namespace
+    Show {
+        /// Only print the variables of this namespace
+        #[arg(short = 'n', long, conflicts_with_all = ["all", "raw"])]
+        namespace: Option<String>,
+
+        /// Print the variables of every namespace, which is also the default
+        #[arg(long, conflicts_with = "raw")]
+        all: bool,
+
+        /// Print the env file as it is
+        #[arg(long)]
+        raw: bool,
+    },
     /// Edit environment variables in your default editor.
     Edit,
     /// Set environment variables available in the runtime
@@ -82,6 +140,8 @@ enum EnvCommand {
         /// Of the environment variable
         value: String,
     },
+    /// Check that the env file is well-formed, reporting every malformed namespace and variable
+    Validate,
     /// Operate on the variables namespaces available in the runtime
     NS {
         #[command(subcommand)]
@@ -103,17 +163,29 @@ enum EnvNamespaceCommand {
     },
 }
 
-fn main() {
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
+    let color_logs = cli.color.apply();
+
     tracing_subscriber::fmt()
         .with_max_level(cli.level)
         .with_writer(std::io::stderr)
+        .with_ansi(color_logs)
         .init();
 
     if let Err(e) = run(cli) {
         error!("{:#}", e);
+
+        let code = e
+            .downcast_ref::<RunFailure>()
+            .map(RunFailure::exit_code)
+            .unwrap_or(1);
+
+        return ExitCode::from(code);
     }
+
+    ExitCode::SUCCESS
 }
 
 fn run(cli: Cli) -> anyhow::Result<()> {
@@ -153,14 +225,90 @@ fn run(cli: Cli) -> anyhow::Result<()> {
                         env.save_to_file()?;
                     }
                 },
-                EnvCommand::Show => println!("{}", fs::read_to_string(env.env_file_name)?),
+                EnvCommand::Show { raw: true, .. } => {
+                    println!("{}", fs::read_to_string(env.env_file_name)?)
+                }
+                EnvCommand::Show {
+                    namespace: Some(namespace),
+                    ..
+                } => {
+                    let variables = env
+                        .namespaced_variables
+                        .get(&namespace)
+                        .ok_or_else(|| anyhow::anyhow!("undefined namespace '{namespace}'"))?;
+
+                    print_variables(variables);
+                }
+                EnvCommand::Show { .. } => {
+                    let mut namespaces: Vec<_> = env.namespaced_variables.iter().collect();
+                    namespaces.sort_by_key(|(name, _)| name.as_str());
+
+                    for (i, (name, variables)) in namespaces.into_iter().enumerate() {
+                        if i > 0 {
+                            println!();
+                        }
+                        println!("[{name}]");
+                        print_variables(variables);
+                    }
+                }
                 EnvCommand::Edit => edit(&env.env_file_name)?,
+                EnvCommand::Validate => {
+                    Environment::validate_file(&env.env_file_name)?;
+                    info!("{} is well-formed", env.env_file_name.display());
+                }
             }
         }
         Command::Completion { shell } => {
             clap_complete::generate(shell, &mut Cli::command(), "rstd", &mut std::io::stdout())
         }
         Command::Lsp => rested::language_server::start(cli.level),
+        Command::Run(run) if run.only_changed => {
+            let scripts = changed_scripts(&std::env::current_dir()?, run.since.as_deref())?;
+
+            if scripts.is_empty() {
+                info!("no changed scripts to run");
+            }
+
+            let mut worst_failure: Option<anyhow::Error> = None;
+
+            for file in scripts {
+                info!("running changed script: {:?}", file);
+
+                let workspace = file.canonicalize().ok();
+                let workspace = workspace.as_ref().and_then(|p| p.parent());
+
+                let env = get_env_from_dir_path_or_from_home_dir(workspace)?;
+
+                let run = RunArgs {
+                    file: Some(file.clone()),
+                    ..run.clone()
+                };
+
+                // keep going, so that every changed script gets checked, exiting with the code
+                // of the worst failure
+                if let Err(e) = run.handle(env) {
+                    let e = e.context(format!("in {file:?}"));
+
+                    match worst_failure.take() {
+                        Some(worst)
+                            if RunFailure::severity_of(&worst) >= RunFailure::severity_of(&e) =>
+                        {
+                            error!("{:#}", e);
+                            worst_failure = Some(worst);
+                        }
+                        Some(worst) => {
+                            error!("{:#}", worst);
+                            worst_failure = Some(e);
+                        }
+                        None => worst_failure = Some(e),
+                    }
+                }
+            }
+
+            if let Some(e) = worst_failure {
+                return Err(e);
+            }
+        }
         Command::Run(run) => {
             let full_path = run.file.as_ref().and_then(|path| path.canonicalize().ok());
             let workspace = full_path.as_ref().and_then(|p| p.parent());
@@ -176,12 +324,20 @@ fn run(cli: Cli) -> anyhow::Result<()> {
             let env = get_env_from_dir_path_or_from_home_dir(workspace)?;
             run.handle(env)?
         }
+        Command::Diff(diff) => {
+            let full_path = diff.file.as_ref().and_then(|path| path.canonicalize().ok());
+            let workspace = full_path.as_ref().and_then(|p| p.parent());
+
+            let env = get_env_from_dir_path_or_from_home_dir(workspace)?;
+            diff.handle(env)?
+        }
         Command::Scratch(scratch) => {
             let env = get_env_from_home_dir()?;
             scratch.handle(env)?
         }
         Command::Config(config) => config.handle()?,
         Command::Fmt(fmt) => fmt.handle()?,
+        Command::Tokens(tokens) => tokens.handle()?,
         Command::Snap(snap) => {
             let full_path = snap.file.as_ref().and_then(|path| path.canonicalize().ok());
             let workspace = full_path.as_ref().and_then(|p| p.parent());
@@ -197,7 +353,24 @@ fn run(cli: Cli) -> anyhow::Result<()> {
             let env = get_env_from_dir_path_or_from_home_dir(workspace)?;
             snap.handle(env)?
         }
+        Command::Curl(curl) => {
+            let full_path = curl.file.as_ref().and_then(|path| path.canonicalize().ok());
+            let workspace = full_path.as_ref().and_then(|p| p.parent());
+
+            let env = get_env_from_dir_path_or_from_home_dir(workspace)?;
+            curl.handle(env)?
+        }
     };
 
     Ok(())
 }
+
+/// Prints the variables as `key=value` lines, sorted by key.
+fn print_variables(variables: &HashMap<String, String>) {
+    let mut variables: Vec<_> = variables.iter().collect();
+    variables.sort();
+
+    for (name, value) in variables {
+        println!("{name}={value}");
+    }
+}
@@ -71,6 +71,13 @@ pub struct ConstantDeclaration<'source> {
     pub value: Expression<'source>,
 }
 
+/// Headers sent with every request after it, like `headers { header "Accept" "*/*" }`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HeadersDeclaration<'source> {
+    pub span: Span,
+    pub block: Block<'source>,
+}
+
 #[derive(Debug, PartialEq, Serialize)]
 pub struct Attribute<'source> {
     pub location: Position,
@@ -86,6 +93,7 @@ pub enum Item<'source> {
     Let(VariableDeclaration<'source>),
     LineComment(Comment<'source>),
     Request(Request<'source>),
+    Headers(HeadersDeclaration<'source>),
     Expr(Expression<'source>),
     Attribute(Attribute<'source>),
     Error(Box<Error<'source>>),
@@ -98,6 +106,10 @@ pub enum RequestMethod {
     DELETE,
     PATCH,
     PUT,
+    HEAD,
+    OPTIONS,
+    /// A POST with a json body made from `query` and `variables` statements
+    GRAPHQL,
 }
 
 impl Display for RequestMethod {
@@ -116,6 +128,24 @@ pub enum Statement<'i> {
         value: Expression<'i>,
         start: Position,
     },
+    /// A body of url-encoded form fields, like `body form { "user": "me" }`.
+    FormBody {
+        value: Expression<'i>,
+        start: Position,
+    },
+    /// A parameter appended to the query string of the url, like `query "sort" "asc"`.
+    Query {
+        name: ParsedNode<'i, StringLiteral<'i>>,
+        value: Expression<'i>,
+    },
+    GraphqlQuery {
+        value: Expression<'i>,
+        start: Position,
+    },
+    GraphqlVariables {
+        value: Expression<'i>,
+        start: Position,
+    },
     LineComment(Comment<'i>),
     Error(Box<Error<'i>>),
 }
@@ -154,9 +184,20 @@ pub enum Expression<'source> {
         span: Span,
         parts: Box<[TemplateStringPart<'source>]>,
     },
+    Conditional(ConditionalExpr<'source>),
+    /// A request whose response is bound with `let`, like `let user = get /user`.
+    Request(Box<Request<'source>>),
     Error(Box<Error<'source>>),
 }
 
+/// `condition ? consequent : alternate`
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ConditionalExpr<'source> {
+    pub condition: Box<Expression<'source>>,
+    pub consequent: Box<Expression<'source>>,
+    pub alternate: Box<Expression<'source>>,
+}
+
 #[derive(Debug, PartialEq, Serialize)]
 pub struct CallExpr<'source> {
     pub identifier: ParsedNode<'source, Token<'source>>,
@@ -244,13 +285,19 @@ mod convert {
     impl<'i> From<&Token<'i>> for StringLiteral<'i> {
         fn from(token: &Token<'i>) -> Self {
             let value = match (token.text.chars().next(), token.text.chars().last()) {
-                (Some('"'), Some('"')) if token.text.len() > 1 => {
+                _ if token.kind == crate::lexer::TokenKind::RawStringLiteral => {
+                    &token.text[2..token.text.len() - 1]
+                }
+                (Some('"'), Some('"')) | (Some('\''), Some('\'')) if token.text.len() > 1 => {
                     &token.text[1..token.text.len() - 1]
                 }
                 (Some('`'), Some('`')) if token.text.len() > 1 => {
                     &token.text[1..token.text.len() - 1]
                 }
-                (_, Some('`')) => &token.text[..token.text.len() - 1],
+                // but not an escaped backtick, which is part of the string
+                (_, Some('`')) if !token.text.ends_with("\\`") => {
+                    &token.text[..token.text.len() - 1]
+                }
                 (Some('`'), _) => &token.text[1..],
                 _ => token.text,
             };
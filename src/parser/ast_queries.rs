@@ -1,18 +1,50 @@
+use std::collections::BTreeSet;
+
 use super::{
-    ast::{self, result::ParsedNode, Program, VariableDeclaration},
-    ast_visit::VisitWith,
+    ast::{self, result::ParsedNode, ConstantDeclaration, Program, VariableDeclaration},
+    ast_visit::{self, VisitWith},
     error::{ErrorsCollector, ParseError},
 };
 use crate::{
     error_meta::ContextualError,
     lexer::{
         self,
-        locations::{GetSpan, Location},
+        locations::{GetSpan, Location, Position},
         Token,
     },
 };
 
+impl<'source> ast::Request<'source> {
+    /// Whether the request was written without a method, and so taken to be a GET.
+    pub fn method_is_inferred(&self) -> bool {
+        self.span.start == self.endpoint.span().start
+    }
+}
+
+/// A `let` or `set` item.
+#[derive(Debug, Clone, Copy)]
+pub enum Declaration<'p, 'source> {
+    Let(&'p VariableDeclaration<'source>),
+    Set(&'p ConstantDeclaration<'source>),
+}
+
 impl<'source> Program<'source> {
+    pub fn requests(&self) -> impl Iterator<Item = &ast::Request<'source>> {
+        self.items.iter().filter_map(|item| match item {
+            ast::Item::Request(request) => Some(request),
+            _ => None,
+        })
+    }
+
+    /// The `let` and `set` items, in the order they're written.
+    pub fn declarations(&self) -> impl Iterator<Item = Declaration<'_, 'source>> {
+        self.items.iter().filter_map(|item| match item {
+            ast::Item::Let(declaration) => Some(Declaration::Let(declaration)),
+            ast::Item::Set(declaration) => Some(Declaration::Set(declaration)),
+            _ => None,
+        })
+    }
+
     pub fn variables(&self) -> impl Iterator<Item = (lexer::locations::Span, &Token<'source>)> {
         self.items.iter().filter_map(|i| match i {
             ast::Item::Let(VariableDeclaration {
@@ -34,6 +66,108 @@ impl<'source> Program<'source> {
             .collect()
     }
 
+    /// Line comments immediately preceding a request (attributes may come in between) are
+    /// taken to be that request's documentation.
+    pub fn request_docs(&self, request: &ast::Request<'source>) -> Option<String> {
+        let position = self.items.iter().position(|item| match item {
+            ast::Item::Request(r) => std::ptr::eq(r, request),
+            _ => false,
+        })?;
+
+        let mut comments = vec![];
+        let mut next_line = request.span.start.line;
+
+        for item in self.items[..position].iter().rev() {
+            match item {
+                ast::Item::Attribute(_) => {}
+                ast::Item::LineComment(comment)
+                    if comment.value.starts_with("//")
+                        && comment.span.end.line + 1 == next_line =>
+                {
+                    comments.push(comment.value.trim_start_matches('/').trim());
+                }
+                _ => break,
+            }
+
+            next_line = item.span().start.line;
+        }
+
+        if comments.is_empty() {
+            return None;
+        }
+
+        comments.reverse();
+
+        Some(comments.join("\n"))
+    }
+
+    /// Names of the variables read with `env(..)` calls, where the name is a plain string.
+    pub fn env_vars(&self) -> BTreeSet<&'source str> {
+        let mut collector = EnvVarsCollector {
+            names: BTreeSet::new(),
+        };
+
+        for item in self.items.iter() {
+            item.visit_with(&mut collector)
+        }
+
+        collector.names
+    }
+
+    /// Names of the variables that the requests starting at these positions read, with their
+    /// attributes, whether directly or through other variables, `set` constants and
+    /// `headers { .. }`.
+    pub fn variables_used_by(&self, requests: &[Position]) -> BTreeSet<&'source str> {
+        let mut collector = IdentifiersCollector {
+            names: BTreeSet::new(),
+        };
+
+        for (position, item) in self.items.iter().enumerate() {
+            match item {
+                ast::Item::Request(request) if requests.contains(&request.span.start) => {
+                    item.visit_with(&mut collector);
+
+                    for item in self.items[..position].iter().rev() {
+                        match item {
+                            ast::Item::Attribute(_) => item.visit_with(&mut collector),
+                            ast::Item::LineComment(_) => {}
+                            _ => break,
+                        }
+                    }
+                }
+                ast::Item::Set(_) | ast::Item::Headers(_) => item.visit_with(&mut collector),
+                _ => {}
+            }
+        }
+
+        let mut unvisited: Vec<_> = collector.names.into_iter().collect();
+        let mut used = BTreeSet::new();
+
+        while let Some(name) = unvisited.pop() {
+            if !used.insert(name) {
+                continue;
+            }
+
+            for item in self.items.iter() {
+                if let ast::Item::Let(VariableDeclaration {
+                    identifier: ParsedNode::Ok(Token { text, .. }),
+                    value,
+                }) = item
+                {
+                    if *text == name {
+                        let mut collector = IdentifiersCollector {
+                            names: BTreeSet::new(),
+                        };
+                        value.visit_with(&mut collector);
+                        unvisited.extend(collector.names);
+                    }
+                }
+            }
+        }
+
+        used
+    }
+
     pub fn errors(&self) -> Vec<ContextualError<ParseError<'source>>> {
         let mut errors = ErrorsCollector { list: vec![] };
         for item in self.items.iter() {
@@ -43,3 +177,37 @@ impl<'source> Program<'source> {
         errors.list
     }
 }
+
+struct IdentifiersCollector<'source> {
+    names: BTreeSet<&'source str>,
+}
+
+impl<'source> ast_visit::Visitor<'source> for IdentifiersCollector<'source> {
+    fn visit_expr(&mut self, expr: &ast::Expression<'source>) {
+        if let ast::Expression::Identifier(ParsedNode::Ok(identifier)) = expr {
+            self.names.insert(identifier.text);
+        }
+
+        expr.visit_children_with(self);
+    }
+}
+
+struct EnvVarsCollector<'source> {
+    names: BTreeSet<&'source str>,
+}
+
+impl<'source> ast_visit::Visitor<'source> for EnvVarsCollector<'source> {
+    fn visit_call_expr(&mut self, expr: &ast::CallExpr<'source>) {
+        expr.visit_children_with(self);
+
+        if let ast::CallExpr {
+            arguments,
+            identifier: ParsedNode::Ok(Token { text: "env", .. }),
+        } = expr
+        {
+            if let Some(ast::Expression::String(name)) = arguments.expressions().next() {
+                self.names.insert(name.value);
+            }
+        }
+    }
+}
@@ -1,15 +1,21 @@
 use crate::lexer::locations::{GetSpan, Span};
 
 use super::ast::{
-    result::ParsedNode, Attribute, CallExpr, ConstantDeclaration, Endpoint, Expression, Item,
-    ObjectEntry, Request, Statement, StringLiteral, VariableDeclaration,
+    result::ParsedNode, Attribute, CallExpr, ConditionalExpr, ConstantDeclaration, Endpoint,
+    Expression, HeadersDeclaration, Item, ObjectEntry, Request, Statement, StringLiteral,
+    VariableDeclaration,
 };
 
 impl<'source> GetSpan for Statement<'source> {
     fn span(&self) -> crate::lexer::locations::Span {
         match self {
-            Statement::Header { name, value } => name.span().to_end_of(value.span()),
-            Statement::Body { value, start } => start.to_end_of(value.span()),
+            Statement::Header { name, value } | Statement::Query { name, value } => {
+                name.span().to_end_of(value.span())
+            }
+            Statement::Body { value, start }
+            | Statement::FormBody { value, start }
+            | Statement::GraphqlQuery { value, start }
+            | Statement::GraphqlVariables { value, start } => start.to_end_of(value.span()),
             Statement::LineComment(literal) => literal.span,
             Statement::Error(e) => e.span,
         }
@@ -26,6 +32,12 @@ impl<'source> GetSpan for CallExpr<'source> {
     }
 }
 
+impl<'source> GetSpan for ConditionalExpr<'source> {
+    fn span(&self) -> Span {
+        self.condition.span().to_end_of(self.alternate.span())
+    }
+}
+
 impl<'source> GetSpan for ObjectEntry<'source> {
     fn span(&self) -> Span {
         self.key.span().to_end_of(self.value.span())
@@ -46,6 +58,8 @@ impl<'source> GetSpan for Expression<'source> {
             Expression::EmptyArray(s) => *s,
             Expression::EmptyObject(s) => *s,
             Expression::Null(s) => *s,
+            Expression::Conditional(expr) => expr.span(),
+            Expression::Request(request) => request.span,
             Expression::Error(e) => e.span,
         }
     }
@@ -60,6 +74,7 @@ impl<'source> GetSpan for Item<'source> {
             Item::Let(decl) => decl.span(),
             Item::LineComment(l) => l.span,
             Item::Request(Request { span, .. }) => *span,
+            Item::Headers(HeadersDeclaration { span, .. }) => *span,
             Item::Attribute(Attribute {
                 location,
                 identifier,
@@ -6,8 +6,8 @@ use crate::{
 use super::{
     ast::{
         result::ParsedNode, Attribute, CallExpr, ConstantDeclaration, Endpoint, Expression,
-        ExpressionList, Item, Literal, ObjectEntry, Program, Request, Statement, StringLiteral,
-        TemplateStringPart, VariableDeclaration,
+        ExpressionList, HeadersDeclaration, Item, Literal, ObjectEntry, Program, Request,
+        Statement, StringLiteral, TemplateStringPart, VariableDeclaration,
     },
     error::ParseError,
 };
@@ -36,6 +36,10 @@ where
         request.visit_children_with(self);
     }
 
+    fn visit_headers_declaration(&mut self, declaration: &HeadersDeclaration<'source>) {
+        declaration.visit_children_with(self);
+    }
+
     fn visit_statement(&mut self, statement: &Statement<'source>) {
         statement.visit_children_with(self);
     }
@@ -129,6 +133,7 @@ impl<'source> VisitWith<'source> for Item<'source> {
             Item::Request(req) => {
                 visitor.visit_request(req);
             }
+            Item::Headers(headers) => visitor.visit_headers_declaration(headers),
             Item::Expr(expr) => visitor.visit_expr(expr),
             Item::Attribute(att) => visitor.visit_attribute(att),
             Item::Error(e) => visitor.visit_error(e),
@@ -185,6 +190,18 @@ impl<'source> VisitWith<'source> for Request<'source> {
     }
 }
 
+impl<'source> VisitWith<'source> for HeadersDeclaration<'source> {
+    fn visit_with<V: Visitor<'source>>(&self, visitor: &mut V) {
+        visitor.visit_headers_declaration(self);
+    }
+
+    fn visit_children_with<V: Visitor<'source>>(&self, visitor: &mut V) {
+        for statement in self.block.statements.iter() {
+            visitor.visit_statement(statement)
+        }
+    }
+}
+
 impl<'source> VisitWith<'source> for Statement<'source> {
     fn visit_with<V: Visitor<'source>>(&self, visitor: &mut V) {
         visitor.visit_statement(self)
@@ -192,11 +209,14 @@ impl<'source> VisitWith<'source> for Statement<'source> {
 
     fn visit_children_with<V: Visitor<'source>>(&self, visitor: &mut V) {
         match self {
-            Statement::Header { name, value } => {
+            Statement::Header { name, value } | Statement::Query { name, value } => {
                 visitor.visit_parsed_node(name);
                 visitor.visit_expr(value);
             }
-            Statement::Body { value, .. } => visitor.visit_expr(value),
+            Statement::Body { value, .. }
+            | Statement::FormBody { value, .. }
+            | Statement::GraphqlQuery { value, .. }
+            | Statement::GraphqlVariables { value, .. } => visitor.visit_expr(value),
             Statement::Error(e) => visitor.visit_error(e),
             Statement::LineComment(_) => {}
         }
@@ -263,6 +283,12 @@ impl<'source> VisitWith<'source> for Expression<'source> {
                     visitor.visit_template_string_part(expr)
                 }
             }
+            Expression::Conditional(expr) => {
+                visitor.visit_expr(&expr.condition);
+                visitor.visit_expr(&expr.consequent);
+                visitor.visit_expr(&expr.alternate);
+            }
+            Expression::Request(request) => visitor.visit_request(request),
             Expression::Error(e) => visitor.visit_error(e),
             Expression::Identifier(ident) => visitor.visit_parsed_node(ident),
             Expression::String(s) => visitor.visit_string(s),
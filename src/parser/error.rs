@@ -28,6 +28,22 @@ pub enum ParseError<'source> {
         found: lexer::Token<'source>,
         expected: Box<[TokenKind]>,
     },
+    /// A character that can't start any token, like a stray `%`.
+    IllegalCharacter { found: lexer::Token<'source> },
+    /// A string literal without its closing quote, or backtick.
+    UnterminatedString { found: lexer::Token<'source> },
+}
+
+impl<'source> ParseError<'source> {
+    /// The token at which parsing failed.
+    pub fn found(&self) -> &lexer::Token<'source> {
+        match self {
+            ParseError::ExpectedToken { found, .. }
+            | ParseError::ExpectedEitherOfTokens { found, .. }
+            | ParseError::IllegalCharacter { found }
+            | ParseError::UnterminatedString { found } => found,
+        }
+    }
 }
 
 impl<'source> std::error::Error for ParseError<'source> {}
@@ -50,6 +66,15 @@ impl<'source> std::fmt::Display for ParseError<'source> {
                     .join(",");
                 format!("expected either one of {} but got {}", expected, found)
             }
+            ParseError::IllegalCharacter { found } => {
+                format!("illegal character {:?}", found.text)
+            }
+            ParseError::UnterminatedString { found } => match found.kind {
+                TokenKind::UnfinishedMultiLineStringLiteral => {
+                    "unterminated string, missing the closing '`'".to_string()
+                }
+                _ => "unterminated string, missing the closing '\"'".to_string(),
+            },
         };
 
         f.write_str(&formatted_error)
@@ -143,6 +168,10 @@ impl<'i> Expectations<'i> {
         token: &Token<'i>,
         expected: TokenKind,
     ) -> ContextualError<ParseError<'i>> {
+        if let Some(error) = self.lexing_error(token) {
+            return error;
+        }
+
         ContextualError::new(
             ParseError::ExpectedToken {
                 found: token.clone(),
@@ -158,6 +187,10 @@ impl<'i> Expectations<'i> {
         token: &Token<'i>,
         expected_kinds: &[TokenKind],
     ) -> ContextualError<ParseError<'i>> {
+        if let Some(error) = self.lexing_error(token) {
+            return error;
+        }
+
         let mut expected_dedpuded: Vec<TokenKind> = vec![];
 
         for kind in expected_kinds {
@@ -175,6 +208,24 @@ impl<'i> Expectations<'i> {
             self.source_code,
         )
     }
+
+    /// Whatever was expected, a token the lexer couldn't make sense of is the real problem, so
+    /// it's reported as such, pointing at just that token.
+    fn lexing_error(&self, token: &Token<'i>) -> Option<ContextualError<ParseError<'i>>> {
+        let error = match token.kind {
+            TokenKind::IllegalToken => ParseError::IllegalCharacter {
+                found: token.clone(),
+            },
+            TokenKind::UnfinishedStringLiteral | TokenKind::UnfinishedMultiLineStringLiteral => {
+                ParseError::UnterminatedString {
+                    found: token.clone(),
+                }
+            }
+            _ => return None,
+        };
+
+        Some(ContextualError::new(error, token.span(), self.source_code))
+    }
 }
 
 pub struct ErrorsCollector<'source> {
@@ -246,6 +297,11 @@ mod tests {
         assert_ast!(r#" let o = ["joe" "world"] "#);
     }
 
+    #[test]
+    fn expecting_statements_in_blocks() {
+        assert_ast!(r#"get /hello { form { a: 1 } }"#);
+    }
+
     #[test]
     fn expecting_partial_block_with_error() {
         assert_ast!(r#"get /hello { header "test" "value" header }"#);
@@ -309,6 +365,18 @@ let b = {
         );
     }
 
+    #[test]
+    fn illegal_characters_are_reported_as_such() {
+        assert_ast!(r#"let a = %"#);
+        assert_ast!(r#"get /a { header "a" & }"#);
+    }
+
+    #[test]
+    fn unterminated_strings_are_reported_as_such() {
+        assert_ast!(r#"let a = "hello"#);
+        assert_ast!(r#"let a = `hello"#);
+    }
+
     #[test]
     fn expected_comma_before_more_parameters() {
         assert_ast!(r#"env("base" "url")"#);
@@ -1,5 +1,5 @@
 pub mod ast;
-mod ast_queries;
+pub mod ast_queries;
 mod ast_span;
 pub mod ast_visit;
 pub mod error;
@@ -96,7 +96,17 @@ impl<'source> Parser<'source> {
         loop {
             let is_top_level_token_ahead = matches!(
                 self.peek_token().kind,
-                Get | Post | Put | Patch | Delete | Set | AttributePrefix | Let | End
+                Get | Post
+                    | Put
+                    | Patch
+                    | Delete
+                    | Head
+                    | Options
+                    | Graphql
+                    | Set
+                    | AttributePrefix
+                    | Let
+                    | End
             );
 
             if is_top_level_token_ahead {
@@ -107,6 +117,34 @@ impl<'source> Parser<'source> {
         }
     }
 
+    /// The current token, read as an identifier if it's `head`, `options`, `query` or
+    /// `variables`. They're only keywords at the start of a request or statement, so they can
+    /// still name variables.
+    fn curr_token_as_identifier(&self) -> Token<'source> {
+        let token = self.curr_token().clone();
+
+        match token.kind {
+            Head | Options | Query | Variables => Token {
+                kind: Ident,
+                ..token
+            },
+            _ => token,
+        }
+    }
+
+    /// Whether the next token could be the url of a request, for telling `let x = head /api`
+    /// apart from `let x = head`.
+    fn peek_token_is_endpoint(&mut self) -> bool {
+        self.peek_token().is_one_of(&[
+            Url,
+            Pathname,
+            StringLiteral,
+            RawStringLiteral,
+            OpeningBackTick,
+            Ident,
+        ])
+    }
+
     fn span_from(&self, start: Position) -> Span {
         start.to_end_of(self.curr_token().span())
     }
@@ -119,21 +157,47 @@ impl<'source> Parser<'source> {
         self.next_token();
 
         while self.curr_token().kind != End {
-            let result: std::result::Result<ast::Item<'_>, _> = match self.curr_token().kind {
+            let kind = self.curr_token().kind;
+
+            let result: std::result::Result<ast::Item<'_>, _> = match kind {
                 Get => self.parse_request(RequestMethod::GET),
                 Post => self.parse_request(RequestMethod::POST),
                 Put => self.parse_request(RequestMethod::PUT),
                 Patch => self.parse_request(RequestMethod::PATCH),
                 Delete => self.parse_request(RequestMethod::DELETE),
+                Head => self.parse_request(RequestMethod::HEAD),
+                Options => self.parse_request(RequestMethod::OPTIONS),
+                Graphql => self.parse_request(RequestMethod::GRAPHQL),
+                Url | Pathname => self.parse_implicit_get_request(),
                 Linecomment | Shebang => Ok(Item::LineComment(self.curr_token().into())),
                 Set => self.parse_set_statement(),
                 AttributePrefix => {
                     let e = Expectations::new(self);
                     let item = self.parse_attribute();
 
-                    if item.is_ok() {
-                        let valid_after_attribute =
-                            [Get, Post, Put, Patch, Delete, AttributePrefix, Linecomment];
+                    let is_named_attribute = matches!(
+                        &item,
+                        Ok(Item::Attribute(Attribute {
+                            identifier: ParsedNode::Ok(_),
+                            ..
+                        }))
+                    );
+
+                    if is_named_attribute {
+                        let valid_after_attribute = [
+                            Get,
+                            Post,
+                            Put,
+                            Patch,
+                            Delete,
+                            Head,
+                            Options,
+                            Graphql,
+                            Url,
+                            Pathname,
+                            AttributePrefix,
+                            Linecomment,
+                        ];
 
                         if let Err(err) = e.expect_peek_one_of(self, &valid_after_attribute) {
                             items.push(
@@ -149,6 +213,10 @@ impl<'source> Parser<'source> {
                     item
                 }
                 Let => self.parse_let_statement(),
+                // `headers` is only special before a block, so it can still name a variable
+                Ident if self.curr_token().text == "headers" && self.peek_token().is(LBracket) => {
+                    self.parse_headers_declaration()
+                }
                 _ => match self.parse_expression() {
                     Ok(exp) => Ok(Item::Expr(exp)),
                     Err(err) => Err(err),
@@ -170,6 +238,10 @@ impl<'source> Parser<'source> {
     }
 
     fn parse_request(&mut self, method: RequestMethod) -> Result<'source, Item<'source>> {
+        Ok(Item::Request(self.parse_request_node(method)))
+    }
+
+    fn parse_request_node(&mut self, method: RequestMethod) -> ast::Request<'source> {
         let e = Expectations::new(self);
 
         let endpoint = self.parse_endpoint();
@@ -182,11 +254,49 @@ impl<'source> Parser<'source> {
             endpoint.span()
         };
 
-        Ok(Item::Request(ast::Request {
+        ast::Request {
             span: e.start.to_end_of(span_next),
             method,
             endpoint,
             block,
+        }
+    }
+
+    /// Parses a request without a method, like `http://localhost/health`, as a GET.
+    fn parse_implicit_get_request(&mut self) -> Result<'source, Item<'source>> {
+        let e = Expectations::new(self);
+
+        let endpoint = match self.curr_token().kind {
+            Url => Endpoint::Url(self.curr_token().into()),
+            _ => Endpoint::Pathname(self.curr_token().into()),
+        };
+
+        let block = self.parse_block();
+
+        let span_next = if let Some(b) = block.as_ref() {
+            b.span
+        } else {
+            endpoint.span()
+        };
+
+        Ok(Item::Request(ast::Request {
+            span: e.start.to_end_of(span_next),
+            method: RequestMethod::GET,
+            endpoint,
+            block,
+        }))
+    }
+
+    fn parse_headers_declaration(&mut self) -> Result<'source, Item<'source>> {
+        let e = Expectations::new(self);
+
+        let block = self
+            .parse_block()
+            .expect("a headers declaration is only parsed before a block");
+
+        Ok(Item::Headers(ast::HeadersDeclaration {
+            span: e.start.to_end_of(block.span),
+            block,
         }))
     }
 
@@ -202,7 +312,10 @@ impl<'source> Parser<'source> {
             Pathname => return Endpoint::Pathname(self.curr_token().into()),
             Ident if peek_kind == LParen => self.parse_call_expression().into(),
             Ident => Expression::Identifier(self.curr_token().into()),
-            StringLiteral => Expression::String(self.curr_token().into()),
+            Head | Options | Query | Variables => {
+                Expression::Identifier((&self.curr_token_as_identifier()).into())
+            }
+            StringLiteral | RawStringLiteral => Expression::String(self.curr_token().into()),
             OpeningBackTick => self.parse_multiline_string_literal(),
             _ => Expression::Error(
                 e.expected_one_of_tokens(self.curr_token(), &[Url, Pathname, StringLiteral, Ident])
@@ -271,8 +384,10 @@ impl<'source> Parser<'source> {
         let statement = match_or_throw! { self.curr_token().kind; e; self;
             Header => self.parse_header()?,
             Body => self.parse_body()?,
+            Query => self.parse_query()?,
+            Variables => self.parse_graphql_variables()?,
             Linecomment | Shebang => Statement::LineComment(self.curr_token().into()),
-            "may only declare headers or a body statement here"
+            "may only declare header, body (or body form), query or variables statements here"
         };
 
         Ok(statement)
@@ -308,6 +423,18 @@ impl<'source> Parser<'source> {
 
         self.next_token();
 
+        // `form` is only special before an object, so it can still name a variable
+        if self.curr_token().is(Ident)
+            && self.curr_token().text == "form"
+            && self.peek_token().is(LBracket)
+        {
+            self.next_token();
+
+            let value = self.parse_expression().unwrap_or_else(Expression::Error);
+
+            return Ok(Statement::FormBody { value, start });
+        }
+
         let value = match self.parse_expression() {
             Ok(e) => e,
             Err(error) => {
@@ -321,14 +448,120 @@ impl<'source> Parser<'source> {
         Ok(Statement::Body { value, start })
     }
 
+    /// Parses `query "name" value` as a query parameter, and `query value` as a graphql query.
+    fn parse_query(&mut self) -> Result<'source, Statement<'source>> {
+        if !self.peek_token().is(StringLiteral) {
+            return self.parse_graphql_query();
+        }
+
+        let start = self.curr_token().start;
+
+        let name: ast::StringLiteral = self.next_token().into();
+
+        // only a value on the same line, since the next line starts another statement, which
+        // may well start with an identifier, like `variables { .. }`
+        let is_parameter = self.peek_token().start.line == name.span.start.line
+            && self.peek_token().is_one_of(&[
+                StringLiteral,
+                RawStringLiteral,
+                OpeningBackTick,
+                Ident,
+                Query,
+                Variables,
+                Number,
+                Boolean,
+                Null,
+            ]);
+
+        if !is_parameter {
+            let value = Expression::String(name);
+
+            let value = if self.peek_token().is(QuestionMark) {
+                self.parse_conditional_expression(value)
+                    .unwrap_or_else(Expression::Error)
+            } else {
+                value
+            };
+
+            return Ok(Statement::GraphqlQuery { value, start });
+        }
+
+        self.next_token();
+
+        let value = self.parse_expression().unwrap_or_else(Expression::Error);
+
+        Ok(Statement::Query {
+            name: ParsedNode::Ok(name),
+            value,
+        })
+    }
+
+    fn parse_graphql_query(&mut self) -> Result<'source, Statement<'source>> {
+        let start = self.curr_token().start;
+
+        self.next_token();
+
+        let value = self.parse_expression().unwrap_or_else(Expression::Error);
+
+        Ok(Statement::GraphqlQuery { value, start })
+    }
+
+    fn parse_graphql_variables(&mut self) -> Result<'source, Statement<'source>> {
+        let start = self.curr_token().start;
+
+        self.next_token();
+
+        let value = self.parse_expression().unwrap_or_else(Expression::Error);
+
+        Ok(Statement::GraphqlVariables { value, start })
+    }
+
     fn parse_expression(&mut self) -> Result<'source, Expression<'source>> {
+        let exp = self.parse_primary_expression()?;
+
+        if self.peek_token().is(QuestionMark) {
+            return self.parse_conditional_expression(exp);
+        }
+
+        Ok(exp)
+    }
+
+    fn parse_conditional_expression(
+        &mut self,
+        condition: Expression<'source>,
+    ) -> Result<'source, Expression<'source>> {
+        let e = Expectations::new(self);
+
+        self.next_token();
+        self.next_token();
+
+        let consequent = self.parse_expression()?;
+
+        e.expect_peek(self, Colon)?;
+
+        self.next_token();
+
+        // right associative, so `a ? b : c ? d : e` is `a ? b : (c ? d : e)`
+        let alternate = self.parse_expression()?;
+
+        Ok(Expression::Conditional(ast::ConditionalExpr {
+            condition: condition.into(),
+            consequent: consequent.into(),
+            alternate: alternate.into(),
+        }))
+    }
+
+    fn parse_primary_expression(&mut self) -> Result<'source, Expression<'source>> {
         let e = Expectations::new(self);
         let kind = self.curr_token().kind;
 
         let exp = match kind {
             Ident if self.peek_token().kind == LParen => self.parse_call_expression().into(),
             Ident => Expression::Identifier(self.curr_token().into()),
-            StringLiteral => Expression::String(self.curr_token().into()),
+            Head | Options | Query | Variables => {
+                Expression::Identifier((&self.curr_token_as_identifier()).into())
+            }
+            StringLiteral | RawStringLiteral => Expression::String(self.curr_token().into()),
             Boolean => Expression::Bool((
                 self.curr_token().span(),
                 self.curr_token()
@@ -341,7 +574,7 @@ impl<'source> Parser<'source> {
                 self.curr_token()
                     .text
                     .parse()
-                    .expect("failed to parse as an unsigned int"),
+                    .expect("failed to parse as a number"),
             )),
             OpeningBackTick => self.parse_multiline_string_literal(),
             LBracket => self.parse_object_literal(),
@@ -455,8 +688,8 @@ impl<'source> Parser<'source> {
         let key_token = self.curr_token();
 
         let key = match_or_throw! { key_token.kind; e; self;
-            Get | Post | Put | Patch | Delete
-                | Header | Body | Set | Let
+            Get | Post | Put | Patch | Delete | Head | Options | Graphql
+                | Header | Body | Query | Variables | Set | Let
                 | Null | Ident | StringLiteral => key_token.into(),
         };
 
@@ -515,6 +748,14 @@ impl<'source> Parser<'source> {
                         parts.push(TemplateStringPart::ExpressionPart(Expression::Error(error)));
                     }
                 }
+                UnfinishedMultiLineStringLiteral => {
+                    let error = expectations.expected_token(self.curr_token(), ClosingBackTick);
+                    parts.push(TemplateStringPart::ExpressionPart(Expression::Error(
+                        error.into(),
+                    )));
+                    end = self.curr_token().end_position();
+                    break;
+                }
                 _ => {
                     end = self.curr_token().end_position();
                     break;
@@ -533,7 +774,18 @@ impl<'source> Parser<'source> {
     fn parse_attribute(&mut self) -> Result<'source, Item<'source>> {
         let e = Expectations::new(self);
 
-        let identifier = e.expect_peek(self, TokenKind::Ident)?.into();
+        // A bare `@`, like one just being typed, is kept as an attribute without a name, so that
+        // whatever follows it is still parsed as usual
+        let identifier = match e.expect_peek_ahead(self, TokenKind::Ident) {
+            Ok(_) => self.next_token().into(),
+            Err(error) => {
+                return Ok(Item::Attribute(Attribute {
+                    location: e.start,
+                    identifier: ParsedNode::Error(error),
+                    arguments: None,
+                }))
+            }
+        };
 
         if self.peek_token().kind != TokenKind::LParen {
             return Ok(Item::Attribute(Attribute {
@@ -604,12 +856,34 @@ impl<'source> Parser<'source> {
 
     fn parse_let_statement(&mut self) -> Result<'source, Item<'source>> {
         let e = Expectations::new(self);
-        let identifier = self.next_token().into();
+        self.next_token();
+        let identifier = (&self.curr_token_as_identifier()).into();
 
         e.expect_peek(self, TokenKind::Assign)?;
 
         self.next_token();
 
+        // the response of a request can be bound too, like `let user = get /user`
+        let kind = self.curr_token().kind;
+        let method = match kind {
+            Get => Some(RequestMethod::GET),
+            Post => Some(RequestMethod::POST),
+            Put => Some(RequestMethod::PUT),
+            Patch => Some(RequestMethod::PATCH),
+            Delete => Some(RequestMethod::DELETE),
+            Head if self.peek_token_is_endpoint() => Some(RequestMethod::HEAD),
+            Options if self.peek_token_is_endpoint() => Some(RequestMethod::OPTIONS),
+            Graphql => Some(RequestMethod::GRAPHQL),
+            _ => None,
+        };
+
+        if let Some(method) = method {
+            return Ok(Item::Let(ast::VariableDeclaration {
+                identifier,
+                value: Expression::Request(self.parse_request_node(method).into()),
+            }));
+        }
+
         Ok(Item::Let(ast::VariableDeclaration {
             value: match self.parse_expression() {
                 Ok(e) => e,
@@ -260,7 +260,7 @@ let a = [m]
 
 get /admin {
    header "Content-Type" "application/json"
-   body json({'a': 12, t: true}) }
+   body json({'a: 12, t: true}) }
 
 [test, 12, {ness: false, wow: [1, 2,3]}]
 
@@ -339,3 +339,216 @@ let o = { key: "value",
 }"#
     );
 }
+
+#[test]
+fn it_formats_conditional_expressions() {
+    assert_fmt!(
+        r#"
+get /api {
+  header "X-Debug" env("DEBUG")?"1":  "0"
+  body  a ? b : c ? "c" : null
+}
+"#
+    );
+}
+
+#[test]
+fn it_formats_graphql_requests() {
+    assert_fmt!(
+        r#"
+graphql   /graphql {
+  query   `{ me { id } }`
+     variables {id: "1"}
+}
+"#
+    );
+}
+
+#[test]
+fn it_sorts_object_keys_with_their_comments() {
+    let program = Program::from(
+        r#"
+let o = {
+  // the zebra
+  zebra: "z",
+  apple: [3, 2, 1],
+  mango: {
+    // inner
+    b: 1,
+    a: 2
+  }
+  // trailing
+}
+"#,
+    );
+
+    let formatted_text = program
+        .format_with(fmt::FormattedPrinter::new().sort_keys(true))
+        .expect("formatted text should contain only valid syntax");
+
+    let reformatted_text = Program::from(&formatted_text)
+        .format_with(fmt::FormattedPrinter::new().sort_keys(true))
+        .expect("formatted text should contain only valid syntax");
+
+    assert_eq!(formatted_text, reformatted_text);
+
+    assert_display_snapshot!(formatted_text);
+}
+
+#[test]
+fn it_keeps_requests_without_a_method_as_written() {
+    assert_fmt!(
+        r#"
+http://localhost/health
+/users   {
+header "Accept" "*/*"
+}
+"#
+    );
+}
+
+#[test]
+fn it_keeps_escapes_in_template_strings() {
+    assert_fmt!(
+        r#"
+let s = `say \`hi\` for \${literally} ${name}`
+"#
+    );
+}
+
+#[test]
+fn it_formats_comments_in_attribute_arguments() {
+    let program = Program::from(
+        r#"
+@log(
+// where the response goes
+"output/x.json")
+get /a {
+  body json([1, // one
+  2])
+}
+"#,
+    );
+
+    let formatted_text = program
+        .to_formatted_string()
+        .expect("formatted text should contain only valid syntax");
+
+    let reformatted_text = Program::from(&formatted_text)
+        .to_formatted_string()
+        .expect("formatted text should contain only valid syntax");
+
+    assert_eq!(formatted_text, reformatted_text);
+
+    assert_display_snapshot!(formatted_text);
+}
+
+#[test]
+fn it_wraps_lists_wider_than_the_max_line_width() {
+    let program = Program::from(r#"let a = [1, 2, 3]"#);
+
+    let format = |width| {
+        program
+            .format_with(fmt::FormattedPrinter::new().max_line_width(width))
+            .expect("formatted text should contain only valid syntax")
+    };
+
+    assert_eq!(format(17), "let a = [1, 2, 3]");
+    assert_eq!(format(16), "let a = [\n  1,\n  2,\n  3\n]");
+}
+
+#[test]
+fn it_wraps_nested_lists_that_still_dont_fit() {
+    let program = Program::from(
+        r#"
+get /a {
+  body json(["a long string", ["another long string", "and another"]])
+}
+"#,
+    );
+
+    let printer = || fmt::FormattedPrinter::new().max_line_width(40);
+
+    let formatted_text = program
+        .format_with(printer())
+        .expect("formatted text should contain only valid syntax");
+
+    let reformatted_text = Program::from(&formatted_text)
+        .format_with(printer())
+        .expect("formatted text should contain only valid syntax");
+
+    assert_eq!(formatted_text, reformatted_text);
+
+    assert_display_snapshot!(formatted_text);
+}
+
+#[test]
+fn it_keeps_raw_strings_as_written() {
+    assert_fmt!(
+        r#"
+post /script {
+  body r`#!/bin/sh
+echo ${HOME}`
+}
+"#
+    );
+}
+
+#[test]
+fn it_keeps_the_quotes_strings_are_written_with() {
+    assert_fmt!(
+        r#"
+post /users {
+  header "Content-Type" 'application/json'
+  body '{"name": "me"}'
+}
+"#
+    );
+}
+
+#[test]
+fn it_formats_requests_bound_with_let() {
+    assert_fmt!(
+        r#"
+let user =   get /user {
+header "Accept"    "application/json"
+}
+"#
+    );
+}
+
+#[test]
+fn it_formats_headers_declarations() {
+    assert_fmt!(
+        r#"
+headers {   header "Accept" "application/json"
+// for every request
+header "Authorization" env("token") }
+get /a
+"#
+    );
+}
+
+#[test]
+fn it_formats_form_bodies() {
+    assert_fmt!(
+        r#"
+post /login {
+body   form {"user": env("u"),
+   "pass": "p" }
+}
+"#
+    );
+}
+
+#[test]
+fn it_formats_query_statements() {
+    assert_fmt!(
+        r#"
+get /api {
+query   "sort"    "asc"
+  query "page" env("page")
+}
+"#
+    );
+}
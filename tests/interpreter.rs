@@ -1,17 +1,19 @@
 use std::{fs::File, io::Read, path::PathBuf};
 
 use insta::assert_debug_snapshot;
-use rested::{interpreter::environment::Environment, parser::ast::Program};
+use rested::{
+    interpreter::{
+        cassette_runner::{CassetteMode, Cassettes},
+        dry_runner::DRY_RUN_BODY,
+        environment::Environment,
+        runner::{RetryPolicy, RunOptions, RunResponse},
+    },
+    parser::ast::Program,
+};
 
 fn new_env_with_vars(vars: &[(&str, &str)]) -> Environment {
-    let mut env = Environment::new(PathBuf::from(".env.rd.json")).unwrap();
-
-    for (key, value) in vars {
-        env.set_variable(key.to_string(), value.to_string())
-            .unwrap();
-    }
-
-    return env;
+    Environment::in_memory()
+        .with_variables(vars.iter().map(|(key, value)| ("default", *key, *value)))
 }
 
 macro_rules! run {
@@ -118,13 +120,201 @@ fn requests_work() {
     delete_api.assert();
 }
 
+#[test]
+fn head_and_options_requests_work() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let head_api = server
+        .mock("HEAD", "/api")
+        .with_status(200)
+        .with_header("Cache-Control", "max-age=60")
+        .create();
+
+    let options_api = server
+        .mock("OPTIONS", "/api")
+        .match_header("Origin", "http://example.com")
+        .with_status(204)
+        .with_header("Access-Control-Allow-Origin", "*")
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        head /api
+
+        options /api {
+           header "Origin" "http://example.com"
+        }
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let responses = program.run_ureq(None);
+
+    head_api.assert();
+    options_api.assert();
+
+    assert!(responses
+        .iter()
+        .all(|(_, res)| matches!(res, RunResponse::Success(body) if body.is_empty())));
+}
+
+#[test]
+fn json_responses_are_pretty_printed() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let _json = server
+        .mock("GET", "/json")
+        .with_header("Content-Type", "application/json; charset=utf-8")
+        .with_body(r#"{"id":7,"tags":["a"]}"#)
+        .create();
+
+    let _problem = server
+        .mock("GET", "/problem")
+        .with_status(200)
+        .with_header("Content-Type", "application/problem+json")
+        .with_body(r#"{"title":"nope"}"#)
+        .create();
+
+    let _malformed = server
+        .mock("GET", "/malformed")
+        .with_header("Content-Type", "application/json")
+        .with_body(r#"{"id":"#)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        get /json
+        get /problem
+        get /malformed
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let bodies = program
+        .run_ureq(None)
+        .into_iter()
+        .map(|(_, res)| match res {
+            RunResponse::Success(body) => body,
+            res => panic!("expected a successful response, got {res:?}"),
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        bodies,
+        [
+            "{\n  \"id\": 7,\n  \"tags\": [\n    \"a\"\n  ]\n}",
+            "{\n  \"title\": \"nope\"\n}",
+            r#"{"id":"#,
+        ]
+    );
+}
+
+#[test]
+fn responses_must_have_the_expected_status() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let _ok = server.mock("GET", "/ok").with_status(200).create();
+
+    let _missing = server
+        .mock("GET", "/missing")
+        .with_status(404)
+        .with_body("not here")
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @expect(200)
+        get /ok
+
+        @expect(404)
+        get /missing
+
+        @expect(200)
+        get /missing
+
+        @expect(201)
+        get /ok
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let responses = program.run_ureq(None);
+
+    assert!(matches!(&responses[0].1, RunResponse::Success(_)));
+    assert!(matches!(&responses[1].1, RunResponse::Success(body) if body == "not here"));
+    assert!(
+        matches!(&responses[2].1, RunResponse::Assertion(err) if err.contains("expected status 200 but got 404"))
+    );
+    assert!(
+        matches!(&responses[3].1, RunResponse::Assertion(err) if err.contains("expected status 201 but got 200"))
+    );
+}
+
+#[test]
+fn response_bodies_must_contain_every_asserted_substring() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let _users = server
+        .mock("POST", "/users")
+        .with_status(201)
+        .with_body(r#"{"ok":true,"id":1}"#)
+        .expect(2)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @assert_contains(`"ok":true`)
+        @assert_contains(`"id":`)
+        post /users
+
+        @assert_contains(`"ok":true`)
+        @assert_contains(`"name":`)
+        @assert_contains(`"email":`)
+        post /users
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let responses = program.run_ureq(None);
+
+    assert!(matches!(&responses[0].1, RunResponse::Success(_)));
+    assert!(matches!(
+        &responses[1].1,
+        RunResponse::Assertion(err)
+            if err.contains(r#"expected the response body to contain "\"name\":""#)
+                && err.contains("[9:10]")
+    ));
+}
+
+#[test]
+fn expect_attribute_requires_a_status_code() {
+    let code = r#"
+        @expect(42)
+        get http://localhost/api
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
 #[test]
 fn comments_are_ignored() {
     let mut server = mockito::Server::new();
 
     let url = server.url();
 
-    let env = Environment::new(PathBuf::from(".env.rd.json")).unwrap();
+    let env = Environment::in_memory();
 
     let get_api = server
         .mock("GET", "/api")
@@ -155,7 +345,7 @@ fn comments_are_ignored() {
 fn requests_are_skippable() {
     let mut server = mockito::Server::new();
     let url = server.url();
-    let mut env = Environment::new(PathBuf::from(".env.rd.json")).unwrap();
+    let mut env = Environment::in_memory();
 
     env.set_variable("b_url".to_string(), url).unwrap();
 
@@ -192,11 +382,51 @@ fn requests_are_skippable() {
     }
 }
 
+#[test]
+fn requests_are_skipped_when_skip_if_is_truthy() {
+    let env = new_env_with_vars(&[("CI", "true"), ("NIGHTLY", "False"), ("STAGING", "0")]);
+
+    let code = r#"
+        @skip_if(env("CI"))
+        get http://localhost/ci
+
+        @skip_if(env("NIGHTLY"))
+        get http://localhost/nightly
+
+        @skip_if(env("STAGING"))
+        get http://localhost/staging
+
+        @skip_if(env("LOCAL", ""))
+        get http://localhost/local
+
+        @skip_if(0)
+        get http://localhost/zero
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let urls = program
+        .items
+        .iter()
+        .map(|item| item.request.url.as_str())
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        urls,
+        [
+            "http://localhost/nightly",
+            "http://localhost/staging",
+            "http://localhost/local",
+            "http://localhost/zero"
+        ]
+    );
+}
+
 #[test]
 fn responses_can_be_logged() {
     let mut server = mockito::Server::new();
     let url = server.url();
-    let mut env = Environment::new(PathBuf::from(".env.rd.json")).unwrap();
+    let mut env = Environment::in_memory();
 
     env.set_variable("b_url".to_string(), url).unwrap();
 
@@ -237,7 +467,7 @@ fn responses_can_be_logged() {
 fn let_bindings_work() {
     let mut server = mockito::Server::new();
     let url = server.url();
-    let mut env = Environment::new(PathBuf::from(".env.rd.json")).unwrap();
+    let mut env = Environment::in_memory();
 
     env.set_variable("test".to_string(), "12345".to_string())
         .unwrap();
@@ -316,9 +546,9 @@ fn running_specific_requests_by_name() {
 
 #[test]
 fn name_attribute_requires_value() {
-    let mut env = Environment::new(PathBuf::from(".env.rd.json")).unwrap();
+    let mut env = Environment::in_memory();
 
-    env.set_variable("b_url".to_string(), "asdfasdf".to_string())
+    env.set_variable("b_url".to_string(), "http://localhost".to_string())
         .unwrap();
 
     let code = r#"
@@ -341,7 +571,7 @@ fn prevents_duplicate_attributes() {
         get /api {}
     "#;
 
-    let env = new_env_with_vars(&[("b_url", "asdfasdf")]);
+    let env = new_env_with_vars(&[("b_url", "http://localhost")]);
 
     let duped_att_err = Program::from(code).interpret(&env).unwrap_err();
 
@@ -354,7 +584,7 @@ fn prevents_duplicate_attributes() {
         get /api {}
     "#;
 
-    let env = new_env_with_vars(&[("b_url", "asdfasdf")]);
+    let env = new_env_with_vars(&[("b_url", "http://localhost")]);
 
     let duped_att_err = Program::from(code).interpret(&env).unwrap_err();
 
@@ -400,7 +630,7 @@ post /api {
     let mock = server
         .mock("POST", "/test")
         .match_body(mockito::Matcher::PartialJsonString(
-            r#"{"t": 123.0, "test": "ing"}"#.to_string(),
+            r#"{"t": 123, "test": "ing"}"#.to_string(),
         ))
         .with_status(200)
         .create();
@@ -409,7 +639,7 @@ post /api {
         .mock("POST", "/api")
         .match_header("Content-Type", "application/json")
         .match_body(mockito::Matcher::PartialJsonString(
-            r#"{"neet": 1337.0, "nothing": null, "arr": ["yo", {"h": "i"}], "hello": {"w": "world", "warudo": "world", "fun": true, "notFun": false, "e": {}, "em": []}}"#.to_string(),
+            r#"{"neet": 1337, "nothing": null, "arr": ["yo", {"h": "i"}], "hello": {"w": "world", "warudo": "world", "fun": true, "notFun": false, "e": {}, "em": []}}"#.to_string(),
         ))
         .with_status(200)
         .create();
@@ -439,3 +669,2271 @@ read("file")
 
     run!(code, env);
 }
+
+#[test]
+fn null_body_sends_no_body() {
+    let mut server = mockito::Server::new();
+    let url = server.url();
+    let env = new_env_with_vars(&[("b_url", &url)]);
+
+    let mock = server
+        .mock("POST", "/x")
+        .match_body("")
+        .with_status(200)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        post /x {
+            body null
+        }
+    "#;
+
+    run!(code, env);
+
+    mock.assert();
+}
+
+#[test]
+fn requests_are_retried_on_given_status_codes() {
+    let mut server = mockito::Server::new();
+    let url = server.url();
+    let env = new_env_with_vars(&[("b_url", &url)]);
+
+    let unavailable = server
+        .mock("GET", "/api")
+        .with_status(503)
+        .expect(3)
+        .create();
+
+    let not_found = server
+        .mock("GET", "/missing")
+        .with_status(404)
+        .expect(1)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        get /api
+        get /missing
+    "#;
+
+    let program = Program::from(code);
+    let program = program.interpret(&env).unwrap();
+
+    program.run_ureq_with_options(
+        None,
+        RunOptions {
+            retry: Some(RetryPolicy {
+                status_codes: [502, 503].into(),
+                retries: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+
+    unavailable.assert();
+    not_found.assert();
+}
+
+#[test]
+fn set_retry_retries_idempotent_requests_with_backoff() {
+    let mut server = mockito::Server::new();
+    let url = server.url();
+    let env = new_env_with_vars(&[("b_url", &url)]);
+
+    let get = server
+        .mock("GET", "/api")
+        .with_status(503)
+        .expect(3)
+        .create();
+
+    let post = server
+        .mock("POST", "/api")
+        .with_status(503)
+        .expect(1)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+        set RETRY { count: 2, backoff: "exponential", base_ms: 10, on: [503], idempotent_only: true }
+
+        get /api
+        post /api
+    "#;
+
+    run!(code, env);
+
+    get.assert();
+    post.assert();
+}
+
+#[test]
+fn set_retry_takes_a_policy_from_a_variable() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let get = server
+        .mock("GET", "/api")
+        .with_status(503)
+        .expect(3)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+        let policy = { count: 2, on: [503] }
+        set RETRY policy
+
+        get /api
+    "#;
+
+    run!(code, env);
+
+    get.assert();
+}
+
+#[test]
+fn malformed_json_bodies_sent_as_json_are_errors() {
+    let env = Environment::in_memory();
+
+    let code = r#"
+        post http://localhost/todos {
+            header "Content-Type" "application/json"
+            body `{ "title": "yams", }`
+        }
+    "#;
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn malformed_json_looking_bodies_without_a_content_type_are_fine() {
+    let env = Environment::in_memory();
+
+    let code = r#"
+        post http://localhost/todos {
+            body `{ "title": "yams", }`
+        }
+
+        post http://localhost/notes {
+            header "Content-Type" "text/plain"
+            body "{ not json"
+        }
+    "#;
+
+    assert!(Program::from(code).interpret(&env).is_ok());
+}
+
+#[test]
+fn set_retry_retries_every_method_by_default() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let post = server
+        .mock("POST", "/api")
+        .with_status(503)
+        .expect(3)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+        set RETRY { count: 2, on: [503] }
+
+        post /api
+    "#;
+
+    run!(code, env);
+
+    post.assert();
+}
+
+#[test]
+fn set_retry_points_at_the_bad_option() {
+    let env = Environment::in_memory();
+
+    let code = r#"set RETRY { count: 2, backoff: "sideways" }"#;
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn set_retry_rejects_unknown_options() {
+    let env = Environment::in_memory();
+
+    let code = r#"set RETRY { count: 2, times: 3 }"#;
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn base_url_requires_a_scheme() {
+    let code = r#"
+        set BASE_URL "localhost:8080"
+        get /api
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn event_streams_stop_at_max_events() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    server
+        .mock("GET", "/events")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body("data: one\n\ndata: two\n\ndata: three\n\n")
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @max_events(2)
+        get /events
+    "#;
+
+    let program = Program::from(code);
+    let program = program.interpret(&env).unwrap();
+
+    let responses = program.run_ureq(None);
+
+    let [(_, RunResponse::Success(body))] = &responses[..] else {
+        panic!("expected a single successful response, but got {responses:?}");
+    };
+
+    assert_eq!(body, "data: one\n\ndata: two\n\n");
+}
+
+#[test]
+fn conditional_expressions_pick_a_branch_by_truthiness() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url()), ("debug", "")]);
+
+    let mock = server
+        .mock("GET", "/api")
+        .match_header("X-Debug", "0")
+        .match_header("X-Trace", "on")
+        .with_status(200)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        get /api {
+           header "X-Debug" env("debug") ? "1" : "0"
+           header "X-Trace" 0 ? "off" : null ? "off" : "on"
+        }
+    "#;
+
+    run!(code, env);
+
+    mock.assert();
+}
+
+#[test]
+fn graphql_requests_post_a_json_body() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server
+        .mock("POST", "/graphql")
+        .match_header("Content-Type", "application/json")
+        .match_body(mockito::Matcher::Json(serde_json::json!({
+            "query": "query($id: ID!) { user(id: $id) { name } }",
+            "variables": { "id": "1" }
+        })))
+        .with_status(200)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        graphql /graphql {
+           query `query($id: ID!) { user(id: $id) { name } }`
+           variables { id: "1" }
+        }
+    "#;
+
+    run!(code, env);
+
+    mock.assert();
+}
+
+#[test]
+fn graphql_string_queries_are_followed_by_variables() {
+    let env = Environment::in_memory();
+
+    let code = r#"
+        graphql http://localhost/graphql {
+           query "query($id: ID!) { user(id: $id) { name } }"
+           variables { id: "1" }
+        }
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let body: serde_json::Value =
+        serde_json::from_str(program.items[0].request.body.as_deref().unwrap()).unwrap();
+
+    assert_eq!(
+        body,
+        serde_json::json!({
+            "query": "query($id: ID!) { user(id: $id) { name } }",
+            "variables": { "id": "1" }
+        })
+    );
+}
+
+#[test]
+fn headers_declarations_apply_to_every_request_after_them() {
+    let env = new_env_with_vars(&[("token", "t0k3n")]);
+
+    let code = r#"
+        get http://localhost/before
+
+        headers {
+           header "Accept" "application/json"
+           header "Authorization" `Bearer ${env("token")}`
+        }
+
+        get http://localhost/a
+
+        get http://localhost/b {
+           header "accept" "text/plain"
+        }
+
+        headers {
+           header "Accept" "application/xml"
+        }
+
+        get http://localhost/c
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let headers = program
+        .items
+        .iter()
+        .map(|item| {
+            item.request
+                .headers
+                .iter()
+                .map(|h| format!("{}: {}", h.name, h.value))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(headers[0], Vec::<String>::new());
+    assert_eq!(
+        headers[1],
+        ["Accept: application/json", "Authorization: Bearer t0k3n"]
+    );
+    assert_eq!(
+        headers[2],
+        ["accept: text/plain", "Authorization: Bearer t0k3n"]
+    );
+    assert_eq!(
+        headers[3],
+        ["Authorization: Bearer t0k3n", "Accept: application/xml"]
+    );
+}
+
+#[test]
+fn headers_declarations_may_only_declare_headers() {
+    let code = r#"
+        headers {
+           body "nope"
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn default_timeout_and_user_agent_apply_to_every_request() {
+    let code = r#"
+        set DEFAULT_TIMEOUT 3000
+        set USER_AGENT "rested/1.0"
+
+        get http://localhost/a
+
+        @timeout(500)
+        get http://localhost/b {
+           header "user-agent" "curl/8"
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let a = &program.items[0].request;
+
+    assert_eq!(a.timeout, Some(std::time::Duration::from_millis(3000)));
+    assert_eq!(a.headers[0].name, "User-Agent");
+    assert_eq!(a.headers[0].value, "rested/1.0");
+
+    let b = &program.items[1].request;
+
+    assert_eq!(b.timeout, Some(std::time::Duration::from_millis(500)));
+    assert_eq!(b.headers.len(), 1);
+    assert_eq!(b.headers[0].value, "curl/8");
+}
+
+#[test]
+fn setting_unknown_constants_fails() {
+    let code = r#"set DEFAULT_RETRIES 3"#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn query_statements_are_appended_to_the_url() {
+    let env = new_env_with_vars(&[("page", "2")]);
+
+    let code = r#"
+        get http://localhost/api?fields=id {
+           query "sort" "asc"
+           query "page" env("page")
+           query "tag" "a b"
+           query "tag" "c&d"
+        }
+
+        graphql http://localhost/graphql {
+           query "{ me { id } }"
+        }
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(
+        program.items[0].request.url,
+        "http://localhost/api?fields=id&sort=asc&page=2&tag=a+b&tag=c%26d"
+    );
+    assert_eq!(program.items[1].request.url, "http://localhost/graphql");
+}
+
+#[test]
+fn escapes_in_quoted_strings_are_resolved() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server
+        .mock("POST", "/api")
+        .match_header("X-Tab", "a\tb")
+        .match_body("say \"hi\" \u{1F600}\n")
+        .with_status(200)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        post /api {
+           header "X-Tab" "a\tb"
+           body "say \"hi\" \u{1F600}\n"
+        }
+    "#;
+
+    run!(code, env);
+
+    mock.assert();
+}
+
+#[test]
+fn template_and_raw_strings_keep_backslashes_and_newlines() {
+    let code = r#"
+        post http://localhost/a {
+           body `a\tb
+c`
+        }
+
+        post http://localhost/b {
+           body r`a\tb`
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(program.items[0].request.body.as_deref(), Some("a\\tb\nc"));
+    assert_eq!(program.items[1].request.body.as_deref(), Some("a\\tb"));
+}
+
+#[test]
+fn invalid_escapes_in_quoted_strings_are_errors() {
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(r#"get "http://localhost/\q""#)
+        .interpret(&env)
+        .unwrap_err();
+
+    assert_debug_snapshot!(err);
+
+    let err = Program::from(r#"get "http://localhost/\u{110000}""#)
+        .interpret(&env)
+        .unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn single_quoted_strings_may_contain_double_quotes() {
+    let code = r#"
+        post http://localhost {
+           body '{"name": "me"}'
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(
+        program.items[0].request.body.as_deref(),
+        Some(r#"{"name": "me"}"#)
+    );
+}
+
+#[test]
+fn negative_and_exponent_numbers_are_numbers() {
+    let code = r#"
+        post http://localhost/a {
+           body json([-42, 3.14, 2e10, 1.5E-3, -1e2])
+        }
+
+        post http://localhost/b {
+           body json(-1)
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(
+        program.items[0].request.body.as_deref(),
+        Some("[-42,3.14,20000000000,0.0015,-100]")
+    );
+    assert_eq!(program.items[1].request.body.as_deref(), Some("-1"));
+}
+
+#[test]
+fn json_calls_pretty_print_with_a_second_argument() {
+    let code = r#"
+        post http://localhost/a {
+           body json({ b: [1, 2], a: "x" }, true)
+        }
+
+        post http://localhost/b {
+           body json({ b: [1, 2], a: "x" })
+        }
+
+        post http://localhost/c {
+           body json({ a: "x" }, false)
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(
+        program.items[0].request.body.as_deref(),
+        Some("{\n  \"a\": \"x\",\n  \"b\": [\n    1,\n    2\n  ]\n}")
+    );
+    assert_eq!(
+        program.items[1].request.body.as_deref(),
+        Some(r#"{"a":"x","b":[1,2]}"#)
+    );
+    assert_eq!(
+        program.items[2].request.body.as_deref(),
+        Some(r#"{"a":"x"}"#)
+    );
+}
+
+#[test]
+fn form_bodies_are_url_encoded() {
+    let env = new_env_with_vars(&[("u", "me@example.com")]);
+
+    let code = r#"
+        post http://localhost/login {
+           body form { "user": env("u"), "pass": "a b&c", "remember": true }
+        }
+
+        post http://localhost/login {
+           header "content-type" "application/x-www-form-urlencoded; charset=utf-8"
+           body form { "tries": 3 }
+        }
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let request = &program.items[0].request;
+
+    assert_eq!(
+        request.body.as_deref(),
+        Some("user=me%40example.com&pass=a+b%26c&remember=true")
+    );
+    assert_eq!(request.headers[0].name, "Content-Type");
+    assert_eq!(
+        request.headers[0].value,
+        "application/x-www-form-urlencoded"
+    );
+
+    let request = &program.items[1].request;
+
+    assert_eq!(request.body.as_deref(), Some("tries=3"));
+    assert_eq!(request.headers.len(), 1);
+}
+
+#[test]
+fn nested_form_fields_are_type_mismatches() {
+    let code = r#"
+        post http://localhost/login {
+           body form { "user": { "name": "me" } }
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn graphql_statements_require_a_graphql_request() {
+    let code = r#"
+        post http://localhost {
+           query "{ me { id } }"
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn base_url_can_differ_per_namespace() {
+    let mut dev = mockito::Server::new();
+    let mut prod = mockito::Server::new();
+
+    let mut env = new_env_with_vars(&[("base_url", &dev.url())]);
+    env.namespaced_variables.insert(
+        "prod".to_string(),
+        [("base_url".to_string(), prod.url())].into(),
+    );
+
+    let dev_mock = dev.mock("GET", "/api").with_status(200).expect(1).create();
+    let prod_mock = prod.mock("GET", "/api").with_status(200).expect(1).create();
+
+    let code = r#"
+        set BASE_URL env("base_url")
+        get /api
+    "#;
+
+    run!(code, env);
+
+    env.select_variables_namespace("prod".to_string());
+
+    run!(code, env);
+
+    dev_mock.assert();
+    prod_mock.assert();
+}
+
+#[test]
+fn base_url_must_not_be_empty() {
+    let code = r#"
+        set BASE_URL env("base_url")
+        get /api
+    "#;
+
+    let env = new_env_with_vars(&[("base_url", "")]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn delete_requests_send_headers_and_a_body() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let with_body = server
+        .mock("DELETE", "/items")
+        .match_header("Authorization", "token")
+        .match_body(mockito::Matcher::Json(serde_json::json!({ "ids": [1, 2] })))
+        .with_status(200)
+        .expect(1)
+        .create();
+
+    let without_body = server
+        .mock("DELETE", "/items/3")
+        .match_header("Authorization", "token")
+        .match_body("")
+        .with_status(200)
+        .expect(1)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        delete /items {
+           header "Authorization" "token"
+           body `{ "ids": [1, 2] }`
+        }
+
+        delete /items/3 {
+           header "Authorization" "token"
+        }
+    "#;
+
+    run!(code, env);
+
+    with_body.assert();
+    without_body.assert();
+}
+
+#[test]
+fn requests_can_be_repeated() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server
+        .mock("GET", "/api")
+        .with_status(200)
+        .expect(3)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @repeat(3)
+        get /api
+    "#;
+
+    let program = Program::from(code);
+    let program = program.interpret(&env).unwrap();
+
+    assert_eq!(program.run_ureq(None).len(), 3);
+
+    mock.assert();
+}
+
+#[test]
+fn repeated_requests_log_to_a_file_per_run() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let _mock = server
+        .mock("GET", "/api")
+        .with_status(200)
+        .with_body("yams")
+        .expect(2)
+        .create();
+
+    let dir = std::env::temp_dir().join("rested_repeated_requests_log_to_a_file_per_run");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let code = format!(
+        r#"
+        set BASE_URL env("b_url")
+
+        @repeat(2)
+        @log("{}")
+        get /api
+    "#,
+        dir.join("out.json").display()
+    );
+
+    let program = Program::from(&code).interpret(&env).unwrap();
+
+    program.run_ureq(None);
+
+    assert_eq!(
+        std::fs::read_to_string(dir.join("out.1.json")).unwrap(),
+        "yams"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("out.2.json")).unwrap(),
+        "yams"
+    );
+    assert!(!dir.join("out.json").exists());
+}
+
+#[test]
+fn repeat_requires_a_positive_count() {
+    let code = r#"
+        @repeat(0)
+        get http://localhost
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn repeat_counts_are_bounded() {
+    let env = new_env_with_vars(&[]);
+
+    for code in [
+        "@repeat(10001) get http://localhost",
+        "@repeat(1e300) get http://localhost",
+    ] {
+        let err = Program::from(code).interpret(&env).unwrap_err();
+
+        assert_debug_snapshot!(err);
+    }
+}
+
+#[test]
+fn binary_files_can_be_read_as_base64() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server
+        .mock("POST", "/upload")
+        .match_body(mockito::Matcher::Json(serde_json::json!({
+            "file": "iVBORw0KGgoA//4="
+        })))
+        .with_status(200)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        post /upload {
+           body json({ file: read_bytes_base64("tests/files/binary.bin") })
+        }
+    "#;
+
+    run!(code, env);
+
+    mock.assert();
+}
+
+#[test]
+fn bodies_read_from_files_get_a_content_type_from_the_extension() {
+    let code = r#"
+        post http://localhost/a {
+           body read("tests/files/test_data.json")
+        }
+
+        post http://localhost/b {
+           header "content-type" "text/plain"
+           body read("tests/files/test_data.json")
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let content_types: Vec<_> = program
+        .items
+        .iter()
+        .map(|item| {
+            item.request
+                .headers
+                .iter()
+                .filter(|h| h.name.eq_ignore_ascii_case("content-type"))
+                .map(|h| h.value.as_str())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    assert_eq!(content_types, [["application/json"], ["text/plain"]]);
+}
+
+#[test]
+fn relative_paths_are_read_from_the_folder_of_the_script() {
+    let env = new_env_with_vars(&[]);
+
+    let absolute = std::fs::canonicalize("tests/files/test_data.json").unwrap();
+
+    let code = format!(
+        r#"
+        post http://localhost/a {{
+           body read("test_data.json")
+        }}
+
+        post http://localhost/b {{
+           body read("{}")
+        }}
+    "#,
+        absolute.display()
+    );
+
+    let options = rested::interpreter::InterpretOptions {
+        script_dir: Some(PathBuf::from("tests/files")),
+        ..Default::default()
+    };
+
+    let program =
+        rested::interpreter::interpret_program_with_options(&code, &env, options).unwrap();
+
+    let expected = std::fs::read_to_string(&absolute).unwrap();
+
+    for item in program.items.iter() {
+        assert_eq!(item.request.body.as_deref(), Some(expected.as_str()));
+    }
+}
+
+#[test]
+fn reading_a_missing_file_as_base64_fails_at_the_argument() {
+    let code = r#"
+        post http://localhost {
+           body read_bytes_base64("tests/files/missing.bin")
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn strings_can_be_base64_encoded_and_decoded() {
+    let env = new_env_with_vars(&[]);
+
+    let code = r#"
+        get http://localhost/me {
+           header "Authorization" `Basic ${base64("user:pass")}`
+           header "X-Decoded" base64_decode("dXNlcjpwYXNz")
+        }
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let headers = &program.items[0].request.headers;
+
+    assert_eq!(headers[0].value, "Basic dXNlcjpwYXNz");
+    assert_eq!(headers[1].value, "user:pass");
+}
+
+#[test]
+fn decoding_invalid_base64_fails_at_the_argument() {
+    let env = new_env_with_vars(&[]);
+
+    let code = r#"
+        get http://localhost/me {
+           header "X-Decoded" base64_decode("not base64!")
+        }
+    "#;
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn strings_can_be_repeated_to_build_large_bodies() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server
+        .mock("POST", "/load")
+        .match_body("abcabcabcabc")
+        .with_status(200)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        post /load {
+           body repeat_string("abc", 4)
+        }
+    "#;
+
+    run!(code, env);
+
+    mock.assert();
+}
+
+#[test]
+fn repeating_a_string_past_the_limit_fails_at_the_count() {
+    let code = r#"
+        post http://localhost {
+           body repeat_string("abc", 100000000)
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn base_url_with_a_trailing_slash_joins_pathnames_with_one_slash() {
+    let code = r#"
+        set BASE_URL "http://localhost/v1/"
+        get /api
+        get /
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let urls: Vec<_> = program
+        .items
+        .iter()
+        .map(|i| i.request.url.as_str())
+        .collect();
+
+    assert_eq!(urls, ["http://localhost/v1/api", "http://localhost/v1/"]);
+}
+
+#[test]
+fn request_vars_are_scoped_to_their_request() {
+    let code = r#"
+        let page = "1"
+
+        @var(page, "3")
+        @var(size, "10")
+        get `http://localhost/items?page=${page}&size=${size}`
+
+        get `http://localhost/items?page=${page}`
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let urls: Vec<_> = program
+        .items
+        .iter()
+        .map(|i| i.request.url.as_str())
+        .collect();
+
+    assert_eq!(
+        urls,
+        [
+            "http://localhost/items?page=3&size=10",
+            "http://localhost/items?page=1"
+        ]
+    );
+}
+
+#[test]
+fn request_vars_do_not_leak_into_later_requests() {
+    let code = r#"
+        @var(page, "3")
+        get http://localhost
+
+        get `http://localhost?page=${page}`
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn request_vars_are_bound_once_per_request() {
+    let code = r#"
+        @var(page, "1")
+        @var(page, "2")
+        get `http://localhost/${page}`
+
+        get `http://localhost/leak/${page}`
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn request_vars_need_a_name() {
+    let code = r#"
+        @var("page", "3")
+        get http://localhost
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn downloads_save_the_raw_response_bytes() {
+    let mut server = mockito::Server::new();
+
+    let file = std::env::temp_dir().join("rested_downloads_save_the_raw_response_bytes.bin");
+    let _ = std::fs::remove_file(&file);
+
+    let env = new_env_with_vars(&[("b_url", &server.url()), ("file", file.to_str().unwrap())]);
+
+    let bytes = [0x89, b'P', b'N', b'G', 0x00, 0xff, 0xfe];
+
+    server
+        .mock("GET", "/export")
+        .with_status(200)
+        .with_header("content-disposition", "attachment; filename=\"export.bin\"")
+        .with_body(bytes)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @download(env("file"))
+        get /export
+    "#;
+
+    let program = Program::from(code);
+    let program = program.interpret(&env).unwrap();
+
+    let responses = program.run_ureq(None);
+
+    assert!(matches!(responses[..], [(_, RunResponse::Success(_))]));
+    assert_eq!(std::fs::read(&file).unwrap(), bytes);
+}
+
+#[test]
+fn requests_without_a_method_are_sent_as_gets() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server.mock("GET", "/health").with_status(200).create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+        /health
+    "#;
+
+    run!(code, env);
+
+    mock.assert();
+}
+
+#[test]
+fn log_can_target_stdout_stderr_or_a_file() {
+    use rested::interpreter::ir::LogDestination;
+
+    let code = r#"
+        @log
+        get http://localhost/a
+
+        @log(stderr)
+        get http://localhost/b
+
+        @log("out.json")
+        get http://localhost/c
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let destinations: Vec<_> = program
+        .items
+        .iter()
+        .map(|i| i.log_destination.as_ref())
+        .collect();
+
+    assert!(matches!(
+        destinations[..],
+        [
+            Some(LogDestination::Std),
+            Some(LogDestination::Stderr),
+            Some(LogDestination::File(_))
+        ]
+    ));
+}
+
+#[test]
+fn template_strings_can_escape_backticks_and_dollar_signs() {
+    let code = r#"
+        let name = "x"
+        get `http://localhost/\`${name}\`/\${name}/\$`
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(
+        program.items[0].request.url,
+        "http://localhost/`x`/${name}/$"
+    );
+}
+
+#[test]
+fn template_strings_can_escape_backslashes() {
+    let code = r#"
+        post http://localhost/a { body `C:\\` }
+        post http://localhost/b { body `\`` }
+        post http://localhost/c { body `a\tb\\\${c}` }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let bodies: Vec<_> = program
+        .items
+        .iter()
+        .map(|item| item.request.body.as_deref().unwrap())
+        .collect();
+
+    assert_eq!(bodies, ["C:\\", "`", "a\\tb\\${c}"]);
+}
+
+#[test]
+fn conditional_requests_send_the_last_validators() {
+    let mut server = mockito::Server::new();
+
+    let cache =
+        std::env::temp_dir().join("rested_conditional_requests_send_the_last_validators.json");
+    let _ = std::fs::remove_file(&cache);
+
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let first = server
+        .mock("GET", "/feed")
+        .match_header("if-none-match", mockito::Matcher::Missing)
+        .with_status(200)
+        .with_header("etag", "\"v1\"")
+        .with_body("fresh")
+        .create();
+
+    let second = server
+        .mock("GET", "/feed")
+        .match_header("if-none-match", "\"v1\"")
+        .with_status(304)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @conditional
+        get /feed
+    "#;
+
+    let program = Program::from(code);
+
+    let run = || {
+        program.interpret(&env).unwrap().run_ureq_with_options(
+            None,
+            RunOptions {
+                conditional_cache_path: Some(cache.clone()),
+                ..Default::default()
+            },
+        )
+    };
+
+    let responses = run();
+    assert!(matches!(&responses[..], [(_, RunResponse::Success(body))] if body == "fresh"));
+
+    let responses = run();
+    assert!(matches!(responses[..], [(_, RunResponse::Success(_))]));
+
+    first.assert();
+    second.assert();
+}
+
+#[test]
+fn replayed_requests_answer_with_recorded_responses() {
+    let mut server = mockito::Server::new();
+
+    let dir = std::env::temp_dir().join("rested_replayed_requests_answer_with_recorded_responses");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server
+        .mock("GET", "/todos")
+        .with_status(200)
+        .with_body("recorded")
+        .expect(1)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        get /todos
+    "#;
+
+    let program = Program::from(code);
+
+    let run = |mode| {
+        program.interpret(&env).unwrap().run_ureq_with_options(
+            None,
+            RunOptions {
+                cassettes: Some(Cassettes {
+                    dir: dir.clone(),
+                    mode,
+                }),
+                ..Default::default()
+            },
+        )
+    };
+
+    run(CassetteMode::Record);
+
+    let responses = run(CassetteMode::Replay);
+    assert!(matches!(&responses[..], [(_, RunResponse::Success(body))] if body == "recorded"));
+
+    mock.assert();
+}
+
+#[test]
+fn idempotency_keys_are_reused_across_repeats() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @idempotency_key
+        @repeat(2)
+        post /payments {
+            body "{}"
+        }
+
+        @idempotency_key
+        post /refunds {
+            header "idempotency-key" "mine"
+        }
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let key_of = |i: usize| {
+        let headers = &program.items[i].request.headers;
+        assert_eq!(headers.len(), 1);
+        headers[0].value.clone()
+    };
+
+    let key = key_of(0);
+    assert_eq!(key_of(1), "mine");
+
+    let payments = server
+        .mock("POST", "/payments")
+        .match_header("idempotency-key", key.as_str())
+        .expect(2)
+        .create();
+
+    let refunds = server
+        .mock("POST", "/refunds")
+        .match_header("idempotency-key", "mine")
+        .create();
+
+    program.run_ureq(None);
+
+    payments.assert();
+    refunds.assert();
+}
+
+#[test]
+fn responses_can_be_printed_as_json_whatever_their_content_type() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let _mock = server
+        .mock("GET", "/todos")
+        .with_header("content-type", "text/plain")
+        .with_body(r#"{"id":1}"#)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @as("json")
+        get /todos
+    "#;
+
+    let responses = Program::from(code).interpret(&env).unwrap().run_ureq(None);
+
+    assert!(
+        matches!(&responses[..], [(_, RunResponse::Success(body))] if body == "{\n  \"id\": 1\n}")
+    );
+}
+
+#[test]
+fn response_formats_are_json_or_text() {
+    let env = Environment::in_memory();
+
+    let code = r#"
+        @as("yaml")
+        get http://localhost/todos
+    "#;
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn auth_sets_the_authorization_header() {
+    let env = new_env_with_vars(&[("token", "abc")]);
+
+    let code = r#"
+        @auth("bearer", env("token"))
+        get http://localhost/me
+
+        @auth("basic", "user", "pass")
+        get http://localhost/me
+
+        @auth("bearer", "ignored")
+        get http://localhost/me {
+            header "Authorization" "Custom xyz"
+        }
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let authorization: Vec<_> = program
+        .items
+        .iter()
+        .map(|item| item.request.headers[0].value.as_str())
+        .collect();
+
+    assert_eq!(
+        authorization,
+        ["Bearer abc", "Basic dXNlcjpwYXNz", "Custom xyz"]
+    );
+}
+
+#[test]
+fn auth_schemes_are_bearer_or_basic() {
+    let env = Environment::in_memory();
+
+    let code = r#"
+        @auth("digest", "user", "pass")
+        get http://localhost/me
+    "#;
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn chunked_requests_send_the_body_in_chunks() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server
+        .mock("POST", "/upload")
+        .match_header("transfer-encoding", "chunked")
+        .match_header("content-length", mockito::Matcher::Missing)
+        .match_body("lots of data")
+        .with_status(200)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @chunked
+        post /upload {
+          body "lots of data"
+        }
+    "#;
+
+    let program = Program::from(code);
+    let program = program.interpret(&env).unwrap();
+
+    let responses = program.run_ureq(None);
+
+    assert!(matches!(responses[..], [(_, RunResponse::Success(_))]));
+    mock.assert();
+}
+
+#[test]
+fn chunked_requests_need_a_body() {
+    let code = r#"
+        @chunked
+        get http://localhost
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn a_given_base_url_overrides_the_one_set_in_the_script() {
+    let code = r#"
+        set BASE_URL "http://localhost:8080"
+
+        get /api
+        get http://example.com/ok
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code)
+        .interpret_with_base_url(&env, "http://localhost:9090/v1")
+        .unwrap();
+
+    let urls: Vec<_> = program
+        .items
+        .iter()
+        .map(|item| item.request.url.as_str())
+        .collect();
+
+    assert_eq!(
+        urls,
+        ["http://localhost:9090/v1/api", "http://example.com/ok"]
+    );
+}
+
+#[test]
+fn request_names_can_be_template_strings() {
+    let code = r#"
+        @name(`req-${env("user")}`)
+        get http://localhost/api
+    "#;
+
+    let env = new_env_with_vars(&[("user", "alice")]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(program.items[0].name.as_deref(), Some("req-alice"));
+}
+
+#[test]
+fn requests_run_by_their_order() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let _mock = server
+        .mock("GET", mockito::Matcher::Any)
+        .with_status(200)
+        .expect(4)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @order(2)
+        get /last
+
+        get /first
+
+        @order(1)
+        get /third
+
+        get /second
+    "#;
+
+    let program = Program::from(code);
+    let program = program.interpret(&env).unwrap();
+
+    let urls: Vec<_> = program
+        .run_ureq(None)
+        .into_iter()
+        .map(|(id, _)| id.url_or_name.trim_start_matches(&server.url()).to_string())
+        .collect();
+
+    assert_eq!(urls, ["/first", "/second", "/third", "/last"]);
+}
+
+#[test]
+fn order_must_be_a_number() {
+    let code = r#"
+        @order("first")
+        get http://localhost
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn each_makes_a_request_per_element() {
+    let code = r#"
+        let ids = ["1", "2"]
+
+        @each(ids)
+        @var(path, `/users/${item}`)
+        get `http://localhost${path}`
+
+        get http://localhost/after
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let urls: Vec<_> = program
+        .items
+        .iter()
+        .map(|item| item.request.url.as_str())
+        .collect();
+
+    assert_eq!(
+        urls,
+        [
+            "http://localhost/users/1",
+            "http://localhost/users/2",
+            "http://localhost/after"
+        ]
+    );
+}
+
+#[test]
+fn input_is_bound_to_the_given_value() {
+    let code = r#"
+        post http://localhost/users {
+            body json(input)
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let input = serde_json::json!({ "ids": ["1", "2"] });
+
+    let program = Program::from(code)
+        .interpret_with_input(&env, input.clone().into())
+        .unwrap();
+
+    let body = program.items[0].request.body.as_deref().unwrap();
+
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(body).unwrap(),
+        input
+    );
+}
+
+#[test]
+fn missing_env_variables_are_placeholders_when_interpreting_partially() {
+    let code = r#"
+        get `${env("HOST")}/users` {
+            header "Authorization" `Bearer ${env("TOKEN")}`
+        }
+    "#;
+
+    let env = new_env_with_vars(&[("HOST", "http://localhost")]);
+
+    assert!(Program::from(code).interpret(&env).is_err());
+
+    let program = Program::from(code).interpret_partially(&env).unwrap();
+
+    let request = &program.items[0].request;
+
+    assert_eq!(request.url, "http://localhost/users");
+    assert_eq!(request.headers[0].value, "Bearer ${env:TOKEN}");
+}
+
+#[test]
+fn env_calls_fall_back_on_their_second_argument() {
+    let code = r#"
+        get `${env("HOST")}/users` {
+            header "Authorization" `Bearer ${env("TOKEN", "dev-token")}`
+            header "X-Host" env("HOST", "unused")
+        }
+    "#;
+
+    let env = new_env_with_vars(&[("HOST", "http://localhost")]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let request = &program.items[0].request;
+
+    assert_eq!(request.url, "http://localhost/users");
+    assert_eq!(request.headers[0].value, "Bearer dev-token");
+    assert_eq!(request.headers[1].value, "http://localhost");
+}
+
+#[test]
+fn env_calls_take_at_most_two_arguments() {
+    let code = r#"get env("HOST", "a", "b")"#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+fn interpret_sending_bound_requests<'source>(
+    code: &'source str,
+    env: &Environment,
+    run_options: RunOptions,
+) -> anyhow::Result<rested::interpreter::ir::Program<'source>> {
+    let options = rested::interpreter::InterpretOptions {
+        send_bound_requests: Some(run_options),
+        ..Default::default()
+    };
+
+    rested::interpreter::interpret_program_with_options(code, env, options)
+}
+
+#[test]
+fn requests_bound_with_let_bind_their_json_responses() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server
+        .mock("GET", "/user")
+        .with_status(200)
+        .with_body(r#"{ "id": 7 }"#)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        let user = get /user
+
+        post /audit {
+            body json(user)
+        }
+    "#;
+
+    let program = interpret_sending_bound_requests(code, &env, RunOptions::default()).unwrap();
+
+    mock.assert();
+
+    let body = program.items[0].request.body.as_deref().unwrap();
+
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(body).unwrap(),
+        serde_json::json!({ "id": 7 })
+    );
+}
+
+#[test]
+fn only_the_requests_bound_with_let_that_a_request_uses_are_sent_when_asked_to() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let session = server
+        .mock("GET", "/session")
+        .with_status(200)
+        .with_body(r#"{ "token": "abc" }"#)
+        .create();
+    let stats = server.mock("GET", "/stats").expect(0).create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        let session = get /session
+        let auth = json(session)
+        let stats = get /stats
+
+        post /audit {
+            header "Authorization" auth
+        }
+
+        post /stats {
+            body json(stats)
+        }
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let options = rested::interpreter::InterpretOptions {
+        send_bound_requests: Some(RunOptions::default()),
+        only_send_bound_requests_used_by: Some(vec![program.items[0].span.start]),
+        ..Default::default()
+    };
+
+    let program = rested::interpreter::interpret_program_with_options(code, &env, options).unwrap();
+
+    session.assert();
+    stats.assert();
+
+    assert_eq!(
+        program.items[0].request.headers[0].value,
+        r#"{"token":"abc"}"#
+    );
+    assert_eq!(program.items[1].request.body.as_deref(), Some("null"));
+}
+
+#[test]
+fn requests_bound_with_let_are_only_sent_when_asked_to() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server.mock("GET", "/user").expect(0).create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        let user = get /user
+
+        post /audit {
+            body json(user)
+        }
+    "#;
+
+    let program = rested::interpreter::interpret_program(code, &env).unwrap();
+
+    mock.assert();
+    assert_eq!(program.items[0].request.body.as_deref(), Some("null"));
+}
+
+#[test]
+fn requests_bound_with_let_are_retried_like_the_rest() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server
+        .mock("GET", "/user")
+        .with_status(503)
+        .expect(3)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        let user = get /user
+    "#;
+
+    let run_options = RunOptions {
+        retry: Some(RetryPolicy {
+            status_codes: [503].into(),
+            retries: 2,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    assert!(interpret_sending_bound_requests(code, &env, run_options).is_err());
+
+    mock.assert();
+}
+
+#[test]
+fn requests_bound_with_let_fail_the_binding_when_they_fail() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server.mock("GET", "/user").with_status(404).create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        let user = get /user
+    "#;
+
+    assert!(interpret_sending_bound_requests(code, &env, RunOptions::default()).is_err());
+
+    mock.assert();
+}
+
+#[test]
+fn requests_bound_with_let_are_not_sent_by_interpret() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let mock = server.mock("GET", "/user").expect(0).create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        let user = get /user
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    mock.assert();
+
+    assert!(matches!(
+        program.let_bindings.get("user"),
+        Some(rested::interpreter::value::Value::Null)
+    ));
+}
+
+#[test]
+fn each_needs_an_array() {
+    let code = r#"
+        @each("1, 2")
+        get `http://localhost/${item}`
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn raw_strings_are_not_interpolated() {
+    let code = r#"
+        let home = "/root"
+
+        post http://localhost/script {
+          body r`echo ${home} \n`
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(
+        program.items[0].request.body.as_deref(),
+        Some(r#"echo ${home} \n"#)
+    );
+}
+
+#[test]
+fn set_allow_insecure_localhost() {
+    let env = Environment::in_memory();
+
+    let program = Program::from("set ALLOW_INSECURE_LOCALHOST true")
+        .interpret(&env)
+        .unwrap();
+
+    assert!(program.allow_insecure_localhost);
+
+    let program = Program::from("get http://localhost")
+        .interpret(&env)
+        .unwrap();
+
+    assert!(!program.allow_insecure_localhost);
+}
+
+#[test]
+fn set_allow_insecure_localhost_requires_a_bool() {
+    let env = Environment::in_memory();
+
+    let code = r#"set ALLOW_INSECURE_LOCALHOST "yes""#;
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn capture_env_saves_a_value_from_the_response_to_the_env_file() {
+    let mut server = mockito::Server::new();
+
+    let env_file = std::env::temp_dir().join("rested_capture_env.env.rd.json");
+    let _ = std::fs::remove_file(&env_file);
+
+    let mut env = Environment::new(&env_file).unwrap();
+    env.set_variable("b_url".to_string(), server.url()).unwrap();
+
+    let login = server
+        .mock("POST", "/login")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{ "data": { "token": "t0k3n", "expires_in": 3600 } }"#)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @capture_env(TOKEN, "$.data.token")
+        post /login
+
+        @capture_env("TTL", "$.data.expires_in")
+        post /login
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let responses = program.run_ureq_with_options(
+        None,
+        RunOptions {
+            env: Some(env),
+            quiet: true,
+            ..Default::default()
+        },
+    );
+
+    login.expect(2).assert();
+    assert!(matches!(
+        responses[..],
+        [(_, RunResponse::Success(_)), (_, RunResponse::Success(_))]
+    ));
+
+    let env = Environment::new(&env_file).unwrap();
+
+    assert_eq!(
+        env.get_variable_value(&"TOKEN".to_string())
+            .map(String::as_str),
+        Some("t0k3n")
+    );
+    assert_eq!(
+        env.get_variable_value(&"TTL".to_string())
+            .map(String::as_str),
+        Some("3600")
+    );
+}
+
+#[test]
+fn capture_env_fails_the_request_when_the_path_is_missing() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let login = server
+        .mock("POST", "/login")
+        .with_status(200)
+        .with_body(r#"{ "data": {} }"#)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @capture_env(TOKEN, "$.data.token")
+        post /login
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let responses = program.run_ureq_with_options(
+        None,
+        RunOptions {
+            env: Some(new_env_with_vars(&[])),
+            quiet: true,
+            ..Default::default()
+        },
+    );
+
+    login.assert();
+    assert!(
+        matches!(&responses[..], [(_, RunResponse::Failure(error))] if error.contains("nothing at $.data.token"))
+    );
+}
+
+#[test]
+fn capture_env_rejects_malformed_paths() {
+    let env = Environment::in_memory();
+
+    let code = r#"
+        @capture_env(TOKEN, "data.token")
+        get http://localhost/login
+    "#;
+
+    let err = Program::from(code).interpret(&env).unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn failures_are_told_apart_by_why_they_failed() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let missing = server
+        .mock("GET", "/missing")
+        .with_status(404)
+        .with_body(r#"{ "error": "not found" }"#)
+        .create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        get /missing
+
+        // nothing listens on port 1
+        get http://127.0.0.1:1/refused
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let responses = program.run_ureq(None);
+
+    missing.assert();
+    assert!(matches!(
+        &responses[..],
+        [
+            (_, RunResponse::ErrorStatus { status: 404, body }),
+            (_, RunResponse::Transport(_)),
+        ] if body == r#"{ "error": "not found" }"#
+    ));
+}
+
+#[test]
+fn requests_that_take_longer_than_their_timeout_fail() {
+    // accepts connections, but never responds
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let env = new_env_with_vars(&[("b_url", &url)]);
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        @timeout(100)
+        get /slow
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(
+        program.items[0].request.timeout,
+        Some(std::time::Duration::from_millis(100))
+    );
+
+    let responses = program.run_ureq(None);
+
+    assert!(matches!(
+        &responses[..],
+        [(_, RunResponse::Transport(message))] if message.ends_with("timed out after 100ms")
+    ));
+}
+
+#[test]
+fn timeouts_need_a_positive_number_of_milliseconds() {
+    let env = new_env_with_vars(&[]);
+
+    let code = r#"
+        @timeout("5s")
+        get http://localhost/slow
+    "#;
+
+    assert!(Program::from(code).interpret(&env).is_err());
+}
+
+#[test]
+fn groups_last_until_the_next_group() {
+    let env = Environment::in_memory();
+
+    let code = r#"
+        get http://localhost/health
+
+        @group("Auth")
+        post http://localhost/login
+
+        @name("logout")
+        post http://localhost/logout
+
+        @group("Items")
+        get http://localhost/items
+    "#;
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    let groups: Vec<_> = program
+        .items
+        .iter()
+        .map(|item| item.group.as_deref())
+        .collect();
+
+    assert_eq!(groups, [None, Some("Auth"), Some("Auth"), Some("Items")]);
+}
+
+#[test]
+fn dry_runs_send_nothing() {
+    let mut server = mockito::Server::new();
+    let env = new_env_with_vars(&[("b_url", &server.url())]);
+
+    let api = server.mock("POST", "/api").expect(0).create();
+    let me = server.mock("GET", "/me").expect(0).create();
+
+    let code = r#"
+        set BASE_URL env("b_url")
+
+        let me = get /me
+
+        @expect(201)
+        @assert_contains("created")
+        post /api {
+           body json({ me: me })
+        }
+    "#;
+
+    let run_options = RunOptions {
+        dry_run: true,
+        quiet: true,
+        ..Default::default()
+    };
+
+    let options = rested::interpreter::InterpretOptions {
+        send_bound_requests: Some(run_options.clone()),
+        ..Default::default()
+    };
+
+    let program = rested::interpreter::interpret_program_with_options(code, &env, options).unwrap();
+
+    let responses = program.run_ureq_with_options(None, run_options);
+
+    api.assert();
+    me.assert();
+    assert!(matches!(
+        &responses[..],
+        [(_, RunResponse::Success(body))] if body == DRY_RUN_BODY
+    ));
+}
+
+#[test]
+fn stdin_calls_are_empty_unless_reading_stdin() {
+    let code = r#"
+        post http://localhost {
+           body stdin()
+        }
+    "#;
+
+    let env = new_env_with_vars(&[]);
+
+    let program = Program::from(code).interpret(&env).unwrap();
+
+    assert_eq!(program.items[0].request.body.as_deref(), Some(""));
+
+    let err = Program::from("get http://localhost { body stdin(1) }")
+        .interpret(&env)
+        .unwrap_err();
+
+    assert_debug_snapshot!(err);
+}
+
+#[test]
+fn stdin_calls_are_errors_when_the_script_came_from_stdin() {
+    let code = "post http://localhost { body stdin() }";
+
+    let env = new_env_with_vars(&[]);
+
+    let options = rested::interpreter::InterpretOptions {
+        stdin: rested::interpreter::Stdin::Script,
+        ..Default::default()
+    };
+
+    let err = rested::interpreter::interpret_program_with_options(code, &env, options).unwrap_err();
+
+    assert!(format!("{err:?}").contains("stdin was already read for the script itself"));
+}
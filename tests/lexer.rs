@@ -23,6 +23,26 @@ delete /api {}
     );
 }
 
+#[test]
+fn lex_query_variables() {
+    assert_lexes!(
+        r#"
+query "{ me { id } }"
+variables { id: 1 }
+"#
+    );
+}
+
+#[test]
+fn lex_head_options() {
+    assert_lexes!(
+        r#"
+head /api
+options /api {}
+"#
+    );
+}
+
 #[test]
 fn lex_string_literals() {
     assert_lexes!(r#""hello""#);
@@ -56,11 +76,21 @@ fn lex_bools() {
     assert_lexes!("true false");
 }
 
+#[test]
+fn lex_identifiers_with_digits() {
+    assert_lexes!("read_bytes_base64 v2 2b");
+}
+
 #[test]
 fn lex_numbers() {
     assert_lexes!("123124 1.0 23.8635");
 }
 
+#[test]
+fn lex_negative_and_exponent_numbers() {
+    assert_lexes!("-42 3.14 2e10 1.5E-3 1e+5 2e x-1");
+}
+
 #[test]
 fn lex_get_url() {
     assert_lexes!("get http://localhost");
@@ -379,3 +409,94 @@ fn lex_eof_position_with_newlines() {
         }
     );
 }
+
+#[test]
+fn tokens_serialize_with_end_positions_and_errors() {
+    let tokens = rested::lexer::tokenize("get /api ~");
+
+    insta::assert_snapshot!(serde_json::to_string_pretty(&tokens).unwrap());
+}
+
+#[test]
+fn lex_escaped_backticks_in_template_strings() {
+    assert_lexes!(r#"`say \`hi\``"#);
+}
+
+#[test]
+fn lex_escaped_dollar_signs_in_template_strings() {
+    assert_lexes!(r#"`cost: \${price} is ${price}\$`"#);
+}
+
+#[test]
+fn lex_escaped_backslashes_in_template_strings() {
+    assert_lexes!(r#"`C:\\` `a\\${b}`"#);
+}
+
+#[test]
+fn lex_escaped_quotes_in_string_literals() {
+    assert_lexes!(r#""say \"hi\"" 'it\'s' "\\""#);
+}
+
+#[test]
+fn lex_single_quoted_string_literals() {
+    assert_lexes!("'hello'");
+
+    assert_lexes!("'foo");
+
+    assert_lexes!(r#"'{"a": 1}' ''"#);
+}
+
+#[test]
+fn lex_raw_strings_without_interpolation() {
+    assert_lexes!(r#"body r`echo ${HOME} \n`"#);
+}
+
+#[test]
+fn lex_unfinished_raw_strings() {
+    assert_lexes!(r#"r`echo ${HOME}"#);
+}
+
+#[test]
+fn lex_illegal_characters_whole() {
+    assert_lexes!("let a = 日");
+    assert_lexes!("get /a %");
+}
+
+/// Lexes random mixes of the characters that the lexer treats specially, checking that it
+/// never panics, and always gets to the end of the input.
+#[test]
+fn lex_random_inputs_without_panicking() {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let pieces = [
+        "`", "${", "}", "{", "\"", "\\", "$", "r`", "r", "/", "//", "#!", "#", "\n", " ", "get",
+        "a", "1", "é", "日", "%", "&", "@", "(", ")", ",", ":", "\t",
+    ];
+
+    let mut rng = StdRng::seed_from_u64(1730);
+
+    for _ in 0..20_000 {
+        let len = rng.gen_range(0..24);
+        let input: String = (0..len)
+            .map(|_| pieces[rng.gen_range(0..pieces.len())])
+            .collect();
+
+        let result = std::panic::catch_unwind(|| {
+            let mut lexer = rested::lexer::Lexer::new(&input);
+
+            // every token takes up at least a byte, save for the empty tokens at the end
+            for _ in 0..=input.len() + 1 {
+                if lexer.next_token().kind == rested::lexer::TokenKind::End {
+                    return true;
+                }
+            }
+
+            false
+        });
+
+        assert!(
+            matches!(result, Ok(true)),
+            "lexing {input:?} panicked or didn't end"
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use insta::assert_ron_snapshot;
 
+use rested::lexer::locations::GetSpan;
+use rested::parser::ast::{ConstantDeclaration, VariableDeclaration};
+use rested::parser::ast_queries::Declaration;
 use rested::parser::Parser;
 
 macro_rules! assert_ast {
@@ -306,3 +309,214 @@ let o = {
 }"#
     );
 }
+
+#[test]
+fn request_docs_are_the_leading_line_comments() {
+    let code = r#"
+// not part of the docs
+
+// Fetches the user
+// by their id
+@name("user")
+get /users/1
+
+get /users
+"#;
+    let program = Parser::new(code).parse();
+
+    let requests: Vec<_> = program.requests().collect();
+
+    assert_eq!(
+        program.request_docs(requests[0]).as_deref(),
+        Some("Fetches the user\nby their id")
+    );
+    assert_eq!(program.request_docs(requests[1]), None);
+}
+
+#[test]
+fn requests_and_declarations_are_queried_in_order() {
+    let code = r#"
+set BASE_URL "http://localhost"
+let id = "1"
+
+get /users
+// a comment
+post /users
+
+let name = "yam"
+"#;
+    let program = Parser::new(code).parse();
+
+    let endpoints: Vec<_> = program
+        .requests()
+        .map(|request| request.endpoint.span().start.line)
+        .collect();
+
+    assert_eq!(endpoints, [4, 6]);
+
+    let declarations: Vec<_> = program
+        .declarations()
+        .map(|declaration| match declaration {
+            Declaration::Let(VariableDeclaration { identifier, .. }) => {
+                format!("let {}", identifier.get().unwrap().text)
+            }
+            Declaration::Set(ConstantDeclaration { identifier, .. }) => {
+                format!("set {}", identifier.get().unwrap().text)
+            }
+        })
+        .collect();
+
+    assert_eq!(declarations, ["set BASE_URL", "let id", "let name"]);
+}
+
+#[test]
+fn parse_conditional_expression() {
+    assert_ast!(
+        r#"
+let debug = env("DEBUG") ? "1" : "0""#
+    );
+}
+
+#[test]
+fn parse_query_statements() {
+    assert_ast!(
+        r#"
+get /api {
+  query "sort" "asc"
+  query "page" env("page")
+}
+graphql /graphql {
+  query "{ me { id } }"
+}"#
+    );
+}
+
+#[test]
+fn parse_query_and_variables_only_at_the_start_of_statements() {
+    assert_ast!(
+        r#"
+let query = "{ me { id } }"
+let variables = { id: 1 }
+graphql /graphql {
+  header "a" query
+  query query
+  variables variables
+}"#
+    );
+}
+
+#[test]
+fn parse_string_queries_followed_by_variables() {
+    assert_ast!(
+        r#"
+graphql /graphql {
+  query "{ me { id } }"
+  variables { id: 1 }
+}"#
+    );
+}
+
+#[test]
+fn parse_query_values_only_on_the_same_line() {
+    assert_ast!(
+        r#"
+let page = 1
+graphql /graphql {
+  query "page" page
+  query "{ me { id } }"
+  variables { id: 1 }
+}"#
+    );
+}
+
+#[test]
+fn parse_head_and_options_only_as_methods() {
+    assert_ast!(
+        r#"
+head /api
+let options = "x"
+options /api {
+  body options
+}
+let h = head
+head options
+let response = options /api"#
+    );
+}
+
+#[test]
+fn parse_headers_declarations() {
+    assert_ast!(
+        r#"
+headers {
+  header "Accept" "application/json"
+}
+let headers = "not a declaration""#
+    );
+}
+
+#[test]
+fn parse_form_bodies() {
+    assert_ast!(
+        r#"
+post /login {
+  body form { "user": env("u"), pass: "p" }
+}
+post /raw {
+  body form
+}"#
+    );
+}
+
+#[test]
+fn parse_requests_bound_with_let() {
+    assert_ast!(
+        r#"
+let user = get /user {
+  header "Accept" "application/json"
+}
+let token = post /login"#
+    );
+}
+
+#[test]
+fn parse_requests_without_a_method_as_gets() {
+    assert_ast!(
+        r#"
+http://localhost/health
+@name("users")
+/users {}"#
+    );
+}
+
+#[test]
+fn env_vars_are_the_string_arguments_of_env_calls() {
+    let program = Parser::new(
+        r#"
+set BASE_URL env("base_url")
+let name = "token"
+
+post /api {
+    header "Authorization" `Bearer ${env("token")}`
+    header "X-Dynamic" env(name)
+    body json({ user: env("user"), again: env("base_url") })
+}
+"#,
+    )
+    .parse();
+
+    assert_eq!(
+        program.env_vars().into_iter().collect::<Vec<_>>(),
+        ["base_url", "token", "user"]
+    );
+}
+
+#[test]
+fn parses_a_bare_attribute_prefix_without_swallowing_the_request_after_it() {
+    assert_ast!(
+        r#"
+@
+get /api
+"#
+    );
+}